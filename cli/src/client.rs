@@ -0,0 +1,255 @@
+//! Blocking client for the `/api` routes.
+
+use protocol::{
+    ApiVersion,
+    ClippyRequest,
+    CompileRequest,
+    CompileResponse,
+    FormatRequest,
+    MacroExpandRequest,
+    QuotaKind,
+    SandboxStructure,
+    SessionDetails,
+    ToolOutput,
+    PROTOCOL_VERSION,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::{self, Read};
+use url::Url;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid server url: {0}")]
+    InvalidServerUrl(url::ParseError),
+    #[error("unable to reach the server: {0}")]
+    Transport(ureq::Error),
+    #[error("{}", describe_error(.0))]
+    Api(protocol::Error),
+    #[error("request failed with status {0}")]
+    Status(u16),
+    #[error("unable to read the response: {0}")]
+    InvalidResponse(io::Error),
+    #[error(
+        "the server speaks protocol {0} which isn't compatible with {}",
+        PROTOCOL_VERSION
+    )]
+    IncompatibleProtocol(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn describe_error(err: &protocol::Error) -> String {
+    use protocol::Error::*;
+    match err {
+        InternalError { message } => format!("something went wrong on the server: {}", message),
+        InvalidRequest { message } => format!("the request is invalid: {}", message),
+        InvalidPath => String::from("the path is invalid"),
+        SessionNotFound => String::from("the session doesn't exist anymore"),
+        NotSessionOwner => String::from("only the owner of the session can do this"),
+        NotAdmin => String::from("the admin token is invalid"),
+        SandboxFileNotFound => String::from("the file doesn't exist"),
+        FileConflict { .. } => String::from("the file was changed somewhere else"),
+        PreconditionRequired => String::from("the file can't be saved without its version"),
+        ShuttingDown => String::from("the server is shutting down"),
+        QuotaExceeded { quota, limit } => match quota {
+            QuotaKind::FileSize => format!("files can't be larger than {} bytes", limit),
+            QuotaKind::FileCount => format!("the sandbox can't contain more than {} files", limit),
+            QuotaKind::TotalSize => format!(
+                "the files of the sandbox can't be larger than {} bytes in total",
+                limit
+            ),
+            QuotaKind::BuildSize => format!(
+                "the build output is larger than {} bytes and was removed",
+                limit
+            ),
+        },
+        RateLimited { retry_after } => {
+            format!("too many requests, try again in {} seconds", retry_after)
+        }
+        ToolUnavailable => String::from("the tools are unavailable right now"),
+        ToolTimedOut { timeout_ms } => format!(
+            "the tool didn't finish within {} seconds",
+            timeout_ms / 1000
+        ),
+        BuildMissing => String::from("compile the code first"),
+    }
+}
+
+/// Content of a sandbox file together with its version.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionedFile {
+    pub content: Vec<u8>,
+    /// Entity tag of the content.
+    pub etag: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Client {
+    base: Url,
+    agent: ureq::Agent,
+}
+impl Client {
+    pub fn new(server: &str) -> Result<Self> {
+        let base = Url::parse(server).map_err(Error::InvalidServerUrl)?;
+        if base.cannot_be_a_base() {
+            return Err(Error::InvalidServerUrl(
+                url::ParseError::RelativeUrlWithCannotBeABaseBase,
+            ));
+        }
+
+        Ok(Self {
+            base,
+            agent: ureq::agent(),
+        })
+    }
+
+    /// Url of the api route made up of `segments`.
+    /// The segments are percent-encoded.
+    fn api_url<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> String {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("base url was checked in the constructor")
+            .pop_if_empty()
+            .push("api")
+            .extend(segments);
+        url.to_string()
+    }
+
+    /// Url of a page of the frontend.
+    pub fn page_url(&self, path: &str) -> String {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("base url was checked in the constructor")
+            .pop_if_empty()
+            .extend(path.split('/'));
+        url.to_string()
+    }
+
+    fn request(&self, method: &str, url: &str, owner_token: Option<&str>) -> ureq::Request {
+        let mut req = self.agent.request(method, url);
+        if let Some(token) = owner_token {
+            req.set("Authorization", &format!("Bearer {}", token));
+        }
+        req
+    }
+
+    fn get_json<Resp: DeserializeOwned>(&self, url: &str) -> Result<Resp> {
+        let resp = check_status(self.request("GET", url, None).call())?;
+        resp.into_json_deserialize().map_err(Error::InvalidResponse)
+    }
+
+    fn post_json<Resp: DeserializeOwned>(
+        &self,
+        url: &str,
+        owner_token: Option<&str>,
+        body: &impl Serialize,
+    ) -> Result<Resp> {
+        let body = serde_json::to_value(body).expect("requests are always valid JSON");
+        let resp = check_status(self.request("POST", url, owner_token).send_json(body))?;
+        resp.into_json_deserialize().map_err(Error::InvalidResponse)
+    }
+
+    /// Make sure the server speaks a compatible version of the protocol.
+    pub fn check_version(&self) -> Result<ApiVersion> {
+        let version: ApiVersion = self.get_json(&self.api_url(vec!["version"]))?;
+        if protocol::is_compatible(&version.protocol) {
+            Ok(version)
+        } else {
+            Err(Error::IncompatibleProtocol(version.protocol))
+        }
+    }
+
+    pub fn create_session(&self) -> Result<SessionDetails> {
+        self.post_json(&self.api_url(vec!["sandbox"]), None, &())
+    }
+
+    pub fn get_structure(&self, id: &str) -> Result<SandboxStructure> {
+        self.get_json(&self.api_url(vec![id, "files"]))
+    }
+
+    /// Get a file of the sandbox or `None` if it doesn't exist.
+    pub fn get_file(&self, id: &str, path: &str) -> Result<Option<VersionedFile>> {
+        let url = self.api_url(vec![id, "files"].into_iter().chain(path.split('/')));
+        let resp = match check_status(self.request("GET", &url, None).call()) {
+            Ok(resp) => resp,
+            Err(Error::Api(protocol::Error::SandboxFileNotFound)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let etag = resp.header("ETag").map(String::from);
+        let mut content = Vec::new();
+        resp.into_reader()
+            .read_to_end(&mut content)
+            .map_err(Error::InvalidResponse)?;
+        Ok(Some(VersionedFile { content, etag }))
+    }
+
+    /// Save a file.
+    /// `etag` is the entity tag of the version that's replaced or `None` for
+    /// new files.
+    pub fn upload_file(
+        &self,
+        id: &str,
+        owner_token: &str,
+        path: &str,
+        content: &[u8],
+        etag: Option<&str>,
+    ) -> Result<()> {
+        let url = self.api_url(vec![id, "files"].into_iter().chain(path.split('/')));
+        let mut req = self.request("PUT", &url, Some(owner_token));
+        match etag {
+            Some(etag) => req.set("If-Match", etag),
+            None => req.set("If-None-Match", "*"),
+        };
+        check_status(req.send_bytes(content)).map(|_| ())
+    }
+
+    pub fn compile(
+        &self,
+        id: &str,
+        owner_token: &str,
+        req: &CompileRequest,
+    ) -> Result<CompileResponse> {
+        self.post_json(&self.api_url(vec![id, "compile"]), Some(owner_token), req)
+    }
+
+    pub fn format(&self, id: &str, owner_token: &str, req: &FormatRequest) -> Result<ToolOutput> {
+        self.post_json(&self.api_url(vec![id, "format"]), Some(owner_token), req)
+    }
+
+    pub fn clippy(&self, id: &str, owner_token: &str, req: &ClippyRequest) -> Result<ToolOutput> {
+        self.post_json(&self.api_url(vec![id, "clippy"]), Some(owner_token), req)
+    }
+
+    pub fn macro_expand(
+        &self,
+        id: &str,
+        owner_token: &str,
+        req: &MacroExpandRequest,
+    ) -> Result<ToolOutput> {
+        self.post_json(
+            &self.api_url(vec![id, "macro-expand"]),
+            Some(owner_token),
+            req,
+        )
+    }
+}
+
+/// Turn responses with an error status into an `Error`.
+fn check_status(resp: ureq::Response) -> Result<ureq::Response> {
+    if resp.synthetic() {
+        let err = resp
+            .into_synthetic_error()
+            .expect("synthetic responses contain an error");
+        return Err(Error::Transport(err));
+    }
+    if !resp.error() {
+        return Ok(resp);
+    }
+
+    let status = resp.status();
+    match resp.into_json_deserialize() {
+        Ok(err) => Err(Error::Api(err)),
+        Err(_) => Err(Error::Status(status)),
+    }
+}
@@ -0,0 +1,294 @@
+//! Command-line client for the playground.
+
+use anyhow::{bail, Context};
+use client::Client;
+use output::print_tool_output;
+use protocol::{
+    Channel,
+    ClippyRequest,
+    CompileRequest,
+    FormatRequest,
+    MacroExpandRequest,
+    Mode,
+    ToolOutput,
+};
+use session::Session;
+use std::{
+    fs,
+    io,
+    path::{Component, Path, PathBuf},
+    process,
+};
+use structopt::StructOpt;
+
+mod client;
+mod output;
+mod session;
+
+const DEFAULT_SERVER: &str = "http://localhost:8000";
+
+/// Directories of the sandbox which are synchronised.
+const SANDBOX_DIRS: &[&str] = &["public", "src"];
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Command-line client for the Yew Playground")]
+struct Args {
+    /// Url of the server.
+    /// Defaults to the server of the current session.
+    #[structopt(long, env = "PLAYGROUND_SERVER")]
+    server: Option<String>,
+    /// File which stores the current session.
+    #[structopt(long, default_value = ".playground.json", parse(from_os_str))]
+    session_file: PathBuf,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Create a new session and make it the current one.
+    New {
+        /// Directory whose "src" and "public" directories are uploaded to
+        /// the new session.
+        #[structopt(long, parse(from_os_str))]
+        template: Option<PathBuf>,
+    },
+    /// Upload the "src" and "public" directories of `dir`.
+    Push {
+        #[structopt(default_value = ".", parse(from_os_str))]
+        dir: PathBuf,
+    },
+    /// Build the crate.
+    Compile {
+        #[structopt(long)]
+        release: bool,
+        /// Use the nightly toolchain.
+        #[structopt(long)]
+        nightly: bool,
+    },
+    /// Format the code.
+    /// Use `pull` to get the formatted files.
+    Fmt,
+    /// Run clippy.
+    Clippy,
+    /// Expand the macros.
+    Expand,
+    /// Print the links to share the session.
+    Share,
+    /// Download the files of the session into `dir`.
+    Pull {
+        #[structopt(default_value = ".", parse(from_os_str))]
+        dir: PathBuf,
+    },
+}
+
+/// Connect to the server and make sure it's compatible.
+fn connect(server: &str) -> anyhow::Result<Client> {
+    let client = Client::new(server)?;
+    client.check_version()?;
+    Ok(client)
+}
+
+/// Paths of all files in `dir` relative to `base` using '/' as the separator.
+fn collect_files(base: &Path, dir: &Path, files: &mut Vec<String>) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("unable to read {}", dir.display())),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(base, &path, files)?;
+            continue;
+        }
+
+        let rel = path
+            .strip_prefix(base)
+            .expect("entries are located in the base");
+        let rel: Vec<&str> = rel
+            .iter()
+            .map(|component| {
+                component
+                    .to_str()
+                    .with_context(|| format!("{} isn't valid UTF-8", path.display()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        files.push(rel.join("/"));
+    }
+    Ok(())
+}
+
+/// Upload the files of `dir` which differ from the files in the sandbox.
+fn push(client: &Client, session: &Session, dir: &Path) -> anyhow::Result<()> {
+    let mut paths = Vec::new();
+    for name in SANDBOX_DIRS {
+        collect_files(dir, &dir.join(name), &mut paths)?;
+    }
+    if paths.is_empty() {
+        bail!("{} doesn't contain any files to push", dir.display());
+    }
+    paths.sort();
+
+    for path in paths {
+        let content =
+            fs::read(dir.join(&path)).with_context(|| format!("unable to read {}", path))?;
+        let current = client.get_file(&session.id, &path)?;
+        if matches!(&current, Some(file) if file.content == content) {
+            continue;
+        }
+
+        let etag = current.as_ref().and_then(|file| file.etag.as_deref());
+        client
+            .upload_file(&session.id, &session.owner_token, &path, &content, etag)
+            .with_context(|| format!("unable to push {}", path))?;
+        println!("pushed {}", path);
+    }
+    Ok(())
+}
+
+/// Path of a file of the sandbox inside `dir`.
+/// The path comes from the server, so it must not leave `dir`.
+fn local_path(dir: &Path, path: &str) -> anyhow::Result<PathBuf> {
+    let rel = Path::new(path);
+    let only_normal = rel
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !only_normal || rel.components().next().is_none() {
+        bail!("the server sent the invalid path {:?}", path);
+    }
+    Ok(dir.join(rel))
+}
+
+/// Download all files of the sandbox into `dir`.
+fn pull(client: &Client, session: &Session, dir: &Path) -> anyhow::Result<()> {
+    let structure = client.get_structure(&session.id)?;
+    for path in structure.file_paths() {
+        let file = match client.get_file(&session.id, path)? {
+            Some(file) => file,
+            // removed in the meantime
+            None => continue,
+        };
+
+        let target = local_path(dir, path)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("unable to create {}", parent.display()))?;
+        }
+        fs::write(&target, &file.content)
+            .with_context(|| format!("unable to write {}", target.display()))?;
+        println!("pulled {}", path);
+    }
+    Ok(())
+}
+
+/// Run the command.
+/// Returns whether it succeeded.
+fn run(args: Args) -> anyhow::Result<bool> {
+    if let Command::New { template } = &args.command {
+        let server = args.server.as_deref().unwrap_or(DEFAULT_SERVER);
+        let client = connect(server)?;
+        let session = Session::new(server.to_owned(), client.create_session()?);
+        session.save(&args.session_file)?;
+        println!("created session {}", session.id);
+
+        if let Some(template) = template {
+            push(&client, &session, template)?;
+        }
+        return Ok(true);
+    }
+
+    let session = Session::load(&args.session_file)?;
+    let client = connect(args.server.as_deref().unwrap_or(&session.server))?;
+    let output: ToolOutput = match args.command {
+        Command::New { .. } => unreachable!("handled above"),
+        Command::Push { dir } => {
+            push(&client, &session, &dir)?;
+            return Ok(true);
+        }
+        Command::Compile { release, nightly } => {
+            let req = CompileRequest {
+                channel: if nightly {
+                    Channel::Nightly
+                } else {
+                    Channel::Stable
+                },
+                mode: if release { Mode::Release } else { Mode::Debug },
+                edition: None,
+                backtrace: false,
+                opt_level: None,
+                lto: None,
+                codegen_units: None,
+                wasm_opt: None,
+            };
+            let resp = client.compile(&session.id, &session.owner_token, &req)?;
+            if let Some(size) = resp.wasm_size {
+                eprintln!("wasm size: {} bytes", size);
+            }
+            resp.output
+        }
+        Command::Fmt => client.format(
+            &session.id,
+            &session.owner_token,
+            &FormatRequest { edition: None },
+        )?,
+        Command::Clippy => client.clippy(
+            &session.id,
+            &session.owner_token,
+            &ClippyRequest { edition: None },
+        )?,
+        Command::Expand => client.macro_expand(
+            &session.id,
+            &session.owner_token,
+            &MacroExpandRequest { edition: None },
+        )?,
+        Command::Share => {
+            println!("editor:  {}", client.page_url(&format!("s/{}", session.id)));
+            println!("preview: {}", session.public_url);
+            return Ok(true);
+        }
+        Command::Pull { dir } => {
+            pull(&client, &session, &dir)?;
+            return Ok(true);
+        }
+    };
+
+    print_tool_output(&output);
+    Ok(output.success)
+}
+
+fn main() {
+    let code = match run(Args::from_args()) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            2
+        }
+    };
+    process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_path_stays_in_dir() {
+        let dir = Path::new("out");
+        assert_eq!(
+            local_path(dir, "src/lib.rs").unwrap(),
+            Path::new("out/src/lib.rs")
+        );
+        for path in &[
+            "",
+            "/etc/passwd",
+            "../lib.rs",
+            "src/../../lib.rs",
+            "./lib.rs",
+        ] {
+            assert!(local_path(dir, path).is_err(), "{:?} was accepted", path);
+        }
+    }
+}
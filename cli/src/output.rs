@@ -0,0 +1,83 @@
+//! Printing the output of tools the same way the console of the frontend
+//! shows it.
+
+use protocol::ToolOutput;
+
+fn format_millis(millis: u64) -> String {
+    format!("{:.2} s", millis as f64 / 1000.0)
+}
+
+fn format_size(size: u64) -> String {
+    const KIB: f64 = 1024.0;
+    let size = size as f64;
+    if size < KIB {
+        format!("{} B", size)
+    } else if size < KIB * KIB {
+        format!("{:.1} KiB", size / KIB)
+    } else {
+        format!("{:.2} MiB", size / (KIB * KIB))
+    }
+}
+
+/// Summary of how the tool exited and the resources it used.
+fn status_line(output: &ToolOutput) -> String {
+    let mut parts = Vec::new();
+    if output.timed_out {
+        parts.push(String::from("Timed out"));
+    } else if let Some(signal) = output.signal {
+        parts.push(format!("Killed by signal {}", signal));
+    } else if let Some(code) = output.exit_code {
+        parts.push(format!("Exited with code {}", code));
+    }
+    parts.push(format!("Took {}", format_millis(output.wall_time_ms)));
+    if let Some(cpu_time) = output.cpu_time_ms {
+        parts.push(format!("CPU time {}", format_millis(cpu_time)));
+    }
+    if let Some(peak_memory) = output.peak_memory {
+        parts.push(format!("Peak memory {}", format_size(peak_memory)));
+    }
+    parts.join(" · ")
+}
+
+/// Print the output streams as they are, including their ANSI escape codes,
+/// followed by the status.
+pub fn print_tool_output(output: &ToolOutput) {
+    const TRUNCATED: &str = "The rest of the output was cut off because it's too large.";
+
+    eprint!("{}", output.stderr);
+    if output.stderr_truncated {
+        eprintln!("{}", TRUNCATED);
+    }
+    print!("{}", output.stdout);
+    if output.stdout_truncated {
+        println!("{}", TRUNCATED);
+    }
+    eprintln!("{}", status_line(output));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_line_matches_the_console() {
+        let output = ToolOutput {
+            exit_code: Some(101),
+            wall_time_ms: 1234,
+            cpu_time_ms: Some(1000),
+            peak_memory: Some(3 * 1024 * 1024),
+            ..ToolOutput::default()
+        };
+        assert_eq!(
+            status_line(&output),
+            "Exited with code 101 · Took 1.23 s · CPU time 1.00 s · Peak memory 3.00 MiB"
+        );
+
+        let output = ToolOutput {
+            timed_out: true,
+            signal: Some(9),
+            ..ToolOutput::default()
+        };
+        assert_eq!(status_line(&output), "Timed out · Took 0.00 s");
+    }
+}
@@ -0,0 +1,44 @@
+//! Session the commands operate on.
+
+use anyhow::Context;
+use protocol::SessionDetails;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Session stored in the session file of the working directory.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Session {
+    /// Url of the server the session lives on.
+    pub server: String,
+    pub id: String,
+    pub public_url: String,
+    /// Token which grants write access.
+    pub owner_token: String,
+}
+impl Session {
+    pub fn new(server: String, details: SessionDetails) -> Self {
+        Self {
+            server,
+            id: details.id,
+            public_url: details.public_url,
+            owner_token: details.owner_token,
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read(path).with_context(|| {
+            format!(
+                "no session found in {}, create one using `new`",
+                path.display()
+            )
+        })?;
+        serde_json::from_slice(&content)
+            .with_context(|| format!("{} isn't a valid session file", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = serde_json::to_vec_pretty(self).expect("sessions are always valid JSON");
+        fs::write(path, content)
+            .with_context(|| format!("unable to write the session to {}", path.display()))
+    }
+}
@@ -3,9 +3,40 @@
 set -euo pipefail
 
 timeout=${PLAYGROUND_TIMEOUT:-10}
+profile=${PLAYGROUND_PROFILE:-dev}
+usage_file=${PLAYGROUND_USAGE_FILE:-/dev/null}
 
-# TODO update cargo.toml according to env variables!
+# `/tmp` is a fresh tmpfs when the root filesystem is read-only.
+cp /playground/tools/Cargo.toml /tmp/Cargo.toml
 
-# Don't use `exec` here. The shell is what prints out the useful
-# "Killed" message
-timeout --signal=KILL "${timeout}" "$@"
+# TODO update the edition in Cargo.toml according to PLAYGROUND_EDITION!
+
+# wasm-pack only reads the `wasm-opt` passes from the manifest.
+if [[ -n "${PLAYGROUND_WASM_OPT:-}" ]]; then
+    if [[ "${PLAYGROUND_WASM_OPT}" == "false" ]]; then
+        wasm_opt="false"
+    else
+        wasm_opt="[\"${PLAYGROUND_WASM_OPT}\"]"
+    fi
+
+    printf '\n[package.metadata.wasm-pack.profile.%s]\nwasm-opt = %s\n' \
+        "${profile}" "${wasm_opt}" >> Cargo.toml
+fi
+
+# The resources are measured here because the server only sees the
+# runtime's client. `time` writes the user and system CPU time in seconds
+# and the peak resident memory in kibibytes.
+# `timeout` stops the tool with `SIGTERM` and exits with 124. Tools which
+# ignore it are killed by the server once the hard timeout is reached.
+status=0
+/usr/bin/time --quiet --output="${usage_file}" --format='%U %S %M' \
+    timeout "${timeout}" "$@" || status=$?
+
+if (( status == 124 )); then
+    echo "timed-out" >> "${usage_file}"
+elif (( status > 128 )); then
+    # `time` exits normally, so the shell doesn't print the useful "Killed"
+    # message by itself
+    echo "Killed by SIG$(kill -l "${status}")" >&2
+fi
+exit "${status}"
@@ -0,0 +1,167 @@
+//! Connection to the collaboration server shared by all components.
+
+use crate::services::{
+    api::SessionRef,
+    collab::{ClientMessage, ServerMessage},
+};
+use std::{collections::HashSet, rc::Rc, time::Duration};
+use yew::{
+    agent::{Agent, AgentLink, Context, HandlerId},
+    format::Json,
+    services::{
+        timeout::TimeoutTask,
+        websocket::{WebSocketService, WebSocketStatus, WebSocketTask},
+        TimeoutService,
+    },
+};
+
+/// Time to wait before connecting again after the connection was lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+#[derive(Debug)]
+pub enum CollabRequest {
+    /// Connect to the session unless already connected to it.
+    Connect(SessionRef),
+    /// Send a message to the server.
+    /// Messages are dropped while disconnected, everything has to be opened
+    /// again once `Connected` is received.
+    Send(ClientMessage),
+}
+
+#[derive(Clone, Debug)]
+pub enum CollabEvent {
+    Connected,
+    Disconnected,
+    Message(Rc<ServerMessage>),
+}
+
+#[derive(Debug)]
+pub enum CollabAgentMsg {
+    Received(anyhow::Result<ServerMessage>),
+    Status(WebSocketStatus),
+    Reconnect,
+}
+
+pub struct CollabAgent {
+    link: AgentLink<Self>,
+    subscribers: HashSet<HandlerId>,
+    session: Option<SessionRef>,
+    socket: Option<WebSocketTask>,
+    open: bool,
+    reconnect_timeout: Option<TimeoutTask>,
+}
+impl CollabAgent {
+    fn broadcast(&self, event: CollabEvent) {
+        for id in &self.subscribers {
+            self.link.respond(*id, event.clone());
+        }
+    }
+
+    fn connect(&mut self) {
+        self.reconnect_timeout = None;
+        let session = match &self.session {
+            Some(session) => session,
+            None => return,
+        };
+
+        let uri = session.collab_uri();
+        let socket = WebSocketService::connect_text(
+            &uri,
+            self.link
+                .callback(|Json(msg)| CollabAgentMsg::Received(msg)),
+            self.link.callback(CollabAgentMsg::Status),
+        );
+        match socket {
+            Ok(socket) => self.socket = Some(socket),
+            Err(err) => {
+                log::error!("failed to connect to the collaboration server: {:?}", err);
+                self.schedule_reconnect();
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        // dropping the task closes the socket
+        self.socket = None;
+        self.reconnect_timeout = None;
+        if self.open {
+            self.open = false;
+            self.broadcast(CollabEvent::Disconnected);
+        }
+    }
+
+    fn schedule_reconnect(&mut self) {
+        self.reconnect_timeout = Some(TimeoutService::spawn(
+            RECONNECT_DELAY,
+            self.link.callback(|_| CollabAgentMsg::Reconnect),
+        ));
+    }
+}
+impl Agent for CollabAgent {
+    type Input = CollabRequest;
+    type Message = CollabAgentMsg;
+    type Output = CollabEvent;
+    type Reach = Context<Self>;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        Self {
+            link,
+            subscribers: HashSet::new(),
+            session: None,
+            socket: None,
+            open: false,
+            reconnect_timeout: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) {
+        use CollabAgentMsg::*;
+        match msg {
+            Received(Ok(msg)) => self.broadcast(CollabEvent::Message(Rc::new(msg))),
+            Received(Err(err)) => log::warn!("received invalid collaboration message: {}", err),
+            Status(WebSocketStatus::Opened) => {
+                self.open = true;
+                self.broadcast(CollabEvent::Connected);
+            }
+            Status(WebSocketStatus::Closed) | Status(WebSocketStatus::Error) => {
+                self.disconnect();
+                if self.session.is_some() {
+                    self.schedule_reconnect();
+                }
+            }
+            Reconnect => self.connect(),
+        }
+    }
+
+    fn connected(&mut self, id: HandlerId) {
+        self.subscribers.insert(id);
+        if self.open {
+            self.link.respond(id, CollabEvent::Connected);
+        }
+    }
+
+    fn handle_input(&mut self, msg: Self::Input, _id: HandlerId) {
+        match msg {
+            CollabRequest::Connect(session) => {
+                if matches!(&self.session, Some(current) if current.id == session.id) {
+                    return;
+                }
+                self.disconnect();
+                self.session = Some(session);
+                self.connect();
+            }
+            CollabRequest::Send(msg) => match &mut self.socket {
+                Some(socket) if self.open => socket.send(Json(&msg)),
+                _ => log::debug!("dropping collaboration message while disconnected"),
+            },
+        }
+    }
+
+    fn disconnected(&mut self, id: HandlerId) {
+        self.subscribers.remove(&id);
+        if self.subscribers.is_empty() {
+            self.session = None;
+            self.disconnect();
+        }
+    }
+}
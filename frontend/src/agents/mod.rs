@@ -1 +1 @@
-
+pub mod collab;
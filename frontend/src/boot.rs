@@ -1,8 +1,12 @@
 use crate::{
     routes::AppRouter,
-    services::locale::{self, LoadBundleTask},
+    services::{
+        api::{self, ApiVersion},
+        locale::{self, LoadBundleTask},
+    },
 };
-use yew::{html, Component, ComponentLink, Html, ShouldRender};
+use fluent::{FluentArgs, FluentValue};
+use yew::{html, services::fetch::FetchTask, Component, ComponentLink, Html, ShouldRender};
 
 #[derive(Debug)]
 pub struct Root;
@@ -32,11 +36,32 @@ impl Component for Root {
 #[derive(Debug)]
 pub enum BootMsg {
     BundleLoaded(anyhow::Result<()>),
+    VersionLoaded(anyhow::Result<ApiVersion>),
 }
 
 #[derive(Debug)]
 pub struct BootComponent {
     load_bundle_task: Option<LoadBundleTask>,
+    version_task: Option<FetchTask>,
+    /// Protocol version of the server if it isn't compatible with ours.
+    incompatible_version: Option<String>,
+}
+impl BootComponent {
+    fn view_version_warning(&self) -> Html {
+        let version = match &self.incompatible_version {
+            Some(version) => version,
+            None => return html! {},
+        };
+
+        let mut args = FluentArgs::new();
+        args.insert("server", FluentValue::from(version.as_str()));
+        args.insert("client", FluentValue::from(protocol::PROTOCOL_VERSION));
+        html! {
+            <div class="version-warning">
+                { locale::get("version-incompatible", Some(&args)) }
+            </div>
+        }
+    }
 }
 impl Component for BootComponent {
     type Message = BootMsg;
@@ -51,7 +76,18 @@ impl Component for BootComponent {
                 },
                 Some,
             );
-        Self { load_bundle_task }
+        let version_task = api::get_version(link.callback(BootMsg::VersionLoaded)).map_or_else(
+            |err| {
+                log::error!("failed to start loading the server version: {}", err);
+                None
+            },
+            Some,
+        );
+        Self {
+            load_bundle_task,
+            version_task,
+            incompatible_version: None,
+        }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
@@ -62,6 +98,21 @@ impl Component for BootComponent {
                 }
                 self.load_bundle_task = None;
             }
+            BootMsg::VersionLoaded(res) => {
+                self.version_task = None;
+                match res {
+                    Ok(version) if !protocol::is_compatible(&version.protocol) => {
+                        log::warn!(
+                            "server speaks protocol {} which isn't compatible with {}",
+                            version.protocol,
+                            protocol::PROTOCOL_VERSION
+                        );
+                        self.incompatible_version = Some(version.protocol);
+                    }
+                    Ok(_) => {}
+                    Err(err) => log::error!("failed to load the server version: {}", err),
+                }
+            }
         }
         true
     }
@@ -74,7 +125,12 @@ impl Component for BootComponent {
         if self.load_bundle_task.is_some() {
             html! {}
         } else {
-            html! { <Root /> }
+            html! {
+                <>
+                    { self.view_version_warning() }
+                    <Root />
+                </>
+            }
         }
     }
 }
@@ -1,21 +1,24 @@
+use super::compile_options::CompileOptionsForm;
 use crate::{
     services::{
         api::{
-            ClippyResponse,
+            CompileOptions,
             CompileResponse,
-            FormatResponse,
-            MacroExpandResponse,
+            EmitResponse,
+            EmitTarget,
             Session,
             SessionRef,
+            ToolOutput,
         },
         locale,
     },
-    utils::NeqAssign,
+    utils::{format_size, NeqAssign},
 };
 use yew::{
     html,
     services::fetch::FetchTask,
     Callback,
+    ChangeData,
     Component,
     ComponentLink,
     Html,
@@ -25,22 +28,56 @@ use yew::{
 
 #[derive(Debug)]
 pub enum ActionBarMsg {
+    SetCompileOptions(CompileOptions),
     Compile,
     CompileResponse(anyhow::Result<CompileResponse>),
     Format,
-    FormatResponse(anyhow::Result<FormatResponse>),
+    FormatResponse(anyhow::Result<ToolOutput>),
     Clippy,
-    ClippyResponse(anyhow::Result<ClippyResponse>),
+    ClippyResponse(anyhow::Result<ToolOutput>),
     MacroExpand,
-    MacroExpandResponse(anyhow::Result<MacroExpandResponse>),
+    MacroExpandResponse(anyhow::Result<ToolOutput>),
+    SetEmitTarget(EmitTarget),
+    Emit,
+    EmitResponse(EmitTarget, anyhow::Result<EmitResponse>),
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ActionBarCallbacks {
     pub compile: Callback<CompileResponse>,
-    pub format: Callback<FormatResponse>,
-    pub clippy: Callback<ClippyResponse>,
-    pub macro_expand: Callback<MacroExpandResponse>,
+    pub format: Callback<ToolOutput>,
+    pub clippy: Callback<ToolOutput>,
+    pub macro_expand: Callback<ToolOutput>,
+    pub emit: Callback<(EmitTarget, EmitResponse)>,
+}
+
+const EMIT_TARGETS: &[EmitTarget] = &[
+    EmitTarget::Mir,
+    EmitTarget::LlvmIr,
+    EmitTarget::Asm,
+    EmitTarget::Wat,
+];
+
+/// Value used to identify the target in the select element.
+fn emit_target_value(target: EmitTarget) -> &'static str {
+    use EmitTarget::*;
+    match target {
+        Mir => "mir",
+        LlvmIr => "llvm-ir",
+        Asm => "asm",
+        Wat => "wat",
+    }
+}
+
+/// Id of the localised name of the target.
+pub fn emit_target_locale_id(target: EmitTarget) -> &'static str {
+    use EmitTarget::*;
+    match target {
+        Mir => "emit_target-mir",
+        LlvmIr => "emit_target-llvm_ir",
+        Asm => "emit_target-asm",
+        Wat => "emit_target-wat",
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -54,6 +91,37 @@ pub struct ActionBar {
     props: ActionBarProps,
     link: ComponentLink<Self>,
     state: ActionBarState,
+    compile_options: CompileOptions,
+    /// Size of the binary produced by the last compilation.
+    wasm_size: Option<u64>,
+    emit_target: EmitTarget,
+}
+impl ActionBar {
+    fn view_emit_select(&self) -> Html {
+        let options = EMIT_TARGETS.iter().map(|&target| {
+            html! {
+                <option value=emit_target_value(target) selected=(target == self.emit_target)>
+                    { locale::get(emit_target_locale_id(target), None) }
+                </option>
+            }
+        });
+        let onchange = self.link.batch_callback(|data| match data {
+            ChangeData::Select(select) => EMIT_TARGETS
+                .iter()
+                .copied()
+                .find(|&target| emit_target_value(target) == select.value())
+                .map(ActionBarMsg::SetEmitTarget)
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        });
+
+        html! {
+            <select onchange=onchange>
+                { for options }
+            </select>
+        }
+    }
 }
 impl Component for ActionBar {
     type Message = ActionBarMsg;
@@ -64,6 +132,9 @@ impl Component for ActionBar {
             props,
             link,
             state: ActionBarState::Idle,
+            compile_options: CompileOptions::default(),
+            wasm_size: None,
+            emit_target: EmitTarget::Mir,
         }
     }
 
@@ -72,26 +143,35 @@ impl Component for ActionBar {
             props: ActionBarProps { session, callbacks },
             link,
             state,
+            compile_options,
+            wasm_size,
+            emit_target,
         } = self;
 
         use ActionBarMsg::*;
         match msg {
-            Compile => state.compile(&session, link.callback(ActionBarMsg::CompileResponse)),
+            SetCompileOptions(options) => compile_options.neq_assign(options),
+            Compile => state.compile(
+                session,
+                compile_options,
+                link.callback(ActionBarMsg::CompileResponse),
+            ),
             CompileResponse(resp) => {
                 if let Some(resp) = state.handle_response(resp) {
+                    *wasm_size = resp.wasm_size;
                     callbacks.compile.emit(resp);
                 }
 
                 true
             }
-            Format => state.format(&session, link.callback(ActionBarMsg::FormatResponse)),
+            Format => state.format(session, link.callback(ActionBarMsg::FormatResponse)),
             FormatResponse(resp) => {
                 if let Some(resp) = state.handle_response(resp) {
                     callbacks.format.emit(resp);
                 }
                 true
             }
-            Clippy => state.clippy(&session, link.callback(ActionBarMsg::ClippyResponse)),
+            Clippy => state.clippy(session, link.callback(ActionBarMsg::ClippyResponse)),
             ClippyResponse(resp) => {
                 if let Some(resp) = state.handle_response(resp) {
                     callbacks.clippy.emit(resp);
@@ -99,7 +179,7 @@ impl Component for ActionBar {
                 true
             }
             MacroExpand => {
-                state.macro_expand(&session, link.callback(ActionBarMsg::MacroExpandResponse))
+                state.macro_expand(session, link.callback(ActionBarMsg::MacroExpandResponse))
             }
             MacroExpandResponse(resp) => {
                 if let Some(resp) = state.handle_response(resp) {
@@ -107,6 +187,21 @@ impl Component for ActionBar {
                 }
                 true
             }
+            SetEmitTarget(target) => emit_target.neq_assign(target),
+            Emit => {
+                let target = *emit_target;
+                state.emit(
+                    session,
+                    target,
+                    link.callback(move |resp| ActionBarMsg::EmitResponse(target, resp)),
+                )
+            }
+            EmitResponse(target, resp) => {
+                if let Some(resp) = state.handle_response(resp) {
+                    callbacks.emit.emit((target, resp));
+                }
+                true
+            }
         }
     }
 
@@ -116,11 +211,31 @@ impl Component for ActionBar {
 
     fn view(&self) -> Html {
         let link = &self.link;
+        let wasm_size = if let Some(size) = self.wasm_size {
+            html! {
+                <span class="action-bar__wasm-size">{ format_size(size) }</span>
+            }
+        } else {
+            html! {}
+        };
+        let error = if let ActionBarState::Error(err) = &self.state {
+            html! {
+                <span class="action-bar__error">{ err.to_string() }</span>
+            }
+        } else {
+            html! {}
+        };
+
         html! {
             <div>
+                <CompileOptionsForm
+                    options=self.compile_options.clone()
+                    onchange=link.callback(ActionBarMsg::SetCompileOptions)
+                />
                 <button onclick=link.callback(|_| ActionBarMsg::Compile)>
                     { locale::get("action_bar-compile", None) }
                 </button>
+                { wasm_size }
                 <button onclick=link.callback(|_| ActionBarMsg::Format)>
                     { locale::get("action_bar-format", None) }
                 </button>
@@ -130,6 +245,11 @@ impl Component for ActionBar {
                 <button onclick=link.callback(|_| ActionBarMsg::MacroExpand)>
                     { locale::get("action_bar-macro_expand", None) }
                 </button>
+                { self.view_emit_select() }
+                <button onclick=link.callback(|_| ActionBarMsg::Emit)>
+                    { locale::get("action_bar-emit", None) }
+                </button>
+                { error }
             </div>
         }
     }
@@ -138,7 +258,7 @@ impl Component for ActionBar {
 #[derive(Debug)]
 enum ActionBarState {
     Idle,
-    Waiting(FetchTask),
+    Waiting(#[allow(dead_code)] FetchTask),
     Error(anyhow::Error),
 }
 impl ActionBarState {
@@ -149,6 +269,7 @@ impl ActionBarState {
     fn compile(
         &mut self,
         session: &Session,
+        options: &CompileOptions,
         callback: Callback<anyhow::Result<CompileResponse>>,
     ) -> bool {
         if self.is_loading() {
@@ -156,7 +277,7 @@ impl ActionBarState {
         }
         *self = Self::Waiting(
             session
-                .compile(callback)
+                .compile(options, callback)
                 .expect("failed to create compile request"),
         );
         true
@@ -165,7 +286,7 @@ impl ActionBarState {
     fn format(
         &mut self,
         session: &Session,
-        callback: Callback<anyhow::Result<FormatResponse>>,
+        callback: Callback<anyhow::Result<ToolOutput>>,
     ) -> bool {
         if self.is_loading() {
             return false;
@@ -181,7 +302,7 @@ impl ActionBarState {
     fn clippy(
         &mut self,
         session: &Session,
-        callback: Callback<anyhow::Result<ClippyResponse>>,
+        callback: Callback<anyhow::Result<ToolOutput>>,
     ) -> bool {
         if self.is_loading() {
             return false;
@@ -197,7 +318,7 @@ impl ActionBarState {
     fn macro_expand(
         &mut self,
         session: &Session,
-        callback: Callback<anyhow::Result<MacroExpandResponse>>,
+        callback: Callback<anyhow::Result<ToolOutput>>,
     ) -> bool {
         if self.is_loading() {
             return false;
@@ -210,6 +331,23 @@ impl ActionBarState {
         true
     }
 
+    fn emit(
+        &mut self,
+        session: &Session,
+        target: EmitTarget,
+        callback: Callback<anyhow::Result<EmitResponse>>,
+    ) -> bool {
+        if self.is_loading() {
+            return false;
+        }
+        *self = Self::Waiting(
+            session
+                .emit(target, callback)
+                .expect("failed to create emit request"),
+        );
+        true
+    }
+
     fn handle_response<T>(&mut self, resp: anyhow::Result<T>) -> Option<T> {
         match resp {
             Ok(res) => {
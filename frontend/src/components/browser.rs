@@ -2,7 +2,7 @@ use crate::{
     services::{api::SessionRef, locale},
     utils::{ComponentRef, NeqAssign},
 };
-use web_sys::{HtmlIFrameElement, Window};
+use web_sys::HtmlIFrameElement;
 use yew::{html, Component, ComponentLink, Html, NodeRef, Properties, ShouldRender};
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -39,12 +39,6 @@ impl Browser {
     fn iframe(&self) -> HtmlIFrameElement {
         self.iframe_ref.cast().expect("failed to get iframe")
     }
-
-    fn iframe_window(&self) -> Window {
-        self.iframe()
-            .content_window()
-            .expect("unable to get content window")
-    }
 }
 impl Component for Browser {
     type Message = BrowserMsg;
@@ -67,10 +61,9 @@ impl Component for Browser {
         use BrowserMsg::*;
         match msg {
             Reload => {
-                self.iframe_window()
-                    .location()
-                    .reload()
-                    .expect("unable to reload");
+                // the sandboxed frame is cross-origin so its location can't be
+                // reloaded directly, setting the source navigates it again
+                self.iframe().set_src(&self.url);
 
                 false
             }
@@ -98,7 +91,7 @@ impl Component for Browser {
                     </button>
                     <span>{ url }</span>
                 </div>
-                <iframe ref=iframe_ref.clone() class="browser__frame" title=locale::get("browser-iframe-title", None) src=&self.url />
+                <iframe ref=iframe_ref.clone() class="browser__frame" title=locale::get("browser-iframe-title", None) sandbox="allow-scripts allow-forms" src=&self.url />
             </div>
         }
     }
@@ -0,0 +1,213 @@
+use crate::{
+    services::{
+        api::{CompileOptions, Lto, Mode, OptLevel, WasmOptLevel},
+        locale,
+    },
+    utils::NeqAssign,
+};
+use yew::{html, Callback, ChangeData, Component, ComponentLink, Html, Properties, ShouldRender};
+
+const MODES: &[(Mode, &str, &str)] = &[
+    (Mode::Debug, "debug", "compile_options-mode-debug"),
+    (Mode::Release, "release", "compile_options-mode-release"),
+];
+
+const OPT_LEVELS: &[(OptLevel, &str, &str)] = &[
+    (OptLevel::Zero, "0", "compile_options-opt_level-0"),
+    (OptLevel::One, "1", "compile_options-opt_level-1"),
+    (OptLevel::Two, "2", "compile_options-opt_level-2"),
+    (OptLevel::Three, "3", "compile_options-opt_level-3"),
+    (OptLevel::S, "s", "compile_options-opt_level-s"),
+    (OptLevel::Z, "z", "compile_options-opt_level-z"),
+];
+
+const LTOS: &[(Lto, &str, &str)] = &[
+    (Lto::Off, "off", "compile_options-lto-off"),
+    (Lto::Thin, "thin", "compile_options-lto-thin"),
+    (Lto::Fat, "fat", "compile_options-lto-fat"),
+];
+
+const WASM_OPT_LEVELS: &[(WasmOptLevel, &str, &str)] = &[
+    (
+        WasmOptLevel::Disabled,
+        "disabled",
+        "compile_options-wasm_opt-disabled",
+    ),
+    (WasmOptLevel::O1, "O1", "compile_options-wasm_opt-o1"),
+    (WasmOptLevel::O2, "O2", "compile_options-wasm_opt-o2"),
+    (WasmOptLevel::O3, "O3", "compile_options-wasm_opt-o3"),
+    (WasmOptLevel::O4, "O4", "compile_options-wasm_opt-o4"),
+    (WasmOptLevel::Os, "Os", "compile_options-wasm_opt-os"),
+    (WasmOptLevel::Oz, "Oz", "compile_options-wasm_opt-oz"),
+];
+
+/// Value of the select option which represents `None`.
+const DEFAULT_VALUE: &str = "default";
+
+/// Render a select element for the given choices.
+/// The first element of every choice is the value, the second one is used to
+/// identify it in the select element and the third one is the locale id of the
+/// label.
+/// If `with_default` is set, there's an additional option which emits `None`.
+fn view_select<T>(
+    label_id: &str,
+    choices: &'static [(T, &'static str, &'static str)],
+    selected: Option<T>,
+    with_default: bool,
+    callback: Callback<Option<T>>,
+) -> Html
+where
+    T: Copy + PartialEq + 'static,
+{
+    let default_option = if with_default {
+        html! {
+            <option value=DEFAULT_VALUE selected=selected.is_none()>
+                { locale::get("compile_options-default", None) }
+            </option>
+        }
+    } else {
+        html! {}
+    };
+    let options = choices.iter().map(|&(choice, value, locale_id)| {
+        html! {
+            <option value=value selected=(selected == Some(choice))>
+                { locale::get(locale_id, None) }
+            </option>
+        }
+    });
+    let onchange = Callback::from(move |data| {
+        if let ChangeData::Select(select) = data {
+            let value = select.value();
+            let choice = choices
+                .iter()
+                .find(|(_, choice_value, _)| *choice_value == value)
+                .map(|&(choice, _, _)| choice);
+            callback.emit(choice);
+        }
+    });
+
+    html! {
+        <label class="compile-options__option">
+            { locale::get(label_id, None) }
+            <select onchange=onchange>
+                { default_option }
+                { for options }
+            </select>
+        </label>
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
+pub enum CompileOptionsMsg {
+    SetMode(Mode),
+    SetOptLevel(Option<OptLevel>),
+    SetLto(Option<Lto>),
+    SetCodegenUnits(Option<u32>),
+    SetWasmOpt(Option<WasmOptLevel>),
+}
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct CompileOptionsProps {
+    pub options: CompileOptions,
+    pub onchange: Callback<CompileOptions>,
+}
+
+/// Form to change the `CompileOptions`.
+#[derive(Debug)]
+pub struct CompileOptionsForm {
+    props: CompileOptionsProps,
+    link: ComponentLink<Self>,
+}
+impl Component for CompileOptionsForm {
+    type Message = CompileOptionsMsg;
+    type Properties = CompileOptionsProps;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let mut options = self.props.options.clone();
+
+        use CompileOptionsMsg::*;
+        match msg {
+            SetMode(mode) => options.mode = mode,
+            SetOptLevel(level) => options.opt_level = level,
+            SetLto(lto) => options.lto = lto,
+            SetCodegenUnits(units) => options.codegen_units = units,
+            SetWasmOpt(level) => options.wasm_opt = level,
+        }
+
+        self.props.onchange.emit(options);
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        let link = &self.link;
+        let CompileOptions {
+            mode,
+            opt_level,
+            lto,
+            codegen_units,
+            wasm_opt,
+        } = self.props.options;
+
+        let onchange_codegen_units = link.callback(|data| {
+            let units = match data {
+                ChangeData::Value(value) => value.parse().ok(),
+                _ => None,
+            };
+            CompileOptionsMsg::SetCodegenUnits(units)
+        });
+        let codegen_units = codegen_units.map(|v| v.to_string()).unwrap_or_default();
+
+        html! {
+            <div class="compile-options">
+                { view_select(
+                    "compile_options-mode",
+                    MODES,
+                    Some(mode),
+                    false,
+                    link.batch_callback(|mode: Option<Mode>| mode.map(CompileOptionsMsg::SetMode).into_iter().collect()),
+                ) }
+                { view_select(
+                    "compile_options-opt_level",
+                    OPT_LEVELS,
+                    opt_level,
+                    true,
+                    link.callback(CompileOptionsMsg::SetOptLevel),
+                ) }
+                { view_select(
+                    "compile_options-lto",
+                    LTOS,
+                    lto,
+                    true,
+                    link.callback(CompileOptionsMsg::SetLto),
+                ) }
+                <label class="compile-options__option">
+                    { locale::get("compile_options-codegen_units", None) }
+                    <input
+                        type="number"
+                        min="1"
+                        max="256"
+                        placeholder=locale::get("compile_options-default", None)
+                        value=codegen_units
+                        onchange=onchange_codegen_units
+                    />
+                </label>
+                { view_select(
+                    "compile_options-wasm_opt",
+                    WASM_OPT_LEVELS,
+                    wasm_opt,
+                    true,
+                    link.callback(CompileOptionsMsg::SetWasmOpt),
+                ) }
+            </div>
+        }
+    }
+}
@@ -1,11 +1,77 @@
-use crate::utils::NeqAssign;
+use crate::{
+    services::{api::ToolOutput, locale},
+    utils::{format_size, NeqAssign},
+};
+use fluent::{FluentArgs, FluentValue};
 use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
 use yew_ansi::Ansi;
 
 #[derive(Clone, Debug, Default, PartialEq, Properties)]
 pub struct ConsoleProps {
-    pub stderr: String,
-    pub stdout: String,
+    /// Output of the last tool which was run.
+    pub output: Option<ToolOutput>,
+}
+
+fn format_millis(millis: u64) -> String {
+    format!("{:.2} s", millis as f64 / 1000.0)
+}
+
+fn localize(key: &str, name: &str, value: String) -> String {
+    let mut args = FluentArgs::new();
+    args.insert(name, FluentValue::from(value));
+    locale::get(key, Some(&args))
+}
+
+/// Summary of how the tool exited and the resources it used.
+fn view_status(output: &ToolOutput) -> Html {
+    let mut parts = Vec::new();
+    if output.timed_out {
+        parts.push(locale::get("console-timed_out", None));
+    } else if let Some(signal) = output.signal {
+        parts.push(localize("console-signal", "signal", signal.to_string()));
+    } else if let Some(code) = output.exit_code {
+        parts.push(localize("console-exit_code", "code", code.to_string()));
+    }
+    parts.push(localize(
+        "console-wall_time",
+        "time",
+        format_millis(output.wall_time_ms),
+    ));
+    if let Some(cpu_time) = output.cpu_time_ms {
+        parts.push(localize(
+            "console-cpu_time",
+            "time",
+            format_millis(cpu_time),
+        ));
+    }
+    if let Some(peak_memory) = output.peak_memory {
+        parts.push(localize(
+            "console-peak_memory",
+            "size",
+            format_size(peak_memory),
+        ));
+    }
+
+    let class = if output.success {
+        "console__status"
+    } else {
+        "console__status console__status--failed"
+    };
+    html! { <div class=class>{ parts.join(" · ") }</div> }
+}
+
+fn view_stream(text: &str, truncated: bool) -> Html {
+    let notice = if truncated {
+        html! { <div class="console__truncated">{ locale::get("console-truncated", None) }</div> }
+    } else {
+        html! {}
+    };
+    html! {
+        <div class="console__stream">
+            <Ansi text=text.to_owned() />
+            { notice }
+        </div>
+    }
 }
 
 #[derive(Debug)]
@@ -29,11 +95,18 @@ impl Component for Console {
     }
 
     fn view(&self) -> Html {
-        let ConsoleProps { stderr, stdout } = &self.props;
+        let output = match &self.props.output {
+            Some(output) => output,
+            None => return html! { <div class="console" /> },
+        };
+
         html! {
             <div class="console">
-                <Ansi text=stderr.clone() />
-                <Ansi text=stdout.clone() />
+                { view_status(output) }
+                <div class="console__streams">
+                    { view_stream(&output.stderr, output.stderr_truncated) }
+                    { view_stream(&output.stdout, output.stdout_truncated) }
+                </div>
             </div>
         }
     }
@@ -1,24 +1,68 @@
 use super::{
-    action_bar::{ActionBar, ActionBarCallbacks},
+    action_bar::{self, ActionBar, ActionBarCallbacks},
     explorer::Explorer,
     icon::{Icon, MdiButton, MdiProps},
+    problems::Problems,
 };
 use crate::{
+    agents::collab::{CollabAgent, CollabEvent, CollabRequest},
     services::{
-        api::{Session, SessionRef},
+        api::{
+            ApiError,
+            CheckResponse,
+            Diagnostic,
+            EmitResponse,
+            EmitTarget,
+            FileContent,
+            Session,
+            SessionRef,
+            VersionedFile,
+        },
+        collab::{
+            self,
+            ClientMessage,
+            DocumentSync,
+            OperationComponent,
+            OutOfSync,
+            Participant,
+            ParticipantId,
+            Selection,
+            ServerMessage,
+            TextOperation,
+        },
         locale,
     },
-    utils::NeqAssign,
+    utils::{self, DiffLine, FileKind, NeqAssign},
 };
+use fluent::{FluentArgs, FluentValue};
+use js_sys::{Array, Object};
 use monaco::{
-    api::{CodeEditorOptions, TextModel},
-    sys::{editor::BuiltinTheme, Uri},
-    yew::CodeEditor,
+    api::{CodeEditorOptions, DisposableClosure, TextModel},
+    sys::{
+        editor::{
+            BuiltinTheme,
+            ICursorSelectionChangedEvent,
+            IEditorOptions,
+            IIdentifiedSingleEditOperation,
+            IModelContentChange,
+            IModelContentChangedEvent,
+            IModelDecorationOptions,
+            IModelDeltaDecoration,
+            ITextModel,
+        },
+        Position,
+        Range,
+        Selection as EditorSelection,
+        Uri,
+    },
+    yew::{CodeEditor, CodeEditorLink},
 };
-use std::{rc::Rc, slice};
+use std::{collections::HashMap, rc::Rc, slice, time::Duration};
+use wasm_bindgen::JsCast;
 use yew::{
+    agent::{Bridge, Bridged},
     html,
-    services::fetch::FetchTask,
+    services::{fetch::FetchTask, timeout::TimeoutTask, TimeoutService},
     Callback,
     Component,
     ComponentLink,
@@ -29,14 +73,35 @@ use yew::{
 
 type TabIdentifier = usize;
 
+/// Time to wait after the last change before checking the code.
+const CHECK_DELAY: Duration = Duration::from_millis(750);
+
 #[derive(Debug)]
 pub enum EditorMsg {
     OpenFile(Rc<protocol::File>),
-    FileResponse(TabIdentifier, anyhow::Result<String>),
+    FileResponse(TabIdentifier, anyhow::Result<VersionedFile>),
     SelectTab(TabIdentifier),
     CloseTab(TabIdentifier),
     SaveTab(TabIdentifier),
-    SaveResponse(TabIdentifier, anyhow::Result<()>),
+    SaveResponse(TabIdentifier, anyhow::Result<Option<String>>),
+    ConflictResponse(TabIdentifier, anyhow::Result<VersionedFile>),
+    ResolveConflict(TabIdentifier, Resolution),
+    ContentChanged(TabIdentifier, TextOperation),
+    EditorCreated(CodeEditorLink),
+    SelectionChanged(EditorSelection),
+    Collab(CollabEvent),
+    RunCheck,
+    CheckResponse(anyhow::Result<CheckResponse>),
+    OpenOutput(EmitTarget, String),
+}
+
+/// How a conflict between the local and the saved content is resolved.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    /// Overwrite the saved content with the local one.
+    KeepMine,
+    /// Discard the local changes.
+    UseSaved,
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -45,15 +110,285 @@ pub struct EditorProps {
     pub action_bar_callbacks: ActionBarCallbacks,
 }
 
-#[derive(Debug)]
 pub struct Editor {
     props: EditorProps,
     link: ComponentLink<Self>,
     tabs: Tabs,
     selected: Option<TabIdentifier>,
     monaco_options: Rc<CodeEditorOptions>,
+    action_bar_callbacks: ActionBarCallbacks,
+    check_timeout: Option<TimeoutTask>,
+    check_task: Option<FetchTask>,
+    diagnostics: Rc<Vec<Diagnostic>>,
+    collab: Box<dyn Bridge<CollabAgent>>,
+    /// Whether the connection to the collaboration server is open.
+    collab_connected: bool,
+    /// Ourselves as seen by the other participants.
+    you: Option<Participant>,
+    /// Everyone else connected to the session.
+    participants: Vec<Participant>,
+    selection_listener: Option<DisposableClosure<dyn FnMut(ICursorSelectionChangedEvent)>>,
+    editor_link: Option<CodeEditorLink>,
 }
 impl Editor {
+    /// Keep the user from typing where the changes would be dropped.
+    /// The edits would otherwise be missing from the synchronised document
+    /// and the edits of the other participants would end up in the wrong
+    /// place.
+    fn update_read_only(&self) {
+        // visitors only follow the edits of the owner and output tabs can't
+        // be edited at all
+        let read_only = self
+            .selected
+            .and_then(|id| self.tabs.get(id))
+            .map_or(!self.props.session.is_owner(), |tab| tab.read_only);
+        if let Some(editor_link) = &self.editor_link {
+            editor_link.with_editor(|editor| {
+                let options: IEditorOptions = Object::new().unchecked_into();
+                options.set_read_only(Some(read_only));
+                editor.as_ref().update_options(options.unchecked_ref());
+            });
+        }
+    }
+
+    /// Wrap the callbacks so that emitted code is opened in a new tab before
+    /// being passed on.
+    fn wrap_action_bar_callbacks(
+        link: &ComponentLink<Self>,
+        callbacks: &ActionBarCallbacks,
+    ) -> ActionBarCallbacks {
+        let open_output = link.callback(|(target, code)| EditorMsg::OpenOutput(target, code));
+        let emit = callbacks.emit.clone();
+        ActionBarCallbacks {
+            emit: Callback::from(move |(target, resp): (EmitTarget, EmitResponse)| {
+                if resp.output.success {
+                    open_output.emit((target, resp.code.clone()));
+                }
+                emit.emit((target, resp));
+            }),
+            ..callbacks.clone()
+        }
+    }
+
+    /// Check the code once the user stops typing.
+    /// Calling this again before the delay has passed restarts the timer.
+    fn schedule_check(&mut self) {
+        self.check_timeout = Some(TimeoutService::spawn(
+            CHECK_DELAY,
+            self.link.callback(|_| EditorMsg::RunCheck),
+        ));
+    }
+
+    fn run_check(&mut self) {
+        self.check_timeout = None;
+        // only the owner may run tools
+        if !self.props.session.is_owner() {
+            return;
+        }
+        // replacing the task aborts the previous check
+        self.check_task = Some(
+            self.props
+                .session
+                .check(
+                    self.tabs.unsaved_sources(),
+                    self.link.callback(EditorMsg::CheckResponse),
+                )
+                .expect("failed to create check request"),
+        );
+    }
+
+    fn save_tab(&mut self, id: TabIdentifier) -> ShouldRender {
+        if let Some(tab) = self.tabs.get_mut(id) {
+            let ok = tab.save(
+                &self.props.session,
+                self.link
+                    .callback(move |resp| EditorMsg::SaveResponse(id, resp)),
+            );
+            if !ok {
+                log::warn!("can't save file");
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Start following the edits of the tab's file.
+    fn open_shared(&mut self, id: TabIdentifier) {
+        match self.tabs.get(id) {
+            Some(tab) if self.collab_connected && tab.is_shared() && tab.sync.is_none() => {
+                self.collab.send(CollabRequest::Send(ClientMessage::Open {
+                    path: tab.file.path.clone(),
+                }))
+            }
+            _ => {}
+        }
+    }
+
+    /// Clear our cursor in the tab for everyone else.
+    fn clear_selection(&mut self, id: TabIdentifier) {
+        match self.tabs.get(id) {
+            Some(tab) if tab.sync.is_some() => {
+                self.collab.send(CollabRequest::Send(ClientMessage::Select {
+                    path: tab.file.path.clone(),
+                    selection: None,
+                }))
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_collab_event(&mut self, event: CollabEvent) -> ShouldRender {
+        match event {
+            CollabEvent::Connected => {
+                self.collab_connected = true;
+                let ids: Vec<_> = self.tabs.iter().map(|tab| tab.id).collect();
+                for id in ids {
+                    self.open_shared(id);
+                }
+                false
+            }
+            CollabEvent::Disconnected => {
+                self.collab_connected = false;
+                self.you = None;
+                self.participants.clear();
+                for tab in self.tabs.iter_mut() {
+                    tab.stop_sync();
+                }
+                true
+            }
+            CollabEvent::Message(msg) => self.handle_collab_message(&msg),
+        }
+    }
+
+    fn handle_collab_message(&mut self, msg: &ServerMessage) -> ShouldRender {
+        use ServerMessage::*;
+        match msg {
+            Welcome { you, participants } => {
+                self.you = Some(you.clone());
+                self.participants = participants.clone();
+                true
+            }
+            Joined { participant } => {
+                self.participants.push(participant.clone());
+                true
+            }
+            Left { participant } => {
+                self.participants.retain(|p| p.id != *participant);
+                for tab in self.tabs.iter_mut() {
+                    if tab.remote_selections.remove(participant).is_some() {
+                        tab.show_remote_selections();
+                    }
+                }
+                true
+            }
+            Document {
+                path,
+                revision,
+                content,
+            } => {
+                let tab = match self.tabs.find_by_path_mut(path) {
+                    Some(tab) => tab,
+                    None => return false,
+                };
+                if !tab.start_sync(*revision, content) {
+                    log::info!("not following {} because of unsaved changes", path);
+                    return false;
+                }
+                if tab.is_source() {
+                    self.schedule_check();
+                }
+                true
+            }
+            Edit {
+                path,
+                revision,
+                operation,
+                ..
+            } => {
+                let tab = match self.tabs.find_by_path_mut(path) {
+                    Some(tab) => tab,
+                    None => return false,
+                };
+                if let Err(err) = tab.remote_edit(*revision, operation.clone()) {
+                    log::warn!("{}: {}", path, err);
+                    tab.stop_sync();
+                    self.collab.send(CollabRequest::Send(ClientMessage::Open {
+                        path: path.clone(),
+                    }));
+                } else if tab.is_source() {
+                    self.schedule_check();
+                }
+                false
+            }
+            Ack { path, revision } => {
+                let next = self
+                    .tabs
+                    .find_by_path_mut(path)
+                    .and_then(|tab| tab.sync.as_mut())
+                    .and_then(|sync| sync.acknowledged(*revision));
+                if let Some((revision, operation)) = next {
+                    self.collab.send(CollabRequest::Send(ClientMessage::Edit {
+                        path: path.clone(),
+                        revision,
+                        operation,
+                    }));
+                }
+                false
+            }
+            Select {
+                path,
+                participant,
+                selection,
+            } => {
+                if matches!(&self.you, Some(you) if you.id == *participant) {
+                    return false;
+                }
+                if let Some(tab) = self.tabs.find_by_path_mut(path) {
+                    match selection {
+                        Some(selection) => tab.remote_selections.insert(*participant, *selection),
+                        None => tab.remote_selections.remove(participant),
+                    };
+                    tab.show_remote_selections();
+                }
+                false
+            }
+            // the explorer shows the new files
+            FilesChanged { .. } => false,
+            Error { error } => {
+                log::warn!("collaboration error: {}", ApiError(error.clone()));
+                false
+            }
+        }
+    }
+
+    /// Send our selection in the selected tab to everyone else.
+    fn share_selection(&mut self, selection: &EditorSelection) {
+        let tab = match self.selected {
+            Some(id) => self.tabs.get(id),
+            None => None,
+        };
+        let (tab, model) = match tab {
+            Some(tab) if tab.sync.is_some() => match &tab.model {
+                Some(model) => (tab, model),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let model: &ITextModel = model.as_ref();
+        let start = Position::new(
+            selection.selection_start_line_number(),
+            selection.selection_start_column(),
+        );
+        let anchor = model.get_offset_at(start.unchecked_ref()) as u64;
+        let head = model.get_offset_at(selection.get_position().unchecked_ref()) as u64;
+        self.collab.send(CollabRequest::Send(ClientMessage::Select {
+            path: tab.file.path.clone(),
+            selection: Some(Selection { anchor, head }),
+        }));
+    }
+
     fn render_tab(&self, tab: &Tab) -> Html {
         let mut classes = vec!["htbar__tab"];
         if matches!(self.selected, Some(id) if id == tab.id) {
@@ -66,14 +401,21 @@ impl Editor {
         let tab_id = tab.id;
         let onclick_tab = self.link.callback(move |_| EditorMsg::SelectTab(tab_id));
         // TODO remove save button
-        let onclick_save = self.link.callback(move |_| EditorMsg::SaveTab(tab_id));
+        let save_button = if tab.read_only {
+            html! {}
+        } else {
+            let onclick_save = self.link.callback(move |_| EditorMsg::SaveTab(tab_id));
+            html! {
+                <button onclick=onclick_save>
+                    { locale::get("editor-save", None) }
+                </button>
+            }
+        };
 
         html! {
             <div key=tab.file.path.clone() class=classes role="tab" onclick=onclick_tab>
                 { &tab.file.name }
-                <button onclick=onclick_save>
-                    { locale::get("editor-save", None) }
-                </button>
+                { save_button }
                 <MdiButton
                     icon=MdiProps::new(Icon::Close)
                     aria_label=locale::get("editor-tab-close", None)
@@ -86,12 +428,14 @@ impl Editor {
     fn view_editor_window(&self) -> Html {
         html! {
             <div class="editor-window">
+                { self.view_presence() }
                 <nav class="htbar htbar--scroll" role="tablist">
                     { for self.tabs.iter().map(|tab| self.render_tab(tab)) }
                 </nav>
                 <div class="editor-window__content">
                     { self.view_content() }
                 </div>
+                <Problems diagnostics=Rc::clone(&self.diagnostics) />
             </div>
         }
     }
@@ -109,7 +453,6 @@ impl Editor {
 
     fn view_tab_content(&self, tab: &Tab) -> Html {
         use ContentState::*;
-        // TODO dirty flag needs to be set again
         match &tab.state {
             Loading(_) => {
                 // TODO render loading state
@@ -121,6 +464,27 @@ impl Editor {
                     { format!("WIP: failed: {}", err) }
                 }
             }
+            Idle if tab.kind == FileKind::Image => {
+                let src = self.props.session.file_uri(&tab.file.path);
+                html! {
+                    <div class="editor-preview">
+                        <img class="editor-preview__image" src=src alt=tab.file.name.clone() />
+                    </div>
+                }
+            }
+            Idle if tab.kind == FileKind::Binary => {
+                let href = self.props.session.file_uri(&tab.file.path);
+                html! {
+                    <div class="editor-preview">
+                        <span class="editor-preview__message">
+                            { locale::get("editor-preview-binary", None) }
+                        </span>
+                        <a class="editor-preview__download" href=href download=tab.file.name.clone()>
+                            { locale::get("editor-preview-download", None) }
+                        </a>
+                    </div>
+                }
+            }
             Idle => {
                 let model = tab.model.clone();
                 // let tab_id = tab.id;
@@ -129,11 +493,117 @@ impl Editor {
                 // });
                 // TODO this is very much WIP
                 html! {
-                    <CodeEditor options=Rc::clone(&self.monaco_options) model=model />
+                    <>
+                        { self.view_conflict(tab) }
+                        <CodeEditor
+                            options=Rc::clone(&self.monaco_options)
+                            model=model
+                            on_editor_created=self.link.callback(EditorMsg::EditorCreated)
+                        />
+                    </>
                 }
             }
         }
     }
+
+    /// Show who else is connected to the session.
+    fn view_presence(&self) -> Html {
+        let you = match &self.you {
+            Some(you) if !self.participants.is_empty() => you,
+            _ => return html! {},
+        };
+
+        html! {
+            <div class="collab-presence">
+                { view_participant(you, Some("editor-collab-you")) }
+                { for self.participants.iter().map(|participant| {
+                    let id = if participant.can_edit { None } else { Some("editor-collab-viewer") };
+                    view_participant(participant, id)
+                }) }
+            </div>
+        }
+    }
+
+    /// Show the differences between the saved and the local content.
+    fn view_conflict(&self, tab: &Tab) -> Html {
+        let (saved, model) = match (&tab.conflict, &tab.model) {
+            (Some(saved), Some(model)) => (saved, model),
+            _ => return html! {},
+        };
+
+        let local = model.get_value();
+        let diff = utils::diff_lines(&saved.content, &local);
+        let tab_id = tab.id;
+        let onclick_keep = self
+            .link
+            .callback(move |_| EditorMsg::ResolveConflict(tab_id, Resolution::KeepMine));
+        let onclick_use_saved = self
+            .link
+            .callback(move |_| EditorMsg::ResolveConflict(tab_id, Resolution::UseSaved));
+
+        html! {
+            <div class="editor-conflict" role="alert">
+                <p class="editor-conflict__message">{ locale::get("editor-conflict", None) }</p>
+                <div class="editor-conflict__actions">
+                    <button onclick=onclick_keep>
+                        { locale::get("editor-conflict-keep_mine", None) }
+                    </button>
+                    <button onclick=onclick_use_saved>
+                        { locale::get("editor-conflict-use_saved", None) }
+                    </button>
+                </div>
+                <pre class="editor-conflict__diff">
+                    { for diff.iter().map(view_diff_line) }
+                </pre>
+            </div>
+        }
+    }
+}
+
+/// Show a participant using the message `locale_id` for the name.
+fn view_participant(participant: &Participant, locale_id: Option<&str>) -> Html {
+    let mut classes = vec![
+        String::from("collab-presence__participant"),
+        format!(
+            "collab-presence__participant--{}",
+            collab::participant_color(participant.id)
+        ),
+    ];
+    if !participant.can_edit {
+        classes.push(String::from("collab-presence__participant--viewer"));
+    }
+
+    let label = match locale_id {
+        Some(id) => {
+            let mut args = FluentArgs::new();
+            args.insert("name", FluentValue::from(participant.name.as_str()));
+            locale::get(id, Some(&args))
+        }
+        None => participant.name.clone(),
+    };
+
+    html! {
+        <span class=classes>{ label }</span>
+    }
+}
+
+fn view_diff_line(line: &DiffLine) -> Html {
+    let mut classes = vec!["editor-conflict__line"];
+    let (marker, text) = match *line {
+        DiffLine::Unchanged(text) => (' ', text),
+        DiffLine::Removed(text) => {
+            classes.push("editor-conflict__line--removed");
+            ('-', text)
+        }
+        DiffLine::Added(text) => {
+            classes.push("editor-conflict__line--added");
+            ('+', text)
+        }
+    };
+
+    html! {
+        <div class=classes>{ format!("{} {}", marker, text) }</div>
+    }
 }
 impl Component for Editor {
     type Message = EditorMsg;
@@ -142,12 +612,28 @@ impl Component for Editor {
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let monaco_options =
             Rc::new(CodeEditorOptions::default().with_builtin_theme(BuiltinTheme::VsDark));
+        // populate the problems right away instead of waiting for the first change
+        link.send_message(EditorMsg::RunCheck);
+        let action_bar_callbacks =
+            Self::wrap_action_bar_callbacks(&link, &props.action_bar_callbacks);
+        let mut collab = CollabAgent::bridge(link.callback(EditorMsg::Collab));
+        collab.send(CollabRequest::Connect(Rc::clone(&props.session)));
         Self {
             props,
             link,
             tabs: Tabs::new(),
             selected: None,
             monaco_options,
+            action_bar_callbacks,
+            check_timeout: None,
+            check_task: None,
+            diagnostics: Rc::default(),
+            collab,
+            collab_connected: false,
+            you: None,
+            participants: Vec::new(),
+            selection_listener: None,
+            editor_link: None,
         }
     }
 
@@ -158,6 +644,10 @@ impl Component for Editor {
                 let id = self
                     .tabs
                     .find_or_create(&self.props.session, &self.link, file);
+                if let Some(previous) = self.selected.filter(|previous| *previous != id) {
+                    self.clear_selection(previous);
+                }
+                self.open_shared(id);
                 self.selected = Some(id);
                 true
             }
@@ -171,6 +661,9 @@ impl Component for Editor {
                 }
             }
             SelectTab(id) => {
+                if let Some(previous) = self.selected.filter(|previous| *previous != id) {
+                    self.clear_selection(previous);
+                }
                 self.selected = Some(id);
                 true
             }
@@ -181,58 +674,185 @@ impl Component for Editor {
                     self.selected = right.or(left).map(|tab| tab.id);
                 }
 
-                self.tabs.remove(id);
+                match self.tabs.remove(id) {
+                    Some(tab) if self.collab_connected && tab.is_shared() => {
+                        self.collab.send(CollabRequest::Send(ClientMessage::Close {
+                            path: tab.file.path.clone(),
+                        }))
+                    }
+                    _ => {}
+                }
                 true
             }
-            SaveTab(id) => {
+            SaveTab(id) => self.save_tab(id),
+            SaveResponse(id, resp) => {
                 if let Some(tab) = self.tabs.get_mut(id) {
-                    let ok = tab.save(
-                        &self.props.session,
-                        self.link
-                            .callback(move |resp| EditorMsg::SaveResponse(id, resp)),
-                    );
-                    if !ok {
-                        log::warn!("can't save file");
+                    match resp {
+                        // someone else saved the file in the meantime
+                        Err(err) if ApiError::is_conflict(&err) => tab.load_conflict(
+                            &self.props.session,
+                            self.link
+                                .callback(move |resp| EditorMsg::ConflictResponse(id, resp)),
+                        ),
+                        resp => tab.handle_save_response(resp),
                     }
                     true
                 } else {
+                    log::debug!("received response for tab which no longer exists: {}", id);
                     false
                 }
             }
-            SaveResponse(id, resp) => {
+            ConflictResponse(id, resp) => {
                 if let Some(tab) = self.tabs.get_mut(id) {
-                    tab.handle_save_response(resp);
+                    tab.handle_conflict_response(resp);
                     true
                 } else {
                     log::debug!("received response for tab which no longer exists: {}", id);
                     false
                 }
             }
+            ResolveConflict(id, resolution) => {
+                let tab = match self.tabs.get_mut(id) {
+                    Some(tab) => tab,
+                    None => return false,
+                };
+                let saved = match tab.conflict.take() {
+                    Some(saved) => saved,
+                    None => return false,
+                };
+
+                match resolution {
+                    Resolution::KeepMine => {
+                        tab.etag = saved.etag;
+                        self.save_tab(id)
+                    }
+                    Resolution::UseSaved => {
+                        tab.replace_content(saved);
+                        if tab.is_source() {
+                            self.schedule_check();
+                        }
+                        true
+                    }
+                }
+            }
+            ContentChanged(id, operation) => {
+                let tab = match self.tabs.get_mut(id).filter(|tab| !tab.read_only) {
+                    Some(tab) => tab,
+                    None => return false,
+                };
+                tab.transform_remote_selections(&operation);
+                let changed = match &mut tab.sync {
+                    // the collaboration server saves the changes
+                    Some(sync) => {
+                        match sync.local_edit(operation) {
+                            Ok(Some((revision, operation))) => {
+                                self.collab.send(CollabRequest::Send(ClientMessage::Edit {
+                                    path: tab.file.path.clone(),
+                                    revision,
+                                    operation,
+                                }))
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                log::warn!("{}: {}", tab.file.path, err);
+                                tab.stop_sync();
+                                self.collab.send(CollabRequest::Send(ClientMessage::Open {
+                                    path: tab.file.path.clone(),
+                                }));
+                            }
+                        }
+                        false
+                    }
+                    None => tab.dirty.neq_assign(true),
+                };
+                // the diff of a conflict shows the local content
+                let rerender = changed || tab.conflict.is_some();
+                if tab.is_source() {
+                    self.schedule_check();
+                }
+                rerender
+            }
+            EditorCreated(editor_link) => {
+                let on_select = self.link.callback(EditorMsg::SelectionChanged);
+                self.selection_listener = editor_link.with_editor(|editor| {
+                    editor.on_did_change_cursor_selection(move |event| {
+                        on_select.emit(event.selection())
+                    })
+                });
+                self.editor_link = Some(editor_link);
+                self.update_read_only();
+                false
+            }
+            SelectionChanged(selection) => {
+                self.share_selection(&selection);
+                false
+            }
+            Collab(event) => self.handle_collab_event(event),
+            RunCheck => {
+                self.run_check();
+                false
+            }
+            CheckResponse(resp) => {
+                self.check_task = None;
+                match resp {
+                    Ok(resp) => {
+                        self.diagnostics = Rc::new(resp.diagnostics);
+                        true
+                    }
+                    Err(err) => {
+                        log::error!("error while checking code: {}", err);
+                        false
+                    }
+                }
+            }
+            OpenOutput(target, code) => {
+                let id = self.tabs.open_output(target, &code);
+                self.selected = Some(id);
+                true
+            }
         }
     }
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
-        self.props.neq_assign(props)
+        if self.props.neq_assign(props) {
+            self.action_bar_callbacks =
+                Self::wrap_action_bar_callbacks(&self.link, &self.props.action_bar_callbacks);
+            self.collab
+                .send(CollabRequest::Connect(Rc::clone(&self.props.session)));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn rendered(&mut self, _first_render: bool) {
+        // the same editor is reused when switching tabs
+        self.update_read_only();
     }
 
     fn view(&self) -> Html {
-        let EditorProps {
-            session,
-            action_bar_callbacks,
-            ..
-        } = &self.props;
+        let session = &self.props.session;
         let onclick_file = self.link.callback(EditorMsg::OpenFile);
+        // visitors can only look at the code
+        let action_bar = if session.is_owner() {
+            html! {
+                <ActionBar session=Rc::clone(session) callbacks=self.action_bar_callbacks.clone() />
+            }
+        } else {
+            html! {
+                <span class="editor__read-only">{ locale::get("editor-read_only", None) }</span>
+            }
+        };
         html! {
             <div class="editor">
                 <Explorer session=Rc::clone(session) onclick_file=onclick_file />
                 { self.view_editor_window() }
-                <ActionBar session=Rc::clone(session) callbacks=action_bar_callbacks.clone() />
+                { action_bar }
             </div>
         }
     }
 }
 
-#[derive(Debug)]
 struct Tabs {
     tabs: Vec<Tab>,
     next_tab_id: usize,
@@ -255,6 +875,10 @@ impl Tabs {
         self.tabs.iter()
     }
 
+    fn iter_mut(&mut self) -> slice::IterMut<'_, Tab> {
+        self.tabs.iter_mut()
+    }
+
     fn surrounding_tabs(&self, id: TabIdentifier) -> (Option<&Tab>, Option<&Tab>) {
         if let Some(pos) = self.tabs.iter().position(|tab| tab.id == id) {
             let left = pos.checked_sub(1).and_then(|i| self.tabs.get(i));
@@ -278,6 +902,23 @@ impl Tabs {
         self.tabs.iter_mut().find(|tab| tab.id == id)
     }
 
+    fn find_by_path_mut(&mut self, path: &str) -> Option<&mut Tab> {
+        self.tabs.iter_mut().find(|tab| tab.file.path == path)
+    }
+
+    /// Get the content of all source files with unsaved changes.
+    fn unsaved_sources(&self) -> Vec<FileContent> {
+        self.iter()
+            .filter(|tab| tab.dirty && tab.is_source())
+            .filter_map(|tab| {
+                tab.model.as_ref().map(|model| FileContent {
+                    path: tab.file.path.clone(),
+                    content: model.get_value(),
+                })
+            })
+            .collect()
+    }
+
     fn create(
         &mut self,
         session: &Session,
@@ -290,11 +931,32 @@ impl Tabs {
             id,
             file,
             link.callback(move |resp| EditorMsg::FileResponse(id, resp)),
+            link.callback(move |operation| EditorMsg::ContentChanged(id, operation)),
         );
         self.tabs.push(tab);
         id
     }
 
+    /// Open a read-only tab containing the emitted code.
+    /// If there's already a tab for the target its content is replaced.
+    fn open_output(&mut self, target: EmitTarget, code: &str) -> TabIdentifier {
+        let path = output_path_for_target(target);
+        if let Some(tab) = self.tabs.iter().find(|tab| tab.file.path == path) {
+            if let Some(model) = &tab.model {
+                model.set_value(code);
+            }
+            return tab.id;
+        }
+
+        let id = self.generate_tab_id();
+        let file = protocol::File {
+            path,
+            name: locale::get(action_bar::emit_target_locale_id(target), None),
+        };
+        self.tabs.push(Tab::output(id, Rc::new(file), code));
+        id
+    }
+
     fn find_or_create(
         &mut self,
         session: &Session,
@@ -307,67 +969,251 @@ impl Tabs {
     }
 }
 
-#[derive(Debug)]
 struct Tab {
     id: TabIdentifier,
     file: Rc<protocol::File>,
     model: Option<TextModel>,
+    on_change: Callback<TextOperation>,
+    change_listener: Option<DisposableClosure<dyn FnMut(IModelContentChangedEvent)>>,
     state: ContentState,
+    kind: FileKind,
     dirty: bool,
+    read_only: bool,
+    /// Whether the tab shows a file of the sandbox.
+    in_sandbox: bool,
+    /// Entity tag of the content the local changes are based on.
+    etag: Option<String>,
+    /// Saved content which conflicts with the local changes.
+    conflict: Option<VersionedFile>,
+    /// State of the collaborative editing if the tab follows the edits of
+    /// the other participants.
+    sync: Option<DocumentSync>,
+    remote_selections: HashMap<ParticipantId, Selection>,
+    /// Identifiers of the decorations showing the remote selections.
+    decorations: Array,
 }
 impl Tab {
     fn open(
         session: &Session,
         id: TabIdentifier,
         file: Rc<protocol::File>,
-        callback: Callback<anyhow::Result<String>>,
+        callback: Callback<anyhow::Result<VersionedFile>>,
+        on_change: Callback<TextOperation>,
     ) -> Self {
-        let state = ContentState::load(session, &file.path, callback);
+        let kind = FileKind::from_path(&file.path);
+        // only text can be edited, everything else is shown in a preview
+        let (state, read_only) = if kind == FileKind::Text {
+            let state = ContentState::load(session, &file.path, callback);
+            (state, !session.is_owner())
+        } else {
+            (ContentState::Idle, true)
+        };
         Self {
             id,
             file,
             model: None,
+            on_change,
+            change_listener: None,
             state,
+            kind,
+            dirty: false,
+            read_only,
+            in_sandbox: true,
+            etag: None,
+            conflict: None,
+            sync: None,
+            remote_selections: HashMap::new(),
+            decorations: Array::new(),
+        }
+    }
+
+    /// Create a read-only tab which isn't backed by a file in the sandbox.
+    fn output(id: TabIdentifier, file: Rc<protocol::File>, code: &str) -> Self {
+        let uri = Uri::file(&file.path);
+        let model =
+            TextModel::get_or_create(&uri, code, None).expect("failed to create text model");
+        // the model might still exist from a previously closed tab
+        model.set_value(code);
+        Self {
+            id,
+            file,
+            model: Some(model),
+            on_change: Callback::noop(),
+            change_listener: None,
+            state: ContentState::Idle,
+            kind: FileKind::Text,
             dirty: false,
+            read_only: true,
+            in_sandbox: false,
+            etag: None,
+            conflict: None,
+            sync: None,
+            remote_selections: HashMap::new(),
+            decorations: Array::new(),
         }
     }
 
-    fn save(&mut self, session: &Session, callback: Callback<anyhow::Result<()>>) -> bool {
-        if self.state.is_loading() {
+    /// Whether the tab contains a Rust source file.
+    fn is_source(&self) -> bool {
+        self.file.path.starts_with("src/") && self.file.path.ends_with(".rs")
+    }
+
+    /// Whether the file can be edited together with the other participants.
+    fn is_shared(&self) -> bool {
+        self.in_sandbox && self.kind == FileKind::Text
+    }
+
+    fn save(
+        &mut self,
+        session: &Session,
+        callback: Callback<anyhow::Result<Option<String>>>,
+    ) -> bool {
+        // saving would only run into the same conflict again
+        if self.read_only || self.state.is_loading() || self.conflict.is_some() {
             return false;
         }
+        // the collaboration server already saved the changes
+        if self.sync.is_some() {
+            return true;
+        }
 
         if let Some(model) = &self.model {
-            self.state = ContentState::save(session, &self.file.path, model.get_value(), callback);
+            self.state = ContentState::save(
+                session,
+                &self.file.path,
+                model.get_value(),
+                self.etag.as_deref(),
+                callback,
+            );
             true
         } else {
             false
         }
     }
 
-    fn handle_load_response(&mut self, resp: anyhow::Result<String>) {
-        let state = &mut self.state;
-        if !state.is_loading() {
+    fn listen_for_changes(&mut self) {
+        if let Some(model) = &self.model {
+            let on_change = self.on_change.clone();
+            let changed_model = model.clone();
+            self.change_listener = Some(model.on_did_change_content(move |event| {
+                on_change.emit(operation_from_event(&changed_model, &event))
+            }));
+        }
+    }
+
+    fn create_model(&mut self, content: &str) {
+        let uri = Uri::file(&self.file.path);
+        let model =
+            TextModel::get_or_create(&uri, content, None).expect("failed to create text model");
+        // the model might still exist from a previously closed tab
+        model.set_value(content);
+        self.model = Some(model);
+        self.listen_for_changes();
+    }
+
+    /// Replace the content with the one of the collaboration server and
+    /// start following the edits of the other participants.
+    /// Returns `false` if there are unsaved changes, which would be lost.
+    fn start_sync(&mut self, revision: u64, content: &str) -> bool {
+        if self.dirty {
+            return false;
+        }
+
+        match &self.model {
+            Some(model) if model.get_value() == content => {}
+            Some(_) => self.replace_content(VersionedFile {
+                content: content.to_owned(),
+                etag: None,
+            }),
+            None => {
+                self.create_model(content);
+                // aborts the request for the file
+                self.state = ContentState::Idle;
+            }
+        }
+        self.sync = Some(DocumentSync::new(revision));
+        self.remote_selections.clear();
+        self.show_remote_selections();
+        true
+    }
+
+    fn stop_sync(&mut self) {
+        self.sync = None;
+        self.remote_selections.clear();
+        self.show_remote_selections();
+    }
+
+    /// Apply an edit made by another participant.
+    fn remote_edit(&mut self, revision: u64, operation: TextOperation) -> Result<(), OutOfSync> {
+        let (sync, model) = match (&mut self.sync, &self.model) {
+            (Some(sync), Some(model)) => (sync, model),
+            _ => return Ok(()),
+        };
+        let operation = sync.remote_edit(revision, operation)?;
+
+        // the edit isn't a change made by the user
+        self.change_listener = None;
+        apply_operation(model, &operation);
+        self.listen_for_changes();
+        self.transform_remote_selections(&operation);
+        Ok(())
+    }
+
+    /// Move the remote selections along with an edit.
+    fn transform_remote_selections(&mut self, operation: &TextOperation) {
+        for selection in self.remote_selections.values_mut() {
+            selection.anchor = operation.transform_index(selection.anchor);
+            selection.head = operation.transform_index(selection.head);
+        }
+    }
+
+    fn show_remote_selections(&mut self) {
+        let model: &ITextModel = match &self.model {
+            Some(model) => model.as_ref(),
+            None => return,
+        };
+        let decorations: Array = self
+            .remote_selections
+            .iter()
+            .flat_map(|(participant, selection)| {
+                selection_decorations(model, *participant, *selection)
+            })
+            .collect();
+        self.decorations = model.delta_decorations(&self.decorations, &decorations, None);
+    }
+
+    /// Replace the local content with the saved one, discarding all changes.
+    fn replace_content(&mut self, file: VersionedFile) {
+        if let Some(model) = &self.model {
+            // replacing the content isn't a change made by the user
+            self.change_listener = None;
+            model.set_value(&file.content);
+            self.listen_for_changes();
+        }
+        self.etag = file.etag;
+        self.dirty = false;
+    }
+
+    fn handle_load_response(&mut self, resp: anyhow::Result<VersionedFile>) {
+        if !self.state.is_loading() {
             log::debug!("ignoring response: not loading");
             return;
         }
 
         match resp {
-            Ok(content) => {
-                let uri = Uri::file(&self.file.path);
-                let model = TextModel::get_or_create(&uri, &content, None)
-                    .expect("failed to create text model");
-                self.model = Some(model);
-                *state = ContentState::Idle;
+            Ok(file) => {
+                self.create_model(&file.content);
+                self.etag = file.etag;
+                self.state = ContentState::Idle;
             }
             Err(err) => {
                 log::error!("error loading file: {}", err);
-                *state = ContentState::Failed(err);
+                self.state = ContentState::Failed(err);
             }
         }
     }
 
-    fn handle_save_response(&mut self, resp: anyhow::Result<()>) {
+    fn handle_save_response(&mut self, resp: anyhow::Result<Option<String>>) {
         let state = &mut self.state;
         if !state.is_loading() {
             log::debug!("ignoring response: not loading");
@@ -375,8 +1221,9 @@ impl Tab {
         }
 
         match resp {
-            Ok(_) => {
+            Ok(etag) => {
                 *state = ContentState::Idle;
+                self.etag = etag;
                 self.dirty = false;
             }
             Err(err) => {
@@ -385,16 +1232,47 @@ impl Tab {
             }
         }
     }
+
+    /// Load the saved content after saving failed because of a conflict.
+    fn load_conflict(
+        &mut self,
+        session: &Session,
+        callback: Callback<anyhow::Result<VersionedFile>>,
+    ) {
+        self.state = ContentState::load(session, &self.file.path, callback);
+    }
+
+    fn handle_conflict_response(&mut self, resp: anyhow::Result<VersionedFile>) {
+        if !self.state.is_loading() {
+            log::debug!("ignoring response: not loading");
+            return;
+        }
+
+        match resp {
+            Ok(file) => {
+                self.conflict = Some(file);
+                self.state = ContentState::Idle;
+            }
+            Err(err) => {
+                log::error!("error loading conflicting file: {}", err);
+                self.state = ContentState::Failed(err);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 enum ContentState {
-    Loading(FetchTask),
+    Loading(#[allow(dead_code)] FetchTask),
     Failed(anyhow::Error),
     Idle,
 }
 impl ContentState {
-    fn load(session: &Session, path: &str, callback: Callback<anyhow::Result<String>>) -> Self {
+    fn load(
+        session: &Session,
+        path: &str,
+        callback: Callback<anyhow::Result<VersionedFile>>,
+    ) -> Self {
         Self::Loading(
             session
                 .get_file(path, callback)
@@ -406,11 +1284,12 @@ impl ContentState {
         session: &Session,
         path: &str,
         content: String,
-        callback: Callback<anyhow::Result<()>>,
+        etag: Option<&str>,
+        callback: Callback<anyhow::Result<Option<String>>>,
     ) -> Self {
         Self::Loading(
             session
-                .upload_file(path, content, callback)
+                .upload_file(path, content, etag, callback)
                 .expect("failed to create save request"),
         )
     }
@@ -419,3 +1298,131 @@ impl ContentState {
         matches!(self, Self::Loading(_))
     }
 }
+
+/// Turn the changes of a model into an operation.
+fn operation_from_event(model: &TextModel, event: &IModelContentChangedEvent) -> TextOperation {
+    // the ranges of all changes refer to the content before the event
+    let mut changes: Vec<(u64, u64, String)> = event
+        .changes()
+        .iter()
+        .map(|change| {
+            let change: IModelContentChange = change.unchecked_into();
+            (
+                change.range_offset() as u64,
+                change.range_length() as u64,
+                change.text(),
+            )
+        })
+        .collect();
+    changes.sort_by_key(|(offset, ..)| *offset);
+
+    let inserted: u64 = changes.iter().map(|(_, _, text)| utf16_len(text)).sum();
+    let deleted: u64 = changes.iter().map(|(_, length, _)| length).sum();
+    let old_len = utf16_len(&model.get_value()) + deleted - inserted;
+
+    let mut operation = TextOperation::new();
+    let mut position = 0;
+    for (offset, length, text) in &changes {
+        operation
+            .retain(offset - position)
+            .delete(*length)
+            .insert(text);
+        position = offset + length;
+    }
+    operation.retain(old_len - position);
+    operation
+}
+
+/// Apply an operation to a model as a single edit.
+fn apply_operation(model: &TextModel, operation: &TextOperation) {
+    use OperationComponent::*;
+    // replaced ranges of the current content
+    let mut edits: Vec<(u64, u64, String)> = Vec::new();
+    let mut position = 0;
+    for component in operation.components() {
+        match component {
+            Retain(n) => position += n,
+            Insert(text) => edits.push((position, position, text.clone())),
+            Delete(n) => {
+                match edits.last_mut() {
+                    // inserts come before deletes at the same position
+                    Some(edit) if edit.1 == position => edit.1 += n,
+                    _ => edits.push((position, position + n, String::new())),
+                }
+                position += n;
+            }
+        }
+    }
+
+    let model: &ITextModel = model.as_ref();
+    let edits: Array = edits
+        .into_iter()
+        .map(|(start, end, text)| {
+            let edit: IIdentifiedSingleEditOperation = Object::new().unchecked_into();
+            edit.set_range(offset_range(model, start, end).unchecked_ref());
+            edit.set_text(Some(&text));
+            edit
+        })
+        .collect();
+    model.apply_edits(&edits);
+}
+
+/// Decorations showing the selection of another participant.
+fn selection_decorations(
+    model: &ITextModel,
+    participant: ParticipantId,
+    selection: Selection,
+) -> Vec<IModelDeltaDecoration> {
+    let color = collab::participant_color(participant);
+    let highlight: IModelDecorationOptions = Object::new().unchecked_into();
+    highlight.set_class_name(Some(&format!("collab-selection--{}", color)));
+    let cursor: IModelDecorationOptions = Object::new().unchecked_into();
+    cursor.set_before_content_class_name(Some(&format!("collab-cursor collab-cursor--{}", color)));
+
+    let start = selection.anchor.min(selection.head);
+    let end = selection.anchor.max(selection.head);
+    vec![
+        decoration(model, start, end, &highlight),
+        decoration(model, selection.head, selection.head, &cursor),
+    ]
+}
+
+fn decoration(
+    model: &ITextModel,
+    start: u64,
+    end: u64,
+    options: &IModelDecorationOptions,
+) -> IModelDeltaDecoration {
+    let decoration: IModelDeltaDecoration = Object::new().unchecked_into();
+    decoration.set_range(offset_range(model, start, end).unchecked_ref());
+    decoration.set_options(options);
+    decoration
+}
+
+/// Range between two offsets counted in UTF-16 code units.
+fn offset_range(model: &ITextModel, start: u64, end: u64) -> Range {
+    let start = model.get_position_at(start as f64);
+    let end = model.get_position_at(end as f64);
+    Range::new(
+        start.line_number(),
+        start.column(),
+        end.line_number(),
+        end.column(),
+    )
+}
+
+fn utf16_len(text: &str) -> u64 {
+    text.encode_utf16().count() as u64
+}
+
+/// Path of the virtual file which holds the emitted code for the target.
+fn output_path_for_target(target: EmitTarget) -> String {
+    use EmitTarget::*;
+    let extension = match target {
+        Mir => "mir",
+        LlvmIr => "ll",
+        Asm => "s",
+        Wat => "wat",
+    };
+    format!("output/compilation.{}", extension)
+}
@@ -1,17 +1,25 @@
 use super::icon::{Icon, Mdi};
 use crate::{
+    agents::collab::{CollabAgent, CollabEvent},
     services::{
-        api::{Session, SessionRef},
+        api::{ApiError, Session, SessionRef},
+        collab::ServerMessage,
         locale,
     },
     utils::NeqAssign,
 };
-use protocol::SandboxStructure;
+use protocol::{FileChange, SandboxStructure};
 use std::rc::Rc;
 use yew::{
+    agent::{Bridge, Bridged},
     html,
-    services::fetch::FetchTask,
+    services::{
+        fetch::FetchTask,
+        reader::{FileData, ReaderTask},
+        ReaderService,
+    },
     Callback,
+    ChangeData,
     Component,
     ComponentLink,
     Html,
@@ -19,9 +27,16 @@ use yew::{
     ShouldRender,
 };
 
+/// Directory uploaded files are placed in.
+const UPLOAD_DIR: &str = "public";
+
 #[derive(Debug)]
 pub enum ExplorerMsg {
     StructureLoaded(anyhow::Result<SandboxStructure>),
+    UploadFiles(Vec<web_sys::File>),
+    FileRead(FileData),
+    UploadResponse(anyhow::Result<()>),
+    Collab(CollabEvent),
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -30,11 +45,102 @@ pub struct ExplorerProps {
     pub onclick_file: Callback<Rc<protocol::File>>,
 }
 
-#[derive(Debug)]
 pub struct Explorer {
     props: ExplorerProps,
     link: ComponentLink<Self>,
     state: ExplorerState,
+    reader_tasks: Vec<ReaderTask>,
+    upload_tasks: Vec<FetchTask>,
+    pending_uploads: usize,
+    /// Error of the last failed upload.
+    upload_error: Option<anyhow::Error>,
+    /// Receives the files created and removed by others.
+    _collab: Box<dyn Bridge<CollabAgent>>,
+    /// Whether changes were missed because the connection was lost.
+    missed_changes: bool,
+}
+impl Explorer {
+    fn upload_files(&mut self, files: Vec<web_sys::File>) {
+        for file in files {
+            match ReaderService::new().read_file(file, self.link.callback(ExplorerMsg::FileRead)) {
+                Ok(task) => {
+                    self.reader_tasks.push(task);
+                    self.pending_uploads += 1;
+                }
+                Err(err) => log::error!("failed to read file: {}", err),
+            }
+        }
+    }
+
+    fn upload_file_data(&mut self, data: FileData) {
+        let path = format!("{}/{}", UPLOAD_DIR, data.name);
+        let task = self
+            .props
+            .session
+            .upload_binary_file(
+                &path,
+                data.content,
+                self.link.callback(ExplorerMsg::UploadResponse),
+            )
+            .expect("failed to create upload request");
+        self.upload_tasks.push(task);
+    }
+
+    /// Reload the structure once all uploads are done.
+    fn handle_upload_response(&mut self, resp: anyhow::Result<()>) -> ShouldRender {
+        if let Err(err) = resp {
+            log::error!("uploading file failed: {}", err);
+            // uploads never replace existing files
+            let err = if ApiError::is_conflict(&err) {
+                anyhow::anyhow!(locale::get("explorer-upload-exists", None))
+            } else {
+                err
+            };
+            self.upload_error = Some(err);
+        }
+
+        self.pending_uploads = self.pending_uploads.saturating_sub(1);
+        if self.pending_uploads > 0 {
+            return false;
+        }
+
+        self.reader_tasks.clear();
+        self.upload_tasks.clear();
+        self.state = ExplorerState::start(&self.props.session, self.link.clone());
+        true
+    }
+
+    fn view_upload(&self) -> Html {
+        if !self.props.session.is_owner() {
+            return html! {};
+        }
+
+        let onchange = self.link.batch_callback(|data| match data {
+            ChangeData::Files(list) => {
+                let files = (0..list.length()).filter_map(|i| list.get(i)).collect();
+                vec![ExplorerMsg::UploadFiles(files)]
+            }
+            _ => Vec::new(),
+        });
+
+        let error = if let Some(err) = &self.upload_error {
+            html! {
+                <span class="explorer__error">{ err.to_string() }</span>
+            }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <>
+                <label class="explorer__upload">
+                    { locale::get("explorer-upload", None) }
+                    <input type="file" multiple=true onchange=onchange />
+                </label>
+                { error }
+            </>
+        }
+    }
 }
 impl Component for Explorer {
     type Message = ExplorerMsg;
@@ -42,11 +148,48 @@ impl Component for Explorer {
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let state = ExplorerState::start(&props.session, link.clone());
-        Self { props, link, state }
+        let collab = CollabAgent::bridge(link.callback(ExplorerMsg::Collab));
+        Self {
+            props,
+            link,
+            state,
+            reader_tasks: Vec::new(),
+            upload_tasks: Vec::new(),
+            pending_uploads: 0,
+            upload_error: None,
+            _collab: collab,
+            missed_changes: false,
+        }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
-        self.state.update(msg)
+        use ExplorerMsg::*;
+        match msg {
+            StructureLoaded(resp) => self.state.handle_structure_response(resp),
+            UploadFiles(files) => {
+                self.upload_files(files);
+                self.upload_error.take().is_some()
+            }
+            FileRead(data) => {
+                self.upload_file_data(data);
+                false
+            }
+            UploadResponse(resp) => self.handle_upload_response(resp),
+            Collab(CollabEvent::Message(msg)) => match &*msg {
+                ServerMessage::FilesChanged { changes } => self.state.apply_changes(changes),
+                _ => false,
+            },
+            Collab(CollabEvent::Connected) if self.missed_changes => {
+                self.missed_changes = false;
+                self.state = ExplorerState::start(&self.props.session, self.link.clone());
+                true
+            }
+            Collab(CollabEvent::Connected) => false,
+            Collab(CollabEvent::Disconnected) => {
+                self.missed_changes = true;
+                false
+            }
+        }
     }
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
@@ -74,6 +217,7 @@ impl Component for Explorer {
                 html! {
                     <nav class="explorer">
                         <span class="explorer__header">{ locale::get("explorer-header", None) }</span>
+                        { self.view_upload() }
                         <Directory onclick_file=onclick_file.clone() start_open=true directory=Rc::clone(public) />
                         <Directory onclick_file=onclick_file.clone() start_open=true directory=Rc::clone(src) />
                     </nav>
@@ -85,8 +229,8 @@ impl Component for Explorer {
 
 #[derive(Debug)]
 enum ExplorerState {
-    Loading(FetchTask),
-    Failed(anyhow::Error),
+    Loading(#[allow(dead_code)] FetchTask),
+    Failed(#[allow(dead_code)] anyhow::Error),
     Loaded {
         public: Rc<protocol::Directory>,
         src: Rc<protocol::Directory>,
@@ -101,17 +245,33 @@ impl ExplorerState {
         )
     }
 
-    pub fn update(&mut self, msg: ExplorerMsg) -> ShouldRender {
-        use ExplorerMsg::*;
+    /// Apply the files created and removed by others.
+    pub fn apply_changes(&mut self, changes: &[FileChange]) -> ShouldRender {
+        let (public, src) = match self {
+            Self::Loaded { public, src } => (public, src),
+            // the structure which is being loaded already contains them
+            _ => return false,
+        };
 
-        match msg {
-            StructureLoaded(Ok(resp)) => {
+        let mut changed = false;
+        for change in changes {
+            changed |= Rc::make_mut(public).apply(change) || Rc::make_mut(src).apply(change);
+        }
+        changed
+    }
+
+    pub fn handle_structure_response(
+        &mut self,
+        resp: anyhow::Result<SandboxStructure>,
+    ) -> ShouldRender {
+        match resp {
+            Ok(resp) => {
                 let public = Rc::new(resp.public);
                 let src = Rc::new(resp.src);
                 *self = Self::Loaded { public, src };
                 true
             }
-            StructureLoaded(Err(err)) => {
+            Err(err) => {
                 log::error!("loading sandbox structure failed: {}", err);
                 *self = Self::Failed(err);
                 true
@@ -109,6 +109,7 @@ macro_rules! str_enum {
         }
     ) => {
         $(#[$meta])*
+        #[allow(dead_code)]
         #[derive(Copy, Clone, Debug, Eq, PartialEq)]
         pub enum $ident {
             $(
@@ -118,7 +119,7 @@ macro_rules! str_enum {
         }
         impl $ident {
             /// Get the variant value
-            pub fn to_value(&self) -> &'static str {
+            pub fn to_value(self) -> &'static str {
                 match self {
                     $(
                         Self::$v_ident => $v_value,
@@ -1,6 +1,9 @@
 pub mod action_bar;
 pub mod browser;
+pub mod compile_options;
 pub mod console;
 pub mod editor;
 pub mod explorer;
 pub mod icon;
+pub mod problems;
+pub mod size_panel;
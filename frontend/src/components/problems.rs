@@ -0,0 +1,87 @@
+use crate::{
+    services::{
+        api::{Diagnostic, DiagnosticLevel},
+        locale,
+    },
+    utils::NeqAssign,
+};
+use std::rc::Rc;
+use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct ProblemsProps {
+    pub diagnostics: Rc<Vec<Diagnostic>>,
+}
+
+#[derive(Debug)]
+pub struct Problems {
+    props: ProblemsProps,
+}
+impl Problems {
+    fn view_diagnostic(diagnostic: &Diagnostic) -> Html {
+        let (class, level) = match diagnostic.level {
+            DiagnosticLevel::Error => ("problems__item--error", "problems-level-error"),
+            DiagnosticLevel::Warning => ("problems__item--warning", "problems-level-warning"),
+            DiagnosticLevel::Note => ("problems__item--note", "problems-level-note"),
+            DiagnosticLevel::Help => ("problems__item--help", "problems-level-help"),
+        };
+
+        let location = diagnostic
+            .spans
+            .iter()
+            .find(|span| span.is_primary)
+            .map(|span| {
+                format!(
+                    "{}:{}:{}",
+                    span.file_name, span.line_start, span.column_start
+                )
+            })
+            .unwrap_or_default();
+
+        html! {
+            <li class=vec!["problems__item", class]>
+                <span class="problems__level">{ locale::get(level, None) }</span>
+                <span class="problems__message">{ &diagnostic.message }</span>
+                <span class="problems__location">{ location }</span>
+            </li>
+        }
+    }
+}
+impl Component for Problems {
+    type Message = ();
+    type Properties = ProblemsProps;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        let diagnostics = &self.props.diagnostics;
+        let content = if diagnostics.is_empty() {
+            html! {
+                <span class="problems__empty">{ locale::get("problems-empty", None) }</span>
+            }
+        } else {
+            html! {
+                <ul class="problems__list">
+                    { for diagnostics.iter().map(Self::view_diagnostic) }
+                </ul>
+            }
+        };
+
+        html! {
+            <div class="problems">
+                <span class="problems__header">{ locale::get("problems-header", None) }</span>
+                { content }
+            </div>
+        }
+    }
+}
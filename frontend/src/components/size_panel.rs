@@ -0,0 +1,154 @@
+use crate::{
+    services::{api::SizeReport, locale},
+    utils::{format_size, NeqAssign},
+};
+use std::{cmp::Ordering, collections::HashMap, rc::Rc};
+use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+
+/// Maximum number of functions shown in the table.
+const MAX_FUNCTIONS: usize = 20;
+
+fn view_delta(current: u64, previous: Option<u64>) -> Html {
+    let previous = match previous {
+        Some(v) => v,
+        None => return html! { <td class="size-panel__delta" /> },
+    };
+
+    let (class, text) = match current.cmp(&previous) {
+        Ordering::Greater => (
+            "size-panel__delta--grown",
+            format!("+{}", format_size(current - previous)),
+        ),
+        Ordering::Less => (
+            "size-panel__delta--shrunk",
+            format!("-{}", format_size(previous - current)),
+        ),
+        Ordering::Equal => ("size-panel__delta--same", String::from("±0")),
+    };
+
+    html! {
+        <td class=vec!["size-panel__delta", class]>{ text }</td>
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Properties)]
+pub struct SizePanelProps {
+    #[prop_or_default]
+    pub current: Option<Rc<SizeReport>>,
+    #[prop_or_default]
+    pub previous: Option<Rc<SizeReport>>,
+}
+
+#[derive(Debug)]
+pub struct SizePanel {
+    props: SizePanelProps,
+}
+impl SizePanel {
+    fn view_crates(current: &SizeReport, previous: Option<&SizeReport>) -> Html {
+        let previous_sizes: HashMap<&str, u64> = previous
+            .map(|report| {
+                report
+                    .crates
+                    .iter()
+                    .map(|krate| (krate.name.as_str(), krate.size))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rows = current.crates.iter().map(|krate| {
+            html! {
+                <tr>
+                    <td>{ &krate.name }</td>
+                    <td>{ format_size(krate.size) }</td>
+                    { view_delta(krate.size, previous_sizes.get(krate.name.as_str()).copied()) }
+                </tr>
+            }
+        });
+
+        html! {
+            <table class="size-panel__table">
+                <caption>{ locale::get("size_panel-crates", None) }</caption>
+                { for rows }
+            </table>
+        }
+    }
+
+    fn view_functions(current: &SizeReport, previous: Option<&SizeReport>) -> Html {
+        let previous_sizes: HashMap<&str, u64> = previous
+            .map(|report| {
+                report
+                    .functions
+                    .iter()
+                    .map(|function| (function.name.as_str(), function.shallow_size))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rows = current.functions.iter().take(MAX_FUNCTIONS).map(|function| {
+            let retained_size = function
+                .retained_size
+                .map(format_size)
+                .unwrap_or_default();
+            html! {
+                <tr>
+                    <td class="size-panel__name">{ &function.name }</td>
+                    <td>{ format_size(function.shallow_size) }</td>
+                    <td>{ retained_size }</td>
+                    { view_delta(function.shallow_size, previous_sizes.get(function.name.as_str()).copied()) }
+                </tr>
+            }
+        });
+
+        html! {
+            <table class="size-panel__table">
+                <caption>{ locale::get("size_panel-functions", None) }</caption>
+                { for rows }
+            </table>
+        }
+    }
+}
+impl Component for SizePanel {
+    type Message = ();
+    type Properties = SizePanelProps;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        let SizePanelProps { current, previous } = &self.props;
+        let current = match current {
+            Some(v) => v,
+            None => {
+                return html! {
+                    <div class="size-panel">
+                        { locale::get("size_panel-empty", None) }
+                    </div>
+                };
+            }
+        };
+        let previous = previous.as_deref();
+
+        html! {
+            <div class="size-panel">
+                <table class="size-panel__table">
+                    <tr>
+                        <td>{ locale::get("size_panel-total", None) }</td>
+                        <td>{ format_size(current.total_size) }</td>
+                        { view_delta(current.total_size, previous.map(|report| report.total_size)) }
+                    </tr>
+                </table>
+                { Self::view_crates(current, previous) }
+                { Self::view_functions(current, previous) }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    services::{
+        api::{Admin, JobDetails, SessionStatus},
+        locale,
+    },
+    utils::{format_duration, format_size},
+};
+use yew::{
+    html,
+    services::fetch::FetchTask,
+    ChangeData,
+    Component,
+    ComponentLink,
+    Html,
+    ShouldRender,
+};
+
+#[derive(Debug)]
+pub enum AdminPageMsg {
+    SetToken(String),
+    Refresh,
+    Expire(String),
+    Sessions(anyhow::Result<Vec<SessionStatus>>),
+    Jobs(anyhow::Result<Vec<JobDetails>>),
+    Expired(anyhow::Result<()>),
+}
+
+/// Overview of the sessions and the running jobs for operators.
+#[derive(Debug)]
+pub struct AdminPage {
+    link: ComponentLink<Self>,
+    admin: Option<Admin>,
+    sessions: Vec<SessionStatus>,
+    jobs: Vec<JobDetails>,
+    error: Option<anyhow::Error>,
+    tasks: Vec<FetchTask>,
+}
+impl AdminPage {
+    fn refresh(&mut self) {
+        let admin = match &self.admin {
+            Some(admin) => admin,
+            None => return,
+        };
+
+        let tasks = vec![
+            admin.list_sessions(self.link.callback(AdminPageMsg::Sessions)),
+            admin.list_jobs(self.link.callback(AdminPageMsg::Jobs)),
+        ];
+        self.tasks = tasks
+            .into_iter()
+            .filter_map(|task| {
+                task.map_err(|err| log::error!("failed to create admin request: {}", err))
+                    .ok()
+            })
+            .collect();
+    }
+
+    fn handle_error(&mut self, err: anyhow::Error) {
+        log::error!("admin request failed: {}", err);
+        self.error = Some(err);
+    }
+
+    fn view_sessions(&self) -> Html {
+        if self.sessions.is_empty() {
+            return html! { <p>{ locale::get("admin-sessions-empty", None) }</p> };
+        }
+
+        let rows = self.sessions.iter().map(|session| {
+            let id = session.id.clone();
+            let onclick = self
+                .link
+                .callback(move |_| AdminPageMsg::Expire(id.clone()));
+            let jobs = session
+                .jobs
+                .iter()
+                .map(|job| job.tool.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            html! {
+                <tr>
+                    <td class="admin__id">{ &session.id }</td>
+                    <td>{ format_duration(session.age) }</td>
+                    <td>{ format_duration(session.idle) }</td>
+                    <td>{ format_size(session.disk_usage) }</td>
+                    <td>{ jobs }</td>
+                    <td>
+                        <button onclick=onclick>
+                            { locale::get("admin-session-expire", None) }
+                        </button>
+                    </td>
+                </tr>
+            }
+        });
+
+        html! {
+            <table class="admin__table">
+                <tr>
+                    <th>{ locale::get("admin-session-id", None) }</th>
+                    <th>{ locale::get("admin-session-age", None) }</th>
+                    <th>{ locale::get("admin-session-idle", None) }</th>
+                    <th>{ locale::get("admin-session-disk_usage", None) }</th>
+                    <th>{ locale::get("admin-session-jobs", None) }</th>
+                    <th />
+                </tr>
+                { for rows }
+            </table>
+        }
+    }
+
+    fn view_jobs(&self) -> Html {
+        if self.jobs.is_empty() {
+            return html! { <p>{ locale::get("admin-jobs-empty", None) }</p> };
+        }
+
+        let rows = self.jobs.iter().map(|job| {
+            html! {
+                <tr>
+                    <td>{ &job.tool }</td>
+                    <td class="admin__id">{ job.session.as_deref().unwrap_or("-") }</td>
+                    <td>{ format_duration(job.running_for) }</td>
+                </tr>
+            }
+        });
+
+        html! {
+            <table class="admin__table">
+                <tr>
+                    <th>{ locale::get("admin-job-tool", None) }</th>
+                    <th>{ locale::get("admin-job-session", None) }</th>
+                    <th>{ locale::get("admin-job-running_for", None) }</th>
+                </tr>
+                { for rows }
+            </table>
+        }
+    }
+}
+impl Component for AdminPage {
+    type Message = AdminPageMsg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            admin: None,
+            sessions: Vec::new(),
+            jobs: Vec::new(),
+            error: None,
+            tasks: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        use AdminPageMsg::*;
+        match msg {
+            SetToken(token) => {
+                self.admin = Some(Admin::new(token));
+                self.refresh();
+                false
+            }
+            Refresh => {
+                self.refresh();
+                false
+            }
+            Expire(id) => {
+                let task = self.admin.as_ref().map(|admin| {
+                    admin.expire_session(&id, self.link.callback(AdminPageMsg::Expired))
+                });
+                match task {
+                    Some(Ok(task)) => self.tasks.push(task),
+                    Some(Err(err)) => log::error!("failed to create admin request: {}", err),
+                    None => {}
+                }
+                false
+            }
+            Sessions(Ok(sessions)) => {
+                self.sessions = sessions;
+                self.error = None;
+                true
+            }
+            Jobs(Ok(jobs)) => {
+                self.jobs = jobs;
+                self.error = None;
+                true
+            }
+            Expired(Ok(())) => {
+                self.refresh();
+                false
+            }
+            Sessions(Err(err)) | Jobs(Err(err)) | Expired(Err(err)) => {
+                self.handle_error(err);
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let onchange_token = self.link.batch_callback(|data| match data {
+            ChangeData::Value(token) if !token.is_empty() => vec![AdminPageMsg::SetToken(token)],
+            _ => Vec::new(),
+        });
+        let onclick_refresh = self.link.callback(|_| AdminPageMsg::Refresh);
+        let error = match &self.error {
+            Some(err) => html! { <p class="admin__error">{ err.to_string() }</p> },
+            None => html! {},
+        };
+
+        html! {
+            <div class="admin">
+                <h1>{ locale::get("admin-title", None) }</h1>
+                <div class="admin__controls">
+                    <input
+                        type="password"
+                        placeholder=locale::get("admin-token", None)
+                        onchange=onchange_token
+                    />
+                    <button onclick=onclick_refresh disabled=self.admin.is_none()>
+                        { locale::get("admin-refresh", None) }
+                    </button>
+                </div>
+                { error }
+                <h2>{ locale::get("admin-sessions", None) }</h2>
+                { self.view_sessions() }
+                <h2>{ locale::get("admin-jobs", None) }</h2>
+                { self.view_jobs() }
+            </div>
+        }
+    }
+}
@@ -75,8 +75,8 @@ impl Component for HomePage {
 #[derive(Debug)]
 enum SessionState {
     Idle,
-    Loading(FetchTask),
-    Failed(anyhow::Error),
+    Loading(#[allow(dead_code)] FetchTask),
+    Failed(#[allow(dead_code)] anyhow::Error),
 }
 impl SessionState {
     fn start(&mut self, callback: Callback<anyhow::Result<Session>>) {
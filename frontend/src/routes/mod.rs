@@ -1,8 +1,10 @@
+use admin::AdminPage;
 use home::HomePage;
 use sandbox::SandboxPage;
 use yew::{html, Component, ComponentLink, Html, ShouldRender};
 use yew_router::{router::Router, Switch};
 
+mod admin;
 mod home;
 mod sandbox;
 
@@ -10,6 +12,8 @@ mod sandbox;
 pub enum AppSwitch {
     #[to = "/s/{id}"]
     Sandbox(String),
+    #[to = "/admin"]
+    Admin,
     #[to = "/"]
     Home,
     #[rest]
@@ -26,6 +30,7 @@ impl AppRouter {
             // TODO handle NotFound
             Home | NotFound(_) => html! { <HomePage /> },
             Sandbox(id) => html! { <SandboxPage id=id /> },
+            Admin => html! { <AdminPage /> },
         }
     }
 }
@@ -4,24 +4,37 @@ use crate::{
         browser::{Browser, Controller as BrowserController},
         console::{Console, ConsoleProps},
         editor::Editor,
+        size_panel::SizePanel,
     },
     services::api::{
-        ClippyResponse,
         CompileResponse,
-        FormatResponse,
-        MacroExpandResponse,
+        EmitResponse,
+        EmitTarget,
         Session,
         SessionRef,
+        SizeReport,
+        SizeResponse,
+        ToolOutput,
     },
     utils::NeqAssign,
 };
 use std::rc::Rc;
-use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+use yew::{
+    html,
+    services::fetch::FetchTask,
+    Component,
+    ComponentLink,
+    Html,
+    Properties,
+    ShouldRender,
+};
 
 #[derive(Debug)]
 pub enum SandboxPageMsg {
     ReloadBrowser,
-    DisplayOutput { stdout: String, stderr: String },
+    DisplayOutput(ToolOutput),
+    AnalyseSize,
+    SizeResponse(anyhow::Result<SizeResponse>),
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -37,6 +50,9 @@ pub struct SandboxPage {
     browser_controller: BrowserController,
     console_props: ConsoleProps,
     action_bar_callbacks: ActionBarCallbacks,
+    size_task: Option<FetchTask>,
+    /// Size reports of the current and the previous build.
+    size_reports: (Option<Rc<SizeReport>>, Option<Rc<SizeReport>>),
 }
 impl Component for SandboxPage {
     type Message = SandboxPageMsg;
@@ -53,6 +69,8 @@ impl Component for SandboxPage {
             browser_controller: BrowserController::default(),
             console_props: ConsoleProps::default(),
             action_bar_callbacks,
+            size_task: None,
+            size_reports: (None, None),
         }
     }
 
@@ -63,10 +81,39 @@ impl Component for SandboxPage {
                 self.browser_controller.reload();
                 false
             }
-            DisplayOutput { stdout, stderr } => {
-                self.console_props = ConsoleProps { stderr, stdout };
+            DisplayOutput(output) => {
+                self.console_props = ConsoleProps {
+                    output: Some(output),
+                };
                 true
             }
+            AnalyseSize => {
+                self.size_task = Some(
+                    self.session
+                        .size(self.link.callback(SizeResponse))
+                        .expect("failed to create size request"),
+                );
+                false
+            }
+            SizeResponse(resp) => {
+                self.size_task = None;
+                match resp {
+                    Ok(resp) => {
+                        if let Some(report) = resp.report {
+                            let (current, previous) = &mut self.size_reports;
+                            *previous = current.replace(Rc::new(report));
+                            true
+                        } else {
+                            log::warn!("size analysis failed: {}", resp.stderr);
+                            false
+                        }
+                    }
+                    Err(err) => {
+                        log::error!("error while analysing the size: {}", err);
+                        false
+                    }
+                }
+            }
         }
     }
 
@@ -80,6 +127,7 @@ impl Component for SandboxPage {
             browser_controller,
             console_props,
             action_bar_callbacks,
+            size_reports: (current_size, previous_size),
             ..
         } = self;
 
@@ -89,6 +137,7 @@ impl Component for SandboxPage {
             <main>
                 <Editor session=Rc::clone(session) action_bar_callbacks=action_bar_callbacks.clone() />
                 <Console with console_props />
+                <SizePanel current=current_size.clone() previous=previous_size.clone() />
                 <Browser session=Rc::clone(session) controller=browser_controller />
             </main>
         }
@@ -99,26 +148,18 @@ fn build_action_bar_callbacks(link: &ComponentLink<SandboxPage>) -> ActionBarCal
     use SandboxPageMsg::*;
     ActionBarCallbacks {
         compile: link.batch_callback(|res: CompileResponse| {
-            vec![
-                ReloadBrowser,
-                DisplayOutput {
-                    stdout: res.stdout,
-                    stderr: res.stderr,
-                },
-            ]
-        }),
-        format: link.callback(|res: FormatResponse| DisplayOutput {
-            stdout: res.stdout,
-            stderr: res.stderr,
-        }),
-        clippy: link.callback(|res: ClippyResponse| DisplayOutput {
-            stdout: res.stdout,
-            stderr: res.stderr,
+            let mut msgs = vec![ReloadBrowser];
+            if res.output.success {
+                msgs.push(AnalyseSize);
+            }
+            msgs.push(DisplayOutput(res.output));
+            msgs
         }),
+        format: link.callback(DisplayOutput),
+        clippy: link.callback(DisplayOutput),
         // TODO display the result in a new tab instead of the console
-        macro_expand: link.callback(|res: MacroExpandResponse| DisplayOutput {
-            stdout: res.stdout,
-            stderr: res.stderr,
-        }),
+        macro_expand: link.callback(DisplayOutput),
+        // the code itself is shown in the editor
+        emit: link.callback(|(_, res): (EmitTarget, EmitResponse)| DisplayOutput(res.output)),
     }
 }
@@ -1,30 +1,187 @@
+use super::locale;
+use crate::utils::format_size;
+use fluent::{FluentArgs, FluentValue};
+use http::request::Builder as RequestBuilder;
 pub use protocol::{
+    ApiVersion,
     Channel,
-    ClippyResponse,
+    CheckResponse,
     CompileResponse,
-    Edition,
-    FormatResponse,
-    MacroExpandResponse,
+    Diagnostic,
+    DiagnosticLevel,
+    EmitResponse,
+    EmitTarget,
+    FileContent,
+    JobDetails,
+    Lto,
     Mode,
+    OptLevel,
     SandboxStructure,
     SessionDetails,
+    SessionStatus,
+    SizeReport,
+    SizeResponse,
+    ToolOutput,
+    WasmOptLevel,
+};
+use protocol::{
+    CheckRequest,
+    ClippyRequest,
+    CompileRequest,
+    EmitRequest,
+    FormatRequest,
+    MacroExpandRequest,
+    QuotaKind,
+    SizeRequest,
 };
-use protocol::{ClippyRequest, CompileRequest, FormatRequest, MacroExpandRequest};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, rc::Rc};
 use yew::{
-    format::{Json, Nothing, Text},
-    services::fetch::{FetchService, FetchTask, Request, Response},
+    format::{Binary, Json, Nothing, Text},
+    services::{
+        fetch::{FetchService, FetchTask, Request, Response},
+        storage::{Area, StorageService},
+    },
     Callback,
 };
 
+/// Error reported by the server.
+/// It's displayed as a localised message.
+#[derive(Debug, thiserror::Error)]
+#[error("{}", describe_error(.0))]
+pub struct ApiError(pub protocol::Error);
+
+fn describe_error(err: &protocol::Error) -> String {
+    use protocol::Error::*;
+    match err {
+        InternalError { message } => {
+            let mut args = FluentArgs::new();
+            args.insert("message", FluentValue::from(message.as_str()));
+            locale::get("error-internal", Some(&args))
+        }
+        InvalidRequest { message } => {
+            let mut args = FluentArgs::new();
+            args.insert("message", FluentValue::from(message.as_str()));
+            locale::get("error-invalid_request", Some(&args))
+        }
+        InvalidPath => locale::get("error-invalid_path", None),
+        SessionNotFound => locale::get("error-session_not_found", None),
+        NotSessionOwner => locale::get("error-not_session_owner", None),
+        NotAdmin => locale::get("error-not_admin", None),
+        SandboxFileNotFound => locale::get("error-sandbox_file_not_found", None),
+        FileConflict { .. } => locale::get("error-file_conflict", None),
+        PreconditionRequired => locale::get("error-precondition_required", None),
+        ShuttingDown => locale::get("error-shutting_down", None),
+        QuotaExceeded { quota, limit } => {
+            let (id, limit) = match quota {
+                QuotaKind::FileSize => ("error-quota-file_size", format_size(*limit)),
+                QuotaKind::FileCount => ("error-quota-file_count", limit.to_string()),
+                QuotaKind::TotalSize => ("error-quota-total_size", format_size(*limit)),
+                QuotaKind::BuildSize => ("error-quota-build_size", format_size(*limit)),
+            };
+            let mut args = FluentArgs::new();
+            args.insert("limit", FluentValue::from(limit));
+            locale::get(id, Some(&args))
+        }
+        RateLimited { retry_after } => {
+            let mut args = FluentArgs::new();
+            args.insert("seconds", FluentValue::from(retry_after.to_string()));
+            locale::get("error-rate_limited", Some(&args))
+        }
+        ToolUnavailable => locale::get("error-tool_unavailable", None),
+        ToolTimedOut { timeout_ms } => {
+            let mut args = FluentArgs::new();
+            args.insert(
+                "seconds",
+                FluentValue::from((timeout_ms / 1000).to_string()),
+            );
+            locale::get("error-tool_timed_out", Some(&args))
+        }
+        BuildMissing => locale::get("error-build_missing", None),
+    }
+}
+
+/// Turn responses with an error status into an `ApiError`.
+fn check_status<B>(response: Response<B>) -> anyhow::Result<B>
+where
+    Json<anyhow::Result<protocol::Error>>: From<B>,
+{
+    let (parts, body) = response.into_parts();
+    if parts.status.is_success() {
+        return Ok(body);
+    }
+
+    match Json::<anyhow::Result<protocol::Error>>::from(body).0 {
+        Ok(err) => Err(ApiError(err).into()),
+        Err(_) => Err(anyhow::anyhow!(
+            "request failed with status {}",
+            parts.status
+        )),
+    }
+}
+
+impl ApiError {
+    /// Check whether the error means that the file was modified by someone
+    /// else.
+    pub fn is_conflict(err: &anyhow::Error) -> bool {
+        matches!(
+            err.downcast_ref::<Self>(),
+            Some(Self(protocol::Error::FileConflict { .. }))
+        )
+    }
+}
+
+/// Get the entity tag of a response.
+fn response_etag<B>(response: &Response<B>) -> Option<String> {
+    response
+        .headers()
+        .get("ETag")
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+/// Attach the owner token to a request.
+fn authorized(builder: RequestBuilder, owner_token: Option<&str>) -> RequestBuilder {
+    match owner_token {
+        Some(token) => builder.header("Authorization", format!("Bearer {}", token)),
+        None => builder,
+    }
+}
+
+/// Make a write conditional.
+/// With an entity tag the file is only replaced if it still has that version,
+/// otherwise it's only written if it doesn't exist yet.
+fn conditional(builder: RequestBuilder, etag: Option<&str>) -> RequestBuilder {
+    match etag {
+        Some(etag) => builder.header("If-Match", etag),
+        None => builder.header("If-None-Match", "*"),
+    }
+}
+
 fn make_api_uri(path: impl Display) -> String {
     // TODO configurable api endpoint
     format!("/api{}", path)
 }
 
+/// Percent-encode every segment of a sandbox path.
+fn encode_file_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| String::from(js_sys::encode_uri_component(segment)))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Get the protocol version and capabilities of the server.
+pub fn get_version(callback: Callback<anyhow::Result<ApiVersion>>) -> anyhow::Result<FetchTask> {
+    let req = Request::get(make_api_uri("/version"))
+        .body(Nothing)
+        .unwrap();
+    perform_json_request(req, callback)
+}
+
 pub fn create_session(callback: Callback<anyhow::Result<Session>>) -> anyhow::Result<FetchTask> {
     post_json(
+        None,
         "/sandbox",
         &(),
         Callback::from(move |resp: anyhow::Result<SessionDetails>| {
@@ -33,16 +190,79 @@ pub fn create_session(callback: Callback<anyhow::Result<Session>>) -> anyhow::Re
     )
 }
 
+/// Options which change how the code is compiled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompileOptions {
+    pub mode: Mode,
+    pub opt_level: Option<OptLevel>,
+    pub lto: Option<Lto>,
+    pub codegen_units: Option<u32>,
+    pub wasm_opt: Option<WasmOptLevel>,
+}
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Debug,
+            opt_level: None,
+            lto: None,
+            codegen_units: None,
+            wasm_opt: None,
+        }
+    }
+}
+
+/// Content of a sandbox file together with its version.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionedFile {
+    pub content: String,
+    /// Entity tag of the content.
+    /// Sent back when saving the file to detect conflicting changes.
+    pub etag: Option<String>,
+}
+
 pub type SessionRef = Rc<Session>;
 
+/// Key under which the owner token of a session is stored.
+fn owner_token_key(id: &str) -> String {
+    format!("playground.owner_token.{}", id)
+}
+
+fn local_storage() -> Option<StorageService> {
+    StorageService::new(Area::Local)
+        .map_err(|err| log::warn!("local storage isn't available: {}", err))
+        .ok()
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Session {
     pub id: String,
     pub details: Option<SessionDetails>,
+    /// Token which grants write access.
+    /// Only the creator of the session has it.
+    owner_token: Option<String>,
 }
 impl Session {
+    /// Create a session using the owner token stored for it.
     pub fn new(id: String) -> Self {
-        Self { id, details: None }
+        let owner_token = local_storage().and_then(|storage| {
+            let token: Text = storage.restore(&owner_token_key(&id));
+            token.ok()
+        });
+        Self {
+            id,
+            details: None,
+            owner_token,
+        }
+    }
+
+    /// Whether the session can be modified.
+    /// Everyone else can only view it.
+    pub fn is_owner(&self) -> bool {
+        self.owner_token.is_some()
+    }
+
+    fn owner_token(&self) -> Option<&str> {
+        self.owner_token.as_deref()
     }
 
     pub fn get_structure(
@@ -56,38 +276,87 @@ impl Session {
         perform_json_request(req, callback)
     }
 
+    /// Get the uri of the collaboration WebSocket.
+    /// The owner token is included so that the owner can edit the files.
+    pub fn collab_uri(&self) -> String {
+        let location = yew::utils::window().location();
+        let scheme = match location.protocol() {
+            Ok(protocol) if protocol == "https:" => "wss",
+            _ => "ws",
+        };
+        let host = location.hostname().unwrap_or_default();
+        // TODO configurable collaboration endpoint
+        let mut uri = format!("{}://{}:8001/collab/{}", scheme, host, self.id);
+        if let Some(token) = self.owner_token() {
+            uri.push_str(&format!("?token={}", token));
+        }
+        uri
+    }
+
+    /// Get the uri which serves the file.
+    /// Used for content which can't be loaded as text (ex. images).
+    pub fn file_uri(&self, path: &str) -> String {
+        make_api_uri(format!("/{}/files/{}", self.id, encode_file_path(path)))
+    }
+
     pub fn get_file(
         &self,
         path: &str,
-        callback: Callback<anyhow::Result<String>>,
+        callback: Callback<anyhow::Result<VersionedFile>>,
     ) -> anyhow::Result<FetchTask> {
-        let req = Request::get(make_api_uri(format!("/{}/files/{}", self.id, path)))
-            .body(Nothing)
-            .unwrap();
+        let req = Request::get(self.file_uri(path)).body(Nothing).unwrap();
 
         FetchService::fetch(
             req,
             Callback::from(move |response: Response<Text>| {
-                let body = response.into_body();
-                callback.emit(body)
+                let etag = response_etag(&response);
+                let file = check_status(response)
+                    .and_then(|body| body)
+                    .map(|content| VersionedFile { content, etag });
+                callback.emit(file)
             }),
         )
     }
 
+    /// Save a file.
+    /// `etag` is the entity tag of the version the changes are based on or
+    /// `None` for new files.
+    /// The callback receives the entity tag of the saved content.
     pub fn upload_file(
         &self,
         path: &str,
         content: String,
-        callback: Callback<anyhow::Result<()>>,
+        etag: Option<&str>,
+        callback: Callback<anyhow::Result<Option<String>>>,
     ) -> anyhow::Result<FetchTask> {
-        let req = Request::put(make_api_uri(format!("/{}/files/{}", self.id, path)))
-            .body(Ok(content))
-            .unwrap();
+        let builder = authorized(Request::put(self.file_uri(path)), self.owner_token());
+        let req = conditional(builder, etag).body(Ok(content)).unwrap();
 
         FetchService::fetch(
             req,
             Callback::from(move |response: Response<Text>| {
-                let body = response.into_body().map(|_| ());
+                let etag = response_etag(&response);
+                let body = check_status(response).and_then(|body| body.map(|_| etag));
+                callback.emit(body)
+            }),
+        )
+    }
+
+    /// Upload a new file.
+    /// Existing files aren't replaced.
+    pub fn upload_binary_file(
+        &self,
+        path: &str,
+        content: Vec<u8>,
+        callback: Callback<anyhow::Result<()>>,
+    ) -> anyhow::Result<FetchTask> {
+        let builder = authorized(Request::put(self.file_uri(path)), self.owner_token());
+        let req = conditional(builder, None).body(Ok(content)).unwrap();
+
+        FetchService::fetch_binary(
+            req,
+            Callback::from(move |response: Response<Binary>| {
+                let body = check_status(response).and_then(|body| body.map(|_| ()));
                 callback.emit(body)
             }),
         )
@@ -95,51 +364,195 @@ impl Session {
 
     pub fn compile(
         &self,
+        options: &CompileOptions,
         callback: Callback<anyhow::Result<CompileResponse>>,
     ) -> anyhow::Result<FetchTask> {
         // TODO have these settings be stored on the server
         let body = CompileRequest {
             channel: Channel::Stable,
-            mode: Mode::Debug,
+            mode: options.mode,
             edition: None,
             backtrace: false,
+            opt_level: options.opt_level,
+            lto: options.lto,
+            codegen_units: options.codegen_units,
+            wasm_opt: options.wasm_opt,
+        };
+
+        post_json(
+            self.owner_token(),
+            format!("/{}/compile", self.id),
+            &body,
+            callback,
+        )
+    }
+
+    pub fn check(
+        &self,
+        files: Vec<FileContent>,
+        callback: Callback<anyhow::Result<CheckResponse>>,
+    ) -> anyhow::Result<FetchTask> {
+        let body = CheckRequest {
+            channel: Channel::Stable,
+            edition: None,
+            files,
+        };
+
+        post_json(
+            self.owner_token(),
+            format!("/{}/check", self.id),
+            &body,
+            callback,
+        )
+    }
+
+    pub fn emit(
+        &self,
+        target: EmitTarget,
+        callback: Callback<anyhow::Result<EmitResponse>>,
+    ) -> anyhow::Result<FetchTask> {
+        let body = EmitRequest {
+            channel: Channel::Stable,
+            mode: Mode::Debug,
+            edition: None,
+            target,
+        };
+
+        post_json(
+            self.owner_token(),
+            format!("/{}/emit", self.id),
+            &body,
+            callback,
+        )
+    }
+
+    pub fn size(
+        &self,
+        callback: Callback<anyhow::Result<SizeResponse>>,
+    ) -> anyhow::Result<FetchTask> {
+        let body = SizeRequest {
+            channel: Channel::Stable,
         };
 
-        post_json(format!("/{}/compile", self.id), &body, callback)
+        post_json(
+            self.owner_token(),
+            format!("/{}/size", self.id),
+            &body,
+            callback,
+        )
     }
 
     pub fn format(
         &self,
-        callback: Callback<anyhow::Result<FormatResponse>>,
+        callback: Callback<anyhow::Result<ToolOutput>>,
     ) -> anyhow::Result<FetchTask> {
         let body = FormatRequest { edition: None };
 
-        post_json(format!("/{}/format", self.id), &body, callback)
+        post_json(
+            self.owner_token(),
+            format!("/{}/format", self.id),
+            &body,
+            callback,
+        )
     }
 
     pub fn clippy(
         &self,
-        callback: Callback<anyhow::Result<ClippyResponse>>,
+        callback: Callback<anyhow::Result<ToolOutput>>,
     ) -> anyhow::Result<FetchTask> {
         let body = ClippyRequest { edition: None };
 
-        post_json(format!("/{}/clippy", self.id), &body, callback)
+        post_json(
+            self.owner_token(),
+            format!("/{}/clippy", self.id),
+            &body,
+            callback,
+        )
     }
 
     pub fn macro_expand(
         &self,
-        callback: Callback<anyhow::Result<MacroExpandResponse>>,
+        callback: Callback<anyhow::Result<ToolOutput>>,
     ) -> anyhow::Result<FetchTask> {
         let body = MacroExpandRequest { edition: None };
 
-        post_json(format!("/{}/macro-expand", self.id), &body, callback)
+        post_json(
+            self.owner_token(),
+            format!("/{}/macro-expand", self.id),
+            &body,
+            callback,
+        )
+    }
+}
+
+/// Access to the admin API using the admin token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Admin {
+    token: String,
+}
+impl Admin {
+    pub fn new(token: String) -> Self {
+        Self { token }
+    }
+
+    pub fn list_sessions(
+        &self,
+        callback: Callback<anyhow::Result<Vec<SessionStatus>>>,
+    ) -> anyhow::Result<FetchTask> {
+        let req = authorized(
+            Request::get(make_api_uri("/admin/sessions")),
+            Some(&self.token),
+        )
+        .body(Nothing)
+        .unwrap();
+        perform_json_request(req, callback)
+    }
+
+    pub fn list_jobs(
+        &self,
+        callback: Callback<anyhow::Result<Vec<JobDetails>>>,
+    ) -> anyhow::Result<FetchTask> {
+        let req = authorized(Request::get(make_api_uri("/admin/jobs")), Some(&self.token))
+            .body(Nothing)
+            .unwrap();
+        perform_json_request(req, callback)
+    }
+
+    /// Remove a session and kill its running jobs.
+    pub fn expire_session(
+        &self,
+        id: &str,
+        callback: Callback<anyhow::Result<()>>,
+    ) -> anyhow::Result<FetchTask> {
+        let uri = make_api_uri(format!("/admin/sessions/{}", id));
+        let req = authorized(Request::delete(uri), Some(&self.token))
+            .body(Nothing)
+            .unwrap();
+
+        FetchService::fetch(
+            req,
+            Callback::from(move |response: Response<Text>| {
+                callback.emit(check_status(response).map(|_| ()))
+            }),
+        )
     }
 }
 
 impl From<SessionDetails> for Session {
+    /// Used for newly created sessions.
+    /// The owner token is stored so the session stays editable after a
+    /// reload.
     fn from(details: SessionDetails) -> Self {
+        if let Some(mut storage) = local_storage() {
+            storage.store(
+                &owner_token_key(&details.id),
+                Ok(details.owner_token.clone()),
+            );
+        }
+
         Self {
             id: details.id.clone(),
+            owner_token: Some(details.owner_token.clone()),
             details: Some(details),
         }
     }
@@ -155,14 +568,15 @@ where
 {
     FetchService::fetch(
         req,
-        Callback::from(move |response: Response<Json<anyhow::Result<RespBody>>>| {
-            let body = response.into_body().0;
+        Callback::from(move |response: Response<Text>| {
+            let body = check_status(response).and_then(|body| Json::from(body).0);
             callback.emit(body)
         }),
     )
 }
 
 fn post_json<Resp>(
+    owner_token: Option<&str>,
     path: impl Display,
     body: &impl Serialize,
     callback: Callback<anyhow::Result<Resp>>,
@@ -170,6 +584,8 @@ fn post_json<Resp>(
 where
     Resp: 'static + for<'de> serde::Deserialize<'de>,
 {
-    let req = Request::post(make_api_uri(path)).body(Json(body)).unwrap();
+    let req = authorized(Request::post(make_api_uri(path)), owner_token)
+        .body(Json(body))
+        .unwrap();
     perform_json_request(req, callback)
 }
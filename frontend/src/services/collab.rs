@@ -0,0 +1,98 @@
+//! Client side of the collaborative editing.
+
+pub use protocol::{
+    ClientMessage,
+    OperationComponent,
+    Participant,
+    ParticipantId,
+    Selection,
+    ServerMessage,
+    TextOperation,
+};
+
+/// The local document diverged from the server and has to be synchronised
+/// again.
+#[derive(Debug, thiserror::Error)]
+#[error("document is out of sync")]
+pub struct OutOfSync;
+
+/// Keeps a local document in sync with the server.
+///
+/// Only one edit is sent at a time. Further local edits are combined into a
+/// buffer until the server acknowledges the outstanding one. Remote edits are
+/// transformed against both so they apply to the local content.
+#[derive(Debug)]
+pub struct DocumentSync {
+    revision: u64,
+    outstanding: Option<TextOperation>,
+    buffer: Option<TextOperation>,
+}
+impl DocumentSync {
+    pub fn new(revision: u64) -> Self {
+        Self {
+            revision,
+            outstanding: None,
+            buffer: None,
+        }
+    }
+
+    /// Handle an edit made locally.
+    /// Returns the edit to send to the server along with the revision it's
+    /// based on, unless another edit is still outstanding.
+    pub fn local_edit(
+        &mut self,
+        operation: TextOperation,
+    ) -> Result<Option<(u64, TextOperation)>, OutOfSync> {
+        if self.outstanding.is_none() {
+            self.outstanding = Some(operation.clone());
+            return Ok(Some((self.revision, operation)));
+        }
+
+        self.buffer = Some(match self.buffer.take() {
+            Some(buffer) => buffer.compose(&operation).ok_or(OutOfSync)?,
+            None => operation,
+        });
+        Ok(None)
+    }
+
+    /// Handle the acknowledgement of the outstanding edit.
+    /// Returns the buffered edit which should be sent next.
+    pub fn acknowledged(&mut self, revision: u64) -> Option<(u64, TextOperation)> {
+        self.revision = revision;
+        self.outstanding = self.buffer.take();
+        self.outstanding
+            .clone()
+            .map(|operation| (revision, operation))
+    }
+
+    /// Handle an edit made by someone else.
+    /// Returns the operation to apply to the local content.
+    pub fn remote_edit(
+        &mut self,
+        revision: u64,
+        mut operation: TextOperation,
+    ) -> Result<TextOperation, OutOfSync> {
+        // the server places our inserts first, so the same is done here
+        if let Some(outstanding) = &self.outstanding {
+            let (outstanding, transformed) =
+                TextOperation::transform(outstanding, &operation).ok_or(OutOfSync)?;
+            self.outstanding = Some(outstanding);
+            operation = transformed;
+        }
+        if let Some(buffer) = &self.buffer {
+            let (buffer, transformed) =
+                TextOperation::transform(buffer, &operation).ok_or(OutOfSync)?;
+            self.buffer = Some(buffer);
+            operation = transformed;
+        }
+
+        self.revision = revision;
+        Ok(operation)
+    }
+}
+
+/// Class name suffix used to colour a participant.
+pub fn participant_color(id: ParticipantId) -> u64 {
+    const COLORS: u64 = 8;
+    id % COLORS
+}
@@ -12,17 +12,7 @@ use yew::{
 };
 
 thread_local! {
-    static BUNDLE: RefCell<Option<FluentBundle<FluentResource>>> = RefCell::new(None);
-}
-
-/// Get the currently loaded language.
-pub fn loaded_language() -> Option<LanguageIdentifier> {
-    BUNDLE.with(|bundle| {
-        bundle
-            .borrow()
-            .as_ref()
-            .and_then(|bundle| bundle.locales.get(0).cloned())
-    })
+    static BUNDLE: RefCell<Option<FluentBundle<FluentResource>>> = const { RefCell::new(None) };
 }
 
 fn load_fluent_resource(lang_id: &LanguageIdentifier, resource: FluentResource) {
@@ -36,7 +26,7 @@ fn load_fluent_resource(lang_id: &LanguageIdentifier, resource: FluentResource)
 
 #[derive(Debug)]
 #[must_use = "loading is aborted as soon as the task is dropped"]
-pub struct LoadBundleTask(FetchTask);
+pub struct LoadBundleTask(#[allow(dead_code)] FetchTask);
 
 /// Load the bundle for the given language.
 pub fn load_bundle(
@@ -149,12 +139,14 @@ impl From<Text> for FluentFetchResult {
         }
     }
 }
-impl Into<Result<FluentResource, anyhow::Error>> for FluentFetchResult {
-    fn into(self) -> Result<FluentResource, anyhow::Error> {
-        match self {
-            Self::Ok(v) => Ok(v),
-            Self::FetchError(err) => Err(err),
-            Self::ParseError(err) => Err(anyhow::anyhow!("failed to parse fluent file: {:?}", err)),
+impl From<FluentFetchResult> for Result<FluentResource, anyhow::Error> {
+    fn from(result: FluentFetchResult) -> Self {
+        match result {
+            FluentFetchResult::Ok(v) => Ok(v),
+            FluentFetchResult::FetchError(err) => Err(err),
+            FluentFetchResult::ParseError(err) => {
+                Err(anyhow::anyhow!("failed to parse fluent file: {:?}", err))
+            }
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, ffi::OsStr, path::Path, rc::Rc};
 use yew::{Component, ComponentLink};
 
 pub trait NeqAssign {
@@ -19,6 +19,134 @@ where
     }
 }
 
+/// Format a size in bytes using binary prefixes.
+pub fn format_size(size: u64) -> String {
+    const KIB: f64 = 1024.0;
+    let size = size as f64;
+    if size < KIB {
+        format!("{} B", size)
+    } else if size < KIB * KIB {
+        format!("{:.1} KiB", size / KIB)
+    } else {
+        format!("{:.2} MiB", size / (KIB * KIB))
+    }
+}
+
+/// Format a number of seconds as hours, minutes and seconds.
+pub fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Line of a diff between two texts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DiffLine<'a> {
+    Unchanged(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Maximum size of the table used to compare the changed lines.
+const MAX_DIFF_CELLS: usize = 1 << 20;
+
+/// Compute a line-based diff from `old` to `new`.
+/// The changed lines are compared using the longest common subsequence. If
+/// there are too many of them they're shown as removed and added as a whole.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // the common prefix and suffix don't need to be compared
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let mut diff: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Unchanged(line))
+        .collect();
+
+    let (mut i, mut j) = (0, 0);
+    let width = new_changed.len() + 1;
+    let cells = (old_changed.len() + 1).saturating_mul(width);
+    if cells <= MAX_DIFF_CELLS {
+        // `lengths[i * width + j]` is the length of the longest common
+        // subsequence of `old_changed[i..]` and `new_changed[j..]`
+        let mut lengths = vec![0u32; cells];
+        for i in (0..old_changed.len()).rev() {
+            for j in (0..new_changed.len()).rev() {
+                lengths[i * width + j] = if old_changed[i] == new_changed[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+
+        while i < old_changed.len() && j < new_changed.len() {
+            if old_changed[i] == new_changed[j] {
+                diff.push(DiffLine::Unchanged(old_changed[i]));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                diff.push(DiffLine::Removed(old_changed[i]));
+                i += 1;
+            } else {
+                diff.push(DiffLine::Added(new_changed[j]));
+                j += 1;
+            }
+        }
+    }
+
+    diff.extend(old_changed[i..].iter().map(|line| DiffLine::Removed(line)));
+    diff.extend(new_changed[j..].iter().map(|line| DiffLine::Added(line)));
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Unchanged(line)),
+    );
+    diff
+}
+
+/// How the content of a file can be displayed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FileKind {
+    Text,
+    Image,
+    Binary,
+}
+impl FileKind {
+    /// Determine the kind of a file based on its extension.
+    /// Unknown extensions are assumed to be text.
+    pub fn from_path(path: &str) -> Self {
+        let extension = match Path::new(path).extension().and_then(OsStr::to_str) {
+            Some(ext) => ext.to_ascii_lowercase(),
+            None => return Self::Text,
+        };
+
+        match extension.as_str() {
+            "apng" | "avif" | "bmp" | "gif" | "ico" | "jpeg" | "jpg" | "png" | "svg" | "webp" => {
+                Self::Image
+            }
+            "eot" | "mp3" | "mp4" | "ogg" | "otf" | "pdf" | "ttf" | "wasm" | "wav" | "webm"
+            | "woff" | "woff2" | "zip" => Self::Binary,
+            _ => Self::Text,
+        }
+    }
+}
+
 /// Reference to a `Component` much like `NodeRef` is for a `Node`.
 /// Using this directly is an anti-pattern. It should only be exposed through a
 /// wrapper type.
@@ -2,15 +2,114 @@ title = Yew Playground
 
 create-session = Create Session
 
+admin-title = Administration
+admin-token = Admin token
+admin-refresh = Refresh
+admin-sessions = Sessions
+admin-sessions-empty = There are no sessions.
+admin-session-id = Session
+admin-session-age = Age
+admin-session-idle = Idle
+admin-session-disk_usage = Disk usage
+admin-session-jobs = Running
+admin-session-expire = Expire
+admin-jobs = Jobs
+admin-jobs-empty = No jobs are running.
+admin-job-tool = Tool
+admin-job-session = Session
+admin-job-running_for = Running for
+
+console-exit_code = Exited with code { $code }
+console-signal = Killed by signal { $signal }
+console-timed_out = Timed out
+console-wall_time = Took { $time }
+console-cpu_time = CPU time { $time }
+console-peak_memory = Peak memory { $size }
+console-truncated = The rest of the output was cut off because it's too large.
+
 action_bar-compile = Compile
 action_bar-clippy = Clippy
 action_bar-format = Format
 action_bar-macro_expand = Expand
+action_bar-emit = Emit
+
+compile_options-default = Default
+compile_options-mode = Mode
+compile_options-mode-debug = Debug
+compile_options-mode-release = Release
+compile_options-opt_level = Optimisation level
+compile_options-opt_level-0 = 0
+compile_options-opt_level-1 = 1
+compile_options-opt_level-2 = 2
+compile_options-opt_level-3 = 3
+compile_options-opt_level-s = s
+compile_options-opt_level-z = z
+compile_options-lto = LTO
+compile_options-lto-off = Off
+compile_options-lto-thin = Thin
+compile_options-lto-fat = Fat
+compile_options-codegen_units = Codegen units
+compile_options-wasm_opt = wasm-opt
+compile_options-wasm_opt-disabled = Disabled
+compile_options-wasm_opt-o1 = -O1
+compile_options-wasm_opt-o2 = -O2
+compile_options-wasm_opt-o3 = -O3
+compile_options-wasm_opt-o4 = -O4
+compile_options-wasm_opt-os = -Os
+compile_options-wasm_opt-oz = -Oz
 
 browser-reload = Reload
 browser-iframe-title = Playground Output
 
 editor-save = Save
 editor-tab-close = Close
+editor-preview-binary = This file can't be displayed.
+editor-preview-download = Download
+editor-read_only = This session belongs to someone else. Changes can't be saved.
+editor-conflict = The file was changed somewhere else since you opened it. Lines starting with "-" are only in the saved version, lines starting with "+" only in yours.
+editor-conflict-keep_mine = Keep mine
+editor-conflict-use_saved = Use saved version
+editor-collab-you = { $name } (you)
+editor-collab-viewer = { $name } (viewing)
+
+error-internal = Something went wrong on the server: { $message }
+error-invalid_request = The request is invalid: { $message }
+error-invalid_path = The path is invalid.
+error-session_not_found = The session doesn't exist anymore.
+error-not_session_owner = Only the owner of the session can do this.
+error-not_admin = The admin token is invalid.
+error-sandbox_file_not_found = The file doesn't exist.
+error-file_conflict = The file was changed somewhere else.
+error-precondition_required = The file can't be saved without knowing which version it's based on.
+error-shutting_down = The server is shutting down. Please try again later.
+error-quota-file_size = Files can't be larger than { $limit }.
+error-quota-file_count = The sandbox can't contain more than { $limit } files.
+error-quota-total_size = The files of the sandbox can't be larger than { $limit } in total.
+error-quota-build_size = The build output is larger than { $limit } and was removed.
+error-rate_limited = Too many requests. Please try again in { $seconds } seconds.
+error-tool_unavailable = The tools are unavailable right now. Please try again later.
+error-tool_timed_out = The tool didn't finish within { $seconds } seconds.
+error-build_missing = Compile the code first.
+
+version-incompatible = The server speaks protocol version { $server } but this page expects { $client }. Please reload the page.
 
 explorer-header = Explorer
+explorer-upload = Upload
+explorer-upload-exists = A file with this name already exists.
+
+problems-header = Problems
+problems-empty = No problems
+problems-level-error = Error
+problems-level-warning = Warning
+problems-level-note = Note
+problems-level-help = Help
+
+emit_target-mir = MIR
+emit_target-llvm_ir = LLVM IR
+emit_target-asm = Assembly
+emit_target-wat = WAT
+
+size_panel-empty = Compile the code to analyse the size of the binary
+size_panel-total = Total
+size_panel-crates = Crates
+size_panel-functions = Functions
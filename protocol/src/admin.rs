@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Tool invocation which is currently running.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct JobDetails {
+    pub tool: String,
+    /// Session the invocation belongs to.
+    pub session: Option<String>,
+    /// Number of seconds since the invocation started.
+    pub running_for: u64,
+}
+
+/// Session as seen by an operator.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SessionStatus {
+    pub id: String,
+    /// Number of seconds since the session was created.
+    pub age: u64,
+    /// Number of seconds since the session was last accessed.
+    pub idle: u64,
+    /// Number of bytes stored by the sandbox.
+    pub disk_usage: u64,
+    pub jobs: Vec<JobDetails>,
+}
+
+/// Record of a tool invocation in the audit log of a session.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AuditEntry {
+    /// Unix timestamp of when the invocation started in seconds.
+    pub timestamp: u64,
+    pub request_id: Option<String>,
+    pub tool: String,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    /// Hex encoded BLAKE2b hash of the "src" directory the tool ran on.
+    pub src_hash: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Signal which terminated the tool.
+    pub signal: Option<i32>,
+    pub duration_ms: u64,
+    /// Peak memory usage in bytes if the backend measures it.
+    pub peak_memory: Option<u64>,
+    pub timed_out: bool,
+    /// Error which prevented the tool from running.
+    pub error: Option<String>,
+}
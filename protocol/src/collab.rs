@@ -0,0 +1,86 @@
+//! Messages exchanged over the collaboration WebSocket of a session.
+
+use crate::{Error, FileChange, TextOperation};
+use serde::{Deserialize, Serialize};
+
+/// Identifies a participant within a session.
+pub type ParticipantId = u64;
+
+/// Someone connected to a session.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Participant {
+    pub id: ParticipantId,
+    /// Display name, generated by the server.
+    pub name: String,
+    /// Whether the participant is allowed to edit the files.
+    pub can_edit: bool,
+}
+
+/// Selected range in a file.
+/// Positions are counted in UTF-16 code units.
+/// The selection is collapsed to a cursor if both positions are equal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Selection {
+    pub anchor: u64,
+    pub head: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    /// Start following the edits of a file.
+    /// The server answers with `Document`.
+    Open { path: String },
+    /// Stop following the edits of a file.
+    Close { path: String },
+    /// Change a file.
+    /// `revision` is the last revision of the file the client knows about.
+    Edit {
+        path: String,
+        revision: u64,
+        operation: TextOperation,
+    },
+    /// Move the cursor or clear it by sending `None`.
+    Select {
+        path: String,
+        selection: Option<Selection>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    /// First message after connecting.
+    Welcome {
+        you: Participant,
+        participants: Vec<Participant>,
+    },
+    Joined { participant: Participant },
+    Left { participant: ParticipantId },
+    /// Complete content of a file.
+    /// Replaces everything the client knows about the file, including edits
+    /// which haven't been acknowledged yet.
+    Document {
+        path: String,
+        revision: u64,
+        content: String,
+    },
+    /// Edit made by another participant which results in `revision`.
+    Edit {
+        path: String,
+        revision: u64,
+        operation: TextOperation,
+        participant: ParticipantId,
+    },
+    /// The client's oldest unacknowledged edit was applied as `revision`.
+    Ack { path: String, revision: u64 },
+    Select {
+        path: String,
+        participant: ParticipantId,
+        selection: Option<Selection>,
+    },
+    /// Files were created or removed.
+    FilesChanged { changes: Vec<FileChange> },
+    /// A message of the client couldn't be handled.
+    Error { error: Error },
+}
@@ -1,11 +1,85 @@
 use serde::{Deserialize, Serialize};
 
+/// Limit of a sandbox.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum QuotaKind {
+    /// Size of a single file.
+    FileSize,
+    /// Number of files in the "public" and "src" directory.
+    FileCount,
+    /// Total size of the files in the "public" and "src" directory.
+    TotalSize,
+    /// Total size of the build output.
+    BuildSize,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum Error {
-    InternalError(String),
+    /// The server failed to handle the request.
+    InternalError { message: String },
+
+    /// The request is malformed, ex. an option is out of range.
+    InvalidRequest { message: String },
+
+    /// The path leaves the sandbox, passes through a symlink or doesn't
+    /// refer to a regular file.
+    InvalidPath,
 
     SessionNotFound,
 
+    /// The request requires the owner token of the session.
+    NotSessionOwner,
+
+    /// The request requires the admin token.
+    NotAdmin,
+
     SandboxFileNotFound,
+
+    /// The file was modified since the client read it.
+    /// `etag` is the entity tag of the current content or `None` if the file
+    /// doesn't exist.
+    FileConflict { etag: Option<String> },
+
+    /// Writing a file requires an `If-Match` or `If-None-Match` header.
+    PreconditionRequired,
+
+    ShuttingDown,
+
+    QuotaExceeded { quota: QuotaKind, limit: u64 },
+
+    /// The client made too many requests.
+    /// `retry_after` is the number of seconds until the next request is
+    /// accepted.
+    RateLimited { retry_after: u64 },
+
+    /// The tools can't be executed right now.
+    ToolUnavailable,
+
+    /// The tool was killed because it didn't finish in time.
+    ToolTimedOut { timeout_ms: u64 },
+
+    /// The request requires the output of a successful build.
+    BuildMissing,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_tagged() {
+        // internally tagged enums can't contain newtype variants of strings
+        let err = Error::InternalError {
+            message: String::from("oops"),
+        };
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(json, r#"{"type":"InternalError","message":"oops"}"#);
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            Error::InternalError { message } if message == "oops"
+        ));
+    }
 }
@@ -1,7 +1,15 @@
+pub use admin::*;
+pub use collab::*;
 pub use error::*;
+pub use ot::*;
 pub use sandbox::*;
 pub use session::*;
+pub use version::*;
 
+mod admin;
+mod collab;
 mod error;
+mod ot;
 mod sandbox;
 mod session;
+mod version;
@@ -0,0 +1,468 @@
+//! Operational transformation of text documents.
+//!
+//! Positions and lengths are counted in UTF-16 code units, which is how the
+//! browser (and Monaco) index strings.
+
+use serde::{Deserialize, Serialize};
+
+/// Part of a `TextOperation`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum OperationComponent {
+    /// Keep the next `n` code units.
+    Retain(u64),
+    /// Insert text at the current position.
+    Insert(String),
+    /// Remove the next `n` code units.
+    Delete(u64),
+}
+
+/// Change to a whole text document.
+/// It covers every code unit of the document it's applied to.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct TextOperation {
+    components: Vec<OperationComponent>,
+}
+
+fn utf16_len(s: &str) -> u64 {
+    s.encode_utf16().count() as u64
+}
+
+/// Split a string after `n` UTF-16 code units.
+/// Returns `None` if that's in the middle of a character.
+fn split_utf16(s: &str, n: u64) -> Option<(&str, &str)> {
+    let mut units = 0;
+    for (index, c) in s.char_indices() {
+        if units == n {
+            return Some(s.split_at(index));
+        }
+        units += c.len_utf16() as u64;
+        if units > n {
+            return None;
+        }
+    }
+
+    if units == n {
+        Some((s, ""))
+    } else {
+        None
+    }
+}
+
+impl TextOperation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn components(&self) -> &[OperationComponent] {
+        &self.components
+    }
+
+    /// Whether the operation doesn't change the document.
+    pub fn is_noop(&self) -> bool {
+        self.components
+            .iter()
+            .all(|component| matches!(component, OperationComponent::Retain(_)))
+    }
+
+    /// Length of the document the operation can be applied to.
+    pub fn base_len(&self) -> u64 {
+        use OperationComponent::*;
+        self.components
+            .iter()
+            .map(|component| match component {
+                Retain(n) | Delete(n) => *n,
+                Insert(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Length of the document after applying the operation.
+    pub fn target_len(&self) -> u64 {
+        use OperationComponent::*;
+        self.components
+            .iter()
+            .map(|component| match component {
+                Retain(n) => *n,
+                Insert(text) => utf16_len(text),
+                Delete(_) => 0,
+            })
+            .sum()
+    }
+
+    pub fn retain(&mut self, n: u64) -> &mut Self {
+        if n == 0 {
+            return self;
+        }
+        if let Some(OperationComponent::Retain(last)) = self.components.last_mut() {
+            *last += n;
+        } else {
+            self.components.push(OperationComponent::Retain(n));
+        }
+        self
+    }
+
+    pub fn insert(&mut self, text: &str) -> &mut Self {
+        use OperationComponent::*;
+        if text.is_empty() {
+            return self;
+        }
+        // inserts always come before deletes so equal operations compare equal
+        match self.components.as_mut_slice() {
+            [.., Insert(last)] | [.., Insert(last), Delete(_)] => last.push_str(text),
+            [.., Delete(_)] => {
+                let delete = self.components.pop().expect("last component exists");
+                self.components.push(Insert(text.to_owned()));
+                self.components.push(delete);
+            }
+            _ => self.components.push(Insert(text.to_owned())),
+        }
+        self
+    }
+
+    pub fn delete(&mut self, n: u64) -> &mut Self {
+        if n == 0 {
+            return self;
+        }
+        if let Some(OperationComponent::Delete(last)) = self.components.last_mut() {
+            *last += n;
+        } else {
+            self.components.push(OperationComponent::Delete(n));
+        }
+        self
+    }
+
+    fn push(&mut self, component: OperationComponent) {
+        use OperationComponent::*;
+        match component {
+            Retain(n) => self.retain(n),
+            Insert(text) => self.insert(&text),
+            Delete(n) => self.delete(n),
+        };
+    }
+
+    /// Apply the operation to a document.
+    /// Returns `None` if the operation doesn't fit the document.
+    pub fn apply(&self, doc: &str) -> Option<String> {
+        use OperationComponent::*;
+        if utf16_len(doc) != self.base_len() {
+            return None;
+        }
+
+        let mut result = String::with_capacity(doc.len());
+        let mut rest = doc;
+        for component in &self.components {
+            match component {
+                Retain(n) => {
+                    let (kept, after) = split_utf16(rest, *n)?;
+                    result.push_str(kept);
+                    rest = after;
+                }
+                Insert(text) => result.push_str(text),
+                Delete(n) => rest = split_utf16(rest, *n)?.1,
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Combine two consecutive operations into one which has the same effect
+    /// as applying `self` followed by `other`.
+    /// Returns `None` if `other` doesn't start where `self` ends.
+    pub fn compose(&self, other: &Self) -> Option<Self> {
+        use OperationComponent::*;
+        if self.target_len() != other.base_len() {
+            return None;
+        }
+
+        let mut result = Self::new();
+        let mut first = Components::new(&self.components);
+        let mut second = Components::new(&other.components);
+        loop {
+            match (first.peek(), second.peek()) {
+                (None, None) => break,
+                (Some(Delete(n)), _) => {
+                    result.delete(n);
+                    first.skip(n);
+                }
+                (_, Some(Insert(text))) => {
+                    result.insert(&text);
+                    second.next_insert();
+                }
+                (Some(Retain(a)), Some(Retain(b))) => {
+                    let n = a.min(b);
+                    result.retain(n);
+                    first.skip(n);
+                    second.skip(n);
+                }
+                (Some(Retain(a)), Some(Delete(b))) => {
+                    let n = a.min(b);
+                    result.delete(n);
+                    first.skip(n);
+                    second.skip(n);
+                }
+                (Some(Insert(_)), Some(Retain(b))) => {
+                    let (kept, _) = first.take_insert(b)?;
+                    result.insert(&kept);
+                    second.skip(utf16_len(&kept));
+                }
+                (Some(Insert(_)), Some(Delete(b))) => {
+                    let (removed, _) = first.take_insert(b)?;
+                    second.skip(utf16_len(&removed));
+                }
+                _ => return None,
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Transform two concurrent operations `a` and `b`, which were made on
+    /// the same document, into `(a', b')` such that applying `a` followed by
+    /// `b'` has the same result as `b` followed by `a'`.
+    /// Inserts at the same position are ordered with `a` first.
+    pub fn transform(a: &Self, b: &Self) -> Option<(Self, Self)> {
+        use OperationComponent::*;
+        if a.base_len() != b.base_len() {
+            return None;
+        }
+
+        let mut a_prime = Self::new();
+        let mut b_prime = Self::new();
+        let mut first = Components::new(&a.components);
+        let mut second = Components::new(&b.components);
+        loop {
+            match (first.peek(), second.peek()) {
+                (None, None) => break,
+                (Some(Insert(text)), _) => {
+                    let len = utf16_len(&text);
+                    a_prime.insert(&text);
+                    b_prime.retain(len);
+                    first.next_insert();
+                }
+                (_, Some(Insert(text))) => {
+                    let len = utf16_len(&text);
+                    a_prime.retain(len);
+                    b_prime.insert(&text);
+                    second.next_insert();
+                }
+                (Some(Retain(x)), Some(Retain(y))) => {
+                    let n = x.min(y);
+                    a_prime.retain(n);
+                    b_prime.retain(n);
+                    first.skip(n);
+                    second.skip(n);
+                }
+                (Some(Delete(x)), Some(Delete(y))) => {
+                    // both removed the same text
+                    let n = x.min(y);
+                    first.skip(n);
+                    second.skip(n);
+                }
+                (Some(Delete(x)), Some(Retain(y))) => {
+                    let n = x.min(y);
+                    a_prime.delete(n);
+                    first.skip(n);
+                    second.skip(n);
+                }
+                (Some(Retain(x)), Some(Delete(y))) => {
+                    let n = x.min(y);
+                    b_prime.delete(n);
+                    first.skip(n);
+                    second.skip(n);
+                }
+                _ => return None,
+            }
+        }
+
+        Some((a_prime, b_prime))
+    }
+
+    /// Move a position in the document the operation is applied to, such as a
+    /// cursor, to the corresponding position in the resulting document.
+    /// Text inserted at the position is placed before it.
+    pub fn transform_index(&self, index: u64) -> u64 {
+        use OperationComponent::*;
+        let mut old = 0;
+        let mut new = index;
+        for component in &self.components {
+            if old > index {
+                break;
+            }
+            match component {
+                Retain(n) => old += n,
+                Insert(text) => new += utf16_len(text),
+                Delete(n) => {
+                    new -= (*n).min(index - old);
+                    old += n;
+                }
+            }
+        }
+
+        new
+    }
+}
+
+/// Cursor over the components of an operation which can consume parts of a
+/// component.
+struct Components<'a> {
+    components: &'a [OperationComponent],
+    /// Remainder of the current component.
+    current: Option<OperationComponent>,
+}
+impl<'a> Components<'a> {
+    fn new(components: &'a [OperationComponent]) -> Self {
+        let mut cursor = Self {
+            components,
+            current: None,
+        };
+        cursor.advance();
+        cursor
+    }
+
+    fn advance(&mut self) {
+        self.current = self.components.first().cloned();
+        if !self.components.is_empty() {
+            self.components = &self.components[1..];
+        }
+    }
+
+    fn peek(&self) -> Option<OperationComponent> {
+        self.current.clone()
+    }
+
+    /// Consume `n` code units of the current retain or delete.
+    fn skip(&mut self, n: u64) {
+        use OperationComponent::*;
+        match &mut self.current {
+            Some(Retain(len)) | Some(Delete(len)) if *len > n => *len -= n,
+            _ => self.advance(),
+        }
+    }
+
+    fn next_insert(&mut self) {
+        self.advance();
+    }
+
+    /// Consume up to `n` code units of the current insert.
+    /// Returns the consumed text and the remainder.
+    fn take_insert(&mut self, n: u64) -> Option<(String, String)> {
+        let text = match &self.current {
+            Some(OperationComponent::Insert(text)) => text.clone(),
+            _ => return None,
+        };
+        if utf16_len(&text) <= n {
+            self.advance();
+            return Some((text, String::new()));
+        }
+
+        let (taken, rest) = split_utf16(&text, n)?;
+        let (taken, rest) = (taken.to_owned(), rest.to_owned());
+        self.current = Some(OperationComponent::Insert(rest.clone()));
+        Some((taken, rest))
+    }
+}
+
+impl std::iter::FromIterator<OperationComponent> for TextOperation {
+    fn from_iter<I: IntoIterator<Item = OperationComponent>>(iter: I) -> Self {
+        let mut operation = Self::new();
+        for component in iter {
+            operation.push(component);
+        }
+        operation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(build: impl FnOnce(&mut TextOperation)) -> TextOperation {
+        let mut operation = TextOperation::new();
+        build(&mut operation);
+        operation
+    }
+
+    #[test]
+    fn apply_uses_utf16_positions() {
+        let operation = op(|o| {
+            o.retain(2).insert("b").delete(1).retain(1);
+        });
+        assert_eq!(operation.base_len(), 4);
+        assert_eq!(operation.target_len(), 4);
+        // the emoji takes two code units
+        assert_eq!(operation.apply("😀xy").unwrap(), "😀by");
+        assert!(operation.apply("abc").is_none());
+        assert!(op(|o| {
+            o.retain(1).delete(3);
+        })
+        .apply("😀xy")
+        .is_none());
+    }
+
+    #[test]
+    fn compose_is_sequential_application() {
+        let doc = "hello world";
+        let a = op(|o| {
+            o.retain(5).insert(",").retain(6);
+        });
+        let b = op(|o| {
+            o.delete(1).insert("H").retain(8).delete(3).insert("rld!");
+        });
+        let composed = a.compose(&b).unwrap();
+        assert_eq!(
+            composed.apply(doc).unwrap(),
+            b.apply(&a.apply(doc).unwrap()).unwrap()
+        );
+        assert!(b.compose(&a).is_none());
+    }
+
+    #[test]
+    fn transform_converges() {
+        let doc = "fn main() {}";
+        let cases = vec![
+            (
+                op(|o| {
+                    o.retain(11).insert("\n    println!();\n").retain(1);
+                }),
+                op(|o| {
+                    o.retain(3).delete(4).insert("start").retain(5);
+                }),
+            ),
+            (
+                op(|o| {
+                    o.retain(3).delete(6).retain(3);
+                }),
+                op(|o| {
+                    o.retain(5).delete(6).retain(1);
+                }),
+            ),
+            (
+                op(|o| {
+                    o.insert("a").retain(12);
+                }),
+                op(|o| {
+                    o.insert("b").retain(12);
+                }),
+            ),
+        ];
+
+        for (a, b) in cases {
+            let (a_prime, b_prime) = TextOperation::transform(&a, &b).unwrap();
+            let via_a = b_prime.apply(&a.apply(doc).unwrap()).unwrap();
+            let via_b = a_prime.apply(&b.apply(doc).unwrap()).unwrap();
+            assert_eq!(via_a, via_b);
+        }
+    }
+
+    #[test]
+    fn indices_follow_the_text() {
+        let operation = op(|o| {
+            o.insert("ab").retain(2).delete(3).retain(1);
+        });
+        assert_eq!(operation.transform_index(0), 2);
+        assert_eq!(operation.transform_index(2), 4);
+        assert_eq!(operation.transform_index(3), 4);
+        assert_eq!(operation.transform_index(6), 5);
+    }
+}
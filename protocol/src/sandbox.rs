@@ -1,26 +1,134 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct File {
     pub path: String,
     pub name: String,
 }
+impl File {
+    /// Create a file from its path in the sandbox.
+    pub fn from_path(path: &str) -> Self {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        Self {
+            path: path.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Directory {
     pub path: String,
     pub name: String,
     pub directories: Vec<Directory>,
     pub files: Vec<File>,
 }
+impl Directory {
+    fn empty(path: String, name: &str) -> Self {
+        Self {
+            path,
+            name: name.to_owned(),
+            directories: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Paths of all files in the directory and its subdirectories.
+    pub fn file_paths(&self) -> Vec<&str> {
+        let mut paths: Vec<&str> = self.files.iter().map(|file| file.path.as_str()).collect();
+        for directory in &self.directories {
+            paths.extend(directory.file_paths());
+        }
+        paths
+    }
+
+    /// Apply a change to the directory.
+    /// Returns `false` if the file isn't located in the directory.
+    pub fn apply(&mut self, change: &FileChange) -> bool {
+        match change {
+            FileChange::Created { file } => self.insert(file),
+            FileChange::Removed { path } => self.remove(path),
+        }
+    }
+
+    fn insert(&mut self, file: &File) -> bool {
+        let rest = match relative_path(&self.path, &file.path) {
+            Some(rest) => rest,
+            None => return false,
+        };
+
+        let name = match rest.find('/') {
+            Some(end) => &rest[..end],
+            None => {
+                if !self.files.contains(file) {
+                    self.files.push(file.clone());
+                }
+                return true;
+            }
+        };
+        let pos = match self.directories.iter().position(|dir| dir.name == name) {
+            Some(pos) => pos,
+            None => {
+                let path = format!("{}/{}", self.path, name);
+                self.directories.push(Self::empty(path, name));
+                self.directories.len() - 1
+            }
+        };
+        self.directories[pos].insert(file)
+    }
+
+    fn remove(&mut self, path: &str) -> bool {
+        if relative_path(&self.path, path).is_none() {
+            return false;
+        }
+
+        if let Some(pos) = self.files.iter().position(|file| file.path == path) {
+            self.files.remove(pos);
+            return true;
+        }
+        self.directories.iter_mut().any(|dir| dir.remove(path))
+    }
+}
+
+/// Part of `path` below the directory `dir`.
+fn relative_path<'a>(dir: &str, path: &'a str) -> Option<&'a str> {
+    path.strip_prefix(dir)?.strip_prefix('/')
+}
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SandboxStructure {
     pub public: Directory,
     pub src: Directory,
 }
+impl SandboxStructure {
+    /// Paths of all files in the sandbox.
+    pub fn file_paths(&self) -> Vec<&str> {
+        let mut paths = self.public.file_paths();
+        paths.extend(self.src.file_paths());
+        paths
+    }
+
+    /// Apply a change to the structure.
+    /// Returns `false` if the file isn't located in the sandbox.
+    pub fn apply(&mut self, change: &FileChange) -> bool {
+        self.public.apply(change) || self.src.apply(change)
+    }
+}
+
+/// File created or removed in a sandbox.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "type")]
+pub enum FileChange {
+    Created { file: File },
+    Removed { path: String },
+}
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Version {
     pub release: String,
     pub commit_hash: String,
@@ -28,6 +136,7 @@ pub struct Version {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ToolVersions {
     pub rustc: Version,
     pub rustfmt: Version,
@@ -36,73 +145,314 @@ pub struct ToolVersions {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Channel {
     Stable,
     Nightly,
 }
+impl Channel {
+    pub const ALL: &'static [Self] = &[Self::Stable, Self::Nightly];
+}
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Mode {
     Debug,
     Release,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Edition {
     Rust2018,
 }
+impl Edition {
+    pub const ALL: &'static [Self] = &[Self::Rust2018];
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum OptLevel {
+    Zero,
+    One,
+    Two,
+    Three,
+    S,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Lto {
+    Off,
+    Thin,
+    Fat,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum WasmOptLevel {
+    Disabled,
+    O1,
+    O2,
+    O3,
+    O4,
+    Os,
+    Oz,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct CompileRequest {
     pub channel: Channel,
     pub mode: Mode,
     #[serde(default)]
     pub edition: Option<Edition>,
     pub backtrace: bool,
+    /// Overrides the `opt-level` of the profile.
+    #[serde(default)]
+    pub opt_level: Option<OptLevel>,
+    /// Overrides the `lto` setting of the profile.
+    #[serde(default)]
+    pub lto: Option<Lto>,
+    /// Overrides the `codegen-units` of the profile.
+    #[serde(default)]
+    pub codegen_units: Option<u32>,
+    /// Overrides the optimisation passes wasm-pack runs with `wasm-opt`.
+    #[serde(default)]
+    pub wasm_opt: Option<WasmOptLevel>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct CompileResponse {
+/// Output of a tool and how it exited.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ToolOutput {
     pub success: bool,
+    /// `None` if the tool was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// Signal which killed the tool.
+    pub signal: Option<i32>,
+    /// Whether the tool was killed because it exceeded the timeout.
+    pub timed_out: bool,
+    pub wall_time_ms: u64,
+    /// User and system CPU time if the server measures it.
+    pub cpu_time_ms: Option<u64>,
+    /// Peak memory usage in bytes if the server measures it.
+    pub peak_memory: Option<u64>,
+    /// Invalid UTF-8 sequences are replaced with U+FFFD.
     pub stdout: String,
     pub stderr: String,
+    /// Whether the rest of the output was cut off because it was too large.
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CompileResponse {
+    pub output: ToolOutput,
+    /// Size of the resulting wasm binary in bytes.
+    #[serde(default)]
+    pub wasm_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct FileContent {
+    pub path: String,
+    pub content: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct FormatRequest {
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CheckRequest {
+    pub channel: Channel,
     #[serde(default)]
     pub edition: Option<Edition>,
+    /// Contents which take precedence over the files stored in the sandbox.
+    /// This allows checking unsaved buffers.
+    #[serde(default)]
+    pub files: Vec<FileContent>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DiagnosticSpan {
+    pub file_name: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
+    pub is_primary: bool,
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    pub message: String,
+    pub code: Option<String>,
+    pub spans: Vec<DiagnosticSpan>,
+    pub rendered: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct FormatResponse {
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CheckResponse {
     pub success: bool,
-    pub stdout: String,
+    pub diagnostics: Vec<Diagnostic>,
     pub stderr: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ClippyRequest {
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct FormatRequest {
     #[serde(default)]
     pub edition: Option<Edition>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct ClippyResponse {
-    pub success: bool,
-    pub stdout: String,
-    pub stderr: String,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ClippyRequest {
+    #[serde(default)]
+    pub edition: Option<Edition>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MacroExpandRequest {
     #[serde(default)]
     pub edition: Option<Edition>,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum EmitTarget {
+    Mir,
+    LlvmIr,
+    Asm,
+    Wat,
+}
+impl EmitTarget {
+    pub const ALL: &'static [Self] = &[Self::Mir, Self::LlvmIr, Self::Asm, Self::Wat];
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EmitRequest {
+    pub channel: Channel,
+    pub mode: Mode,
+    #[serde(default)]
+    pub edition: Option<Edition>,
+    pub target: EmitTarget,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EmitResponse {
+    /// Invalid UTF-8 sequences are replaced with U+FFFD.
+    pub code: String,
+    /// Whether the rest of the code was cut off because it was too large.
+    #[serde(default)]
+    pub code_truncated: bool,
+    pub output: ToolOutput,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct FunctionSize {
+    pub name: String,
+    pub shallow_size: u64,
+    #[serde(default)]
+    pub retained_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CrateSize {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SizeReport {
+    /// Size of the wasm binary in bytes.
+    pub total_size: u64,
+    /// Largest items in the binary sorted by their shallow size.
+    pub functions: Vec<FunctionSize>,
+    /// Sum of the shallow sizes of all items belonging to a crate.
+    pub crates: Vec<CrateSize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SizeRequest {
+    pub channel: Channel,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct MacroExpandResponse {
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SizeResponse {
     pub success: bool,
-    pub stdout: String,
+    pub report: Option<SizeReport>,
     pub stderr: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structure() -> SandboxStructure {
+        SandboxStructure {
+            public: Directory::empty(String::from("public"), "public"),
+            src: Directory::empty(String::from("src"), "src"),
+        }
+    }
+
+    fn created(path: &str) -> FileChange {
+        FileChange::Created {
+            file: File::from_path(path),
+        }
+    }
+
+    #[test]
+    fn changes_create_directories() {
+        let mut structure = structure();
+        assert!(structure.apply(&created("src/lib.rs")));
+        assert!(structure.apply(&created("src/a/b/mod.rs")));
+        assert!(structure.apply(&created("src/a/b/mod.rs")));
+        assert!(!structure.apply(&created("target/debug/x")));
+
+        assert_eq!(structure.file_paths(), vec!["src/lib.rs", "src/a/b/mod.rs"]);
+        let b = &structure.src.directories[0].directories[0];
+        assert_eq!((b.path.as_str(), b.name.as_str()), ("src/a/b", "b"));
+        assert_eq!(b.files[0].name, "mod.rs");
+    }
+
+    #[test]
+    fn changes_remove_files() {
+        let mut structure = structure();
+        structure.apply(&created("public/index.html"));
+        structure.apply(&created("src/a/mod.rs"));
+
+        let removed = |path: &str| FileChange::Removed {
+            path: path.to_owned(),
+        };
+        assert!(structure.apply(&removed("src/a/mod.rs")));
+        assert!(!structure.apply(&removed("src/a/mod.rs")));
+        // the prefix alone doesn't make it a file of the directory
+        assert!(!structure.apply(&removed("publicity/index.html")));
+        assert_eq!(structure.file_paths(), vec!["public/index.html"]);
+    }
+}
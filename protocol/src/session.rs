@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SessionDetails {
     pub id: String,
     pub public_url: String,
+    /// Secret which has to be sent with requests modifying the session.
+    pub owner_token: String,
 }
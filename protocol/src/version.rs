@@ -0,0 +1,49 @@
+use crate::{Channel, Edition, EmitTarget};
+use serde::{Deserialize, Serialize};
+
+/// Version of the protocol described by this crate as `major.minor`.
+/// The major version is increased for incompatible changes.
+pub const PROTOCOL_VERSION: &str = "1.0";
+
+/// Check whether a peer speaking the protocol `version` is compatible with
+/// this one.
+pub fn is_compatible(version: &str) -> bool {
+    fn major(version: &str) -> &str {
+        version.split('.').next().unwrap_or_default()
+    }
+    major(version) == major(PROTOCOL_VERSION)
+}
+
+/// Features the server supports.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Capabilities {
+    pub channels: Vec<Channel>,
+    pub editions: Vec<Edition>,
+    pub emit_targets: Vec<EmitTarget>,
+    /// API operations which run a tool, ex. "macro-expand".
+    pub tools: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ApiVersion {
+    /// `PROTOCOL_VERSION` of the server.
+    pub protocol: String,
+    /// Version of the server itself.
+    pub server: String,
+    pub capabilities: Capabilities,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compatible_versions_share_the_major_version() {
+        assert!(is_compatible(PROTOCOL_VERSION));
+        assert!(is_compatible("1.7"));
+        assert!(!is_compatible("2.0"));
+        assert!(!is_compatible(""));
+    }
+}
@@ -0,0 +1,161 @@
+//! API for operators to inspect and manage the sessions.
+
+use crate::{
+    bearer_token,
+    janitor::{self, Janitor, Session},
+    sandbox::{Context, RunningInvocation},
+    Error,
+    Result,
+};
+use protocol::{AuditEntry, JobDetails, SessionStatus};
+use rocket::{
+    http::Status,
+    request::{self, FromRequest},
+    Outcome,
+    Route,
+    State,
+};
+use rocket_contrib::{json::Json, uuid::Uuid as UuidParam};
+use serde::Deserialize;
+use std::{sync::Arc, time::Instant};
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Token which grants access to the admin API.
+    /// The API is disabled if this isn't set.
+    pub token: Option<String>,
+}
+
+/// Admin token sent as a bearer token in the `Authorization` header.
+#[derive(Debug)]
+struct AdminToken(Option<String>);
+impl AdminToken {
+    fn authorize(&self, config: &AdminConfig) -> Result<()> {
+        match (&self.0, &config.token) {
+            (Some(token), Some(expected))
+                if janitor::constant_time_eq(token.as_bytes(), expected.as_bytes()) =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::from(protocol::Error::NotAdmin)),
+        }
+    }
+}
+impl<'a, 'r> FromRequest<'a, 'r> for AdminToken {
+    type Error = !;
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(Self(bearer_token(request)))
+    }
+}
+
+fn job_details(invocation: &RunningInvocation, now: Instant) -> JobDetails {
+    JobDetails {
+        tool: invocation.tool.to_owned(),
+        session: invocation
+            .session
+            .map(|session| session.to_simple().to_string()),
+        running_for: now
+            .saturating_duration_since(invocation.started_at)
+            .as_secs(),
+    }
+}
+
+fn session_status(session: &Session, jobs: &[RunningInvocation], now: Instant) -> SessionStatus {
+    let disk_usage = session.sandbox.disk_usage().unwrap_or_else(|err| {
+        tracing::warn!(
+            "unable to measure the disk usage of {}: {}",
+            session.id,
+            err
+        );
+        0
+    });
+    SessionStatus {
+        id: session.get_id_string(),
+        age: now.saturating_duration_since(session.created_at).as_secs(),
+        idle: now
+            .saturating_duration_since(session.last_active())
+            .as_secs(),
+        disk_usage,
+        jobs: jobs
+            .iter()
+            .filter(|job| job.session == Some(session.id))
+            .map(|job| job_details(job, now))
+            .collect(),
+    }
+}
+
+#[rocket::get("/sessions")]
+fn list_sessions(
+    token: AdminToken,
+    config: State<AdminConfig>,
+    janitor: State<Arc<Janitor>>,
+    context: State<Arc<Context>>,
+) -> Result<Json<Vec<SessionStatus>>> {
+    token.authorize(&config)?;
+    let jobs = context.stats().running_invocations();
+    let now = Instant::now();
+    let sessions = janitor
+        .sessions()
+        .iter()
+        .map(|session| session_status(session, &jobs, now))
+        .collect();
+    Ok(Json(sessions))
+}
+
+/// Remove a session right away and kill its running invocations.
+#[rocket::delete("/sessions/<id>")]
+fn expire_session(
+    token: AdminToken,
+    config: State<AdminConfig>,
+    janitor: State<Arc<Janitor>>,
+    context: State<Arc<Context>>,
+    id: UuidParam,
+) -> Result<Status> {
+    token.authorize(&config)?;
+    let session = janitor
+        .remove_session(&id)
+        .ok_or_else(|| Error::from(protocol::Error::SessionNotFound))?;
+    context.kill_invocations(|invocation| invocation != Some(&session.id))?;
+    Ok(Status::NoContent)
+}
+
+#[rocket::get("/sessions/<id>/audit")]
+fn session_audit_log(
+    token: AdminToken,
+    config: State<AdminConfig>,
+    janitor: State<Arc<Janitor>>,
+    id: UuidParam,
+) -> Result<Json<Vec<AuditEntry>>> {
+    token.authorize(&config)?;
+    // looked up without `get_session` so reading the log doesn't count as
+    // activity
+    let session = janitor
+        .sessions()
+        .into_iter()
+        .find(|session| session.id == *id)
+        .ok_or_else(|| Error::from(protocol::Error::SessionNotFound))?;
+    Ok(Json(session.sandbox.audit_log()?))
+}
+
+#[rocket::get("/jobs")]
+fn list_jobs(
+    token: AdminToken,
+    config: State<AdminConfig>,
+    context: State<Arc<Context>>,
+) -> Result<Json<Vec<JobDetails>>> {
+    token.authorize(&config)?;
+    let now = Instant::now();
+    let jobs = context
+        .stats()
+        .running_invocations()
+        .iter()
+        .map(|invocation| job_details(invocation, now))
+        .collect();
+    Ok(Json(jobs))
+}
+
+pub fn routes() -> Vec<Route> {
+    rocket::routes![list_sessions, expire_session, session_audit_log, list_jobs]
+}
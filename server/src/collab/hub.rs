@@ -0,0 +1,709 @@
+//! Shared state of the collaboration sessions.
+
+use crate::{
+    janitor::{Session, SessionRef},
+    sandbox::{self, Precondition},
+};
+use protocol::{
+    ClientMessage,
+    Participant,
+    ParticipantId,
+    Selection,
+    ServerMessage,
+    TextOperation,
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::Sender,
+        Arc,
+        Mutex,
+    },
+};
+use uuid::Uuid;
+
+/// Number of operations kept to transform edits based on older revisions.
+/// Clients which fall further behind receive the whole document again.
+const MAX_HISTORY: usize = 256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("the session already has {0} participants")]
+    SessionFull(usize),
+}
+
+#[derive(Debug)]
+struct Member {
+    participant: Participant,
+    sender: Sender<ServerMessage>,
+}
+
+/// File which is being edited.
+#[derive(Debug)]
+struct Document {
+    revision: u64,
+    content: String,
+    /// Version of the content in the sandbox.
+    version: String,
+    /// Whether the content has edits which weren't written to the sandbox.
+    unsaved: bool,
+    /// Operations which led to `revision`, the newest one last.
+    history: VecDeque<TextOperation>,
+    followers: HashSet<ParticipantId>,
+    selections: HashMap<ParticipantId, Selection>,
+}
+impl Document {
+    fn load(session: &Session, path: &str) -> Result<Self, protocol::Error> {
+        let (_, content) = session.sandbox.read_file(Path::new(path)).map_err(|err| {
+            tracing::debug!("unable to read collaborative file: {}", err);
+            protocol::Error::SandboxFileNotFound
+        })?;
+        let version = sandbox::content_version(&content);
+        let content = String::from_utf8(content).map_err(|_| protocol::Error::InternalError {
+            message: String::from("the file isn't valid UTF-8"),
+        })?;
+
+        Ok(Self {
+            revision: 0,
+            content,
+            version,
+            unsaved: false,
+            history: VecDeque::new(),
+            followers: HashSet::new(),
+            selections: HashMap::new(),
+        })
+    }
+
+    /// Reload the content after the file was changed outside of the
+    /// collaboration session.
+    /// Unsaved edits are discarded.
+    /// Returns `false` if the content didn't change.
+    fn reload(&mut self, session: &Session, path: &str) -> Result<bool, protocol::Error> {
+        let loaded = Self::load(session, path)?;
+        if loaded.version == self.version && !self.unsaved {
+            return Ok(false);
+        }
+
+        // the operations don't apply to the new content
+        self.revision += 1;
+        self.history.clear();
+        self.selections.clear();
+        self.content = loaded.content;
+        self.version = loaded.version;
+        self.unsaved = false;
+        Ok(true)
+    }
+
+    fn snapshot(&self, path: &str) -> ServerMessage {
+        ServerMessage::Document {
+            path: path.to_owned(),
+            revision: self.revision,
+            content: self.content.clone(),
+        }
+    }
+
+    /// Transform an operation based on `revision` so it applies to the
+    /// current content.
+    fn rebase(&self, revision: u64, mut operation: TextOperation) -> Option<TextOperation> {
+        let missed = self.revision.checked_sub(revision)? as usize;
+        if missed > self.history.len() {
+            return None;
+        }
+
+        for concurrent in self.history.iter().skip(self.history.len() - missed) {
+            let (rebased, _) = TextOperation::transform(&operation, concurrent)?;
+            operation = rebased;
+        }
+        Some(operation)
+    }
+
+    fn push_history(&mut self, operation: TextOperation) {
+        self.revision += 1;
+        self.history.push_back(operation);
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// Participants and open documents of a session.
+#[derive(Debug)]
+struct Room {
+    session: SessionRef,
+    members: HashMap<ParticipantId, Member>,
+    documents: HashMap<String, Document>,
+}
+impl Room {
+    fn new(session: SessionRef) -> Self {
+        Self {
+            session,
+            members: HashMap::new(),
+            documents: HashMap::new(),
+        }
+    }
+
+    fn send(&self, participant: ParticipantId, message: ServerMessage) {
+        if let Some(member) = self.members.get(&participant) {
+            // the connection removes the member once it's closed
+            let _ = member.sender.send(message);
+        }
+    }
+
+    fn broadcast(&self, message: &ServerMessage, except: Option<ParticipantId>) {
+        for (id, member) in &self.members {
+            if Some(*id) != except {
+                let _ = member.sender.send(message.clone());
+            }
+        }
+    }
+
+    fn broadcast_to_followers(
+        &self,
+        path: &str,
+        message: &ServerMessage,
+        except: Option<ParticipantId>,
+    ) {
+        let followers = match self.documents.get(path) {
+            Some(document) => &document.followers,
+            None => return,
+        };
+        for id in followers {
+            if Some(*id) != except {
+                self.send(*id, message.clone());
+            }
+        }
+    }
+
+    fn send_error(&self, participant: ParticipantId, error: protocol::Error) {
+        self.send(participant, ServerMessage::Error { error });
+    }
+
+    fn open(&mut self, participant: ParticipantId, path: String) {
+        if !self.documents.contains_key(&path) {
+            match Document::load(&self.session, &path) {
+                Ok(document) => {
+                    self.documents.insert(path.clone(), document);
+                }
+                Err(err) => return self.send_error(participant, err),
+            }
+        }
+
+        let document = self
+            .documents
+            .get_mut(&path)
+            .expect("document was just loaded");
+        document.followers.insert(participant);
+        let mut messages = vec![document.snapshot(&path)];
+        messages.extend(
+            document
+                .selections
+                .iter()
+                .map(|(id, selection)| ServerMessage::Select {
+                    path: path.clone(),
+                    participant: *id,
+                    selection: Some(*selection),
+                }),
+        );
+        for message in messages {
+            self.send(participant, message);
+        }
+    }
+
+    fn close(&mut self, participant: ParticipantId, path: &str) {
+        let document = match self.documents.get_mut(path) {
+            Some(document) => document,
+            None => return,
+        };
+        document.followers.remove(&participant);
+        let had_selection = document.selections.remove(&participant).is_some();
+        if document.followers.is_empty() {
+            self.save(path);
+            self.documents.remove(path);
+        } else if had_selection {
+            let message = ServerMessage::Select {
+                path: path.to_owned(),
+                participant,
+                selection: None,
+            };
+            self.broadcast_to_followers(path, &message, Some(participant));
+        }
+    }
+
+    /// Apply an edit to the content in memory.
+    /// The content is written to the sandbox by `save`.
+    fn edit(
+        &mut self,
+        participant: ParticipantId,
+        path: String,
+        revision: u64,
+        operation: TextOperation,
+    ) {
+        let can_edit = self
+            .members
+            .get(&participant)
+            .is_some_and(|member| member.participant.can_edit);
+        if !can_edit {
+            return self.send_error(participant, protocol::Error::NotSessionOwner);
+        }
+
+        let document = match self.documents.get_mut(&path) {
+            Some(document) if document.followers.contains(&participant) => document,
+            _ => return self.send_error(participant, protocol::Error::SandboxFileNotFound),
+        };
+
+        let applied = document.rebase(revision, operation).and_then(|operation| {
+            let content = operation.apply(&document.content)?;
+            Some((operation, content))
+        });
+        let (operation, content) = match applied {
+            Some(applied) => applied,
+            None => {
+                // the client is out of sync
+                let snapshot = document.snapshot(&path);
+                return self.send(participant, snapshot);
+            }
+        };
+
+        document.content = content;
+        document.unsaved = true;
+        for selection in document.selections.values_mut() {
+            selection.anchor = operation.transform_index(selection.anchor);
+            selection.head = operation.transform_index(selection.head);
+        }
+        document.push_history(operation.clone());
+        let revision = document.revision;
+
+        self.send(
+            participant,
+            ServerMessage::Ack {
+                path: path.clone(),
+                revision,
+            },
+        );
+        let message = ServerMessage::Edit {
+            path: path.clone(),
+            revision,
+            operation,
+            participant,
+        };
+        self.broadcast_to_followers(&path, &message, Some(participant));
+    }
+
+    /// Write the edits of a document to the sandbox.
+    fn save(&mut self, path: &str) {
+        let document = match self.documents.get_mut(path) {
+            Some(document) if document.unsaved => document,
+            _ => return,
+        };
+
+        let precondition = Precondition::Version(document.version.clone());
+        match self.session.sandbox.write_to_file(
+            Path::new(path),
+            document.content.as_bytes(),
+            &precondition,
+        ) {
+            Ok(version) => {
+                document.version = version;
+                document.unsaved = false;
+            }
+            Err(sandbox::Error::VersionMismatch { .. }) => {
+                // the file was saved somewhere else, everyone starts over
+                self.reload(path);
+            }
+            Err(err) => {
+                // go back to the content in the sandbox
+                tracing::debug!("unable to write collaborative edits: {}", err);
+                let error = protocol::Error::from(err);
+                let followers = document.followers.clone();
+                self.reload(path);
+                for id in followers {
+                    self.send_error(id, error.clone());
+                }
+            }
+        }
+    }
+
+    fn save_all(&mut self) {
+        let paths: Vec<String> = self.documents.keys().cloned().collect();
+        for path in paths {
+            self.save(&path);
+        }
+    }
+
+    fn select(&mut self, participant: ParticipantId, path: String, selection: Option<Selection>) {
+        let document = match self.documents.get_mut(&path) {
+            Some(document) if document.followers.contains(&participant) => document,
+            _ => return,
+        };
+        match selection {
+            Some(selection) => document.selections.insert(participant, selection),
+            None => document.selections.remove(&participant),
+        };
+
+        let message = ServerMessage::Select {
+            path: path.clone(),
+            participant,
+            selection,
+        };
+        self.broadcast_to_followers(&path, &message, Some(participant));
+    }
+
+    /// Reload a document from the sandbox and send it to the followers if it
+    /// changed.
+    fn reload(&mut self, path: &str) {
+        let document = match self.documents.get_mut(path) {
+            Some(document) => document,
+            None => return,
+        };
+        match document.reload(&self.session, path) {
+            Ok(true) => {
+                let snapshot = document.snapshot(path);
+                self.broadcast_to_followers(path, &snapshot, None);
+            }
+            Ok(false) => {}
+            Err(err) => {
+                tracing::debug!("removing document {} which can't be reloaded", path);
+                let followers = document.followers.clone();
+                self.documents.remove(path);
+                for id in followers {
+                    self.send_error(id, err.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Connects the participants of every session.
+#[derive(Debug)]
+pub struct Hub {
+    max_participants: usize,
+    rooms: Mutex<HashMap<Uuid, Arc<Mutex<Room>>>>,
+    next_participant: AtomicU64,
+}
+impl Hub {
+    pub fn new(max_participants: usize) -> Self {
+        Self {
+            max_participants,
+            rooms: Mutex::default(),
+            next_participant: AtomicU64::new(1),
+        }
+    }
+
+    fn room(&self, session: &Uuid) -> Option<Arc<Mutex<Room>>> {
+        let rooms = self.rooms.lock().expect("hub lock poisoned");
+        rooms.get(session).cloned()
+    }
+
+    fn with_room(&self, session: &Uuid, f: impl FnOnce(&mut Room)) {
+        if let Some(room) = self.room(session) {
+            f(&mut room.lock().expect("room lock poisoned"));
+        }
+    }
+
+    /// Add a participant to the session.
+    /// Messages for the participant are sent to `sender`.
+    pub fn join(
+        &self,
+        session: &SessionRef,
+        can_edit: bool,
+        sender: Sender<ServerMessage>,
+    ) -> Result<ParticipantId, Error> {
+        // holding the hub lock makes sure that the room isn't removed
+        let mut rooms = self.rooms.lock().expect("hub lock poisoned");
+        let room = rooms
+            .entry(session.id)
+            .or_insert_with(|| Arc::new(Mutex::new(Room::new(Arc::clone(session)))));
+        let room = Arc::clone(room);
+        let mut room = room.lock().expect("room lock poisoned");
+        drop(rooms);
+        if room.members.len() >= self.max_participants {
+            return Err(Error::SessionFull(self.max_participants));
+        }
+
+        let id = self.next_participant.fetch_add(1, Ordering::Relaxed);
+        let participant = Participant {
+            id,
+            name: format!("Guest {}", id),
+            can_edit,
+        };
+        let participants = room
+            .members
+            .values()
+            .map(|member| member.participant.clone())
+            .collect();
+        let _ = sender.send(ServerMessage::Welcome {
+            you: participant.clone(),
+            participants,
+        });
+        room.broadcast(
+            &ServerMessage::Joined {
+                participant: participant.clone(),
+            },
+            None,
+        );
+        room.members.insert(
+            id,
+            Member {
+                participant,
+                sender,
+            },
+        );
+
+        tracing::debug!("participant {} joined session {}", id, session.id);
+        Ok(id)
+    }
+
+    /// Remove a participant from the session.
+    pub fn leave(&self, session: &Uuid, participant: ParticipantId) {
+        let mut rooms = self.rooms.lock().expect("hub lock poisoned");
+        let room = match rooms.get(session) {
+            Some(room) => Arc::clone(room),
+            None => return,
+        };
+        let mut room = room.lock().expect("room lock poisoned");
+
+        room.members.remove(&participant);
+        let paths: Vec<String> = room.documents.keys().cloned().collect();
+        for path in paths {
+            room.close(participant, &path);
+        }
+        room.broadcast(&ServerMessage::Left { participant }, None);
+
+        if room.members.is_empty() {
+            rooms.remove(session);
+        }
+        tracing::debug!("participant {} left session {}", participant, session);
+    }
+
+    pub fn handle(&self, session: &Session, participant: ParticipantId, message: ClientMessage) {
+        self.with_room(&session.id, |room| match message {
+            ClientMessage::Open { path } => room.open(participant, path),
+            ClientMessage::Close { path } => room.close(participant, &path),
+            ClientMessage::Edit {
+                path,
+                revision,
+                operation,
+            } => room.edit(participant, path, revision, operation),
+            ClientMessage::Select { path, selection } => room.select(participant, path, selection),
+        });
+    }
+
+    /// Write the edits made in the session to the sandbox.
+    /// Has to be called before the files of the session are used.
+    pub fn save(&self, session: &Session) {
+        self.with_room(&session.id, Room::save_all);
+    }
+
+    /// Write the edits made in every session to the sandbox.
+    pub fn save_all(&self) {
+        let rooms: Vec<_> = {
+            let rooms = self.rooms.lock().expect("hub lock poisoned");
+            rooms.values().cloned().collect()
+        };
+        for room in rooms {
+            room.lock().expect("room lock poisoned").save_all();
+        }
+    }
+
+    /// Reload a file which was written outside of the collaboration session.
+    pub fn file_written(&self, session: &Session, path: &Path) {
+        self.with_room(&session.id, |room| {
+            if let Some(path) = path.to_str() {
+                room.reload(path);
+            }
+        });
+    }
+
+    /// Reload all open documents after a tool modified the files.
+    pub fn files_changed(&self, session: &Session) {
+        self.with_room(&session.id, |room| {
+            let paths: Vec<String> = room.documents.keys().cloned().collect();
+            for path in paths {
+                room.reload(&path);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        janitor::{Janitor, SessionRef},
+        sandbox::{Context, FakeBackend, Quota, Sandbox},
+    };
+    use std::{
+        env,
+        fs,
+        sync::mpsc::{self, Receiver},
+    };
+
+    const TEMPLATE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../template");
+    const PATH: &str = "src/lib.rs";
+
+    fn create_session(janitor: &Janitor) -> SessionRef {
+        let context = Context::new(
+            Arc::new(FakeBackend::succeeding()),
+            env::temp_dir().join("yew-playground-test"),
+            Quota::default(),
+        )
+        .map(Arc::new)
+        .unwrap();
+        janitor
+            .create_session(|id| {
+                Sandbox::create_from_template(Path::new(TEMPLATE_PATH), id, context)
+            })
+            .unwrap()
+    }
+
+    fn join(
+        hub: &Hub,
+        session: &SessionRef,
+        can_edit: bool,
+    ) -> (ParticipantId, Receiver<ServerMessage>) {
+        let (sender, receiver) = mpsc::channel();
+        let id = hub.join(session, can_edit, sender).unwrap();
+        (id, receiver)
+    }
+
+    fn open(hub: &Hub, session: &Session, participant: ParticipantId) {
+        let path = String::from(PATH);
+        hub.handle(session, participant, ClientMessage::Open { path });
+    }
+
+    fn edit(
+        hub: &Hub,
+        session: &Session,
+        participant: ParticipantId,
+        revision: u64,
+        operation: TextOperation,
+    ) {
+        let path = String::from(PATH);
+        hub.handle(
+            session,
+            participant,
+            ClientMessage::Edit {
+                path,
+                revision,
+                operation,
+            },
+        );
+    }
+
+    fn insert_at(len: u64, index: u64, text: &str) -> TextOperation {
+        let mut operation = TextOperation::new();
+        operation.retain(index).insert(text).retain(len - index);
+        operation
+    }
+
+    #[test]
+    fn concurrent_edits_converge() {
+        let janitor = Janitor::default();
+        let session = create_session(&janitor);
+        let hub = Hub::new(4);
+        let (alice, alice_messages) = join(&hub, &session, true);
+        let (bob, bob_messages) = join(&hub, &session, true);
+        open(&hub, &session, alice);
+        open(&hub, &session, bob);
+
+        let content = fs::read_to_string(Path::new(TEMPLATE_PATH).join(PATH)).unwrap();
+        let len = content.encode_utf16().count() as u64;
+        // both edit revision 0 at the same time
+        edit(&hub, &session, alice, 0, insert_at(len, 0, "// alice\n"));
+        edit(&hub, &session, bob, 0, insert_at(len, len, "// bob\n"));
+
+        let expected = format!("// alice\n{}// bob\n", content);
+        hub.save(&session);
+        let (_, saved) = session.sandbox.read_file(Path::new(PATH)).unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap(), expected);
+
+        // bob applies alice's edit on top of his own unacknowledged one
+        let bob_messages: Vec<_> = bob_messages.try_iter().collect();
+        let alice_edit = bob_messages
+            .iter()
+            .find_map(|message| match message {
+                ServerMessage::Edit { operation, .. } => Some(operation.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let bob_local = insert_at(len, len, "// bob\n").apply(&content).unwrap();
+        let (alice_edit, _) =
+            TextOperation::transform(&alice_edit, &insert_at(len, len, "// bob\n")).unwrap();
+        assert_eq!(alice_edit.apply(&bob_local).unwrap(), expected);
+        assert!(bob_messages
+            .iter()
+            .any(|message| matches!(message, ServerMessage::Ack { revision: 2, .. })));
+
+        let alice_messages: Vec<_> = alice_messages.try_iter().collect();
+        assert!(alice_messages
+            .iter()
+            .any(|message| matches!(message, ServerMessage::Ack { revision: 1, .. })));
+    }
+
+    #[test]
+    fn edits_are_written_in_batches() {
+        let janitor = Janitor::default();
+        let session = create_session(&janitor);
+        let hub = Hub::new(4);
+        let (participant, _messages) = join(&hub, &session, true);
+        open(&hub, &session, participant);
+
+        let content = fs::read_to_string(Path::new(TEMPLATE_PATH).join(PATH)).unwrap();
+        let len = content.encode_utf16().count() as u64;
+        edit(&hub, &session, participant, 0, insert_at(len, 0, "a"));
+        edit(&hub, &session, participant, 1, insert_at(len + 1, 0, "b"));
+        let (_, saved) = session.sandbox.read_file(Path::new(PATH)).unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap(), content);
+
+        hub.save_all();
+        let (_, saved) = session.sandbox.read_file(Path::new(PATH)).unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap(), format!("ba{}", content));
+
+        // the last participant leaving writes the remaining edits
+        edit(&hub, &session, participant, 2, insert_at(len + 2, 0, "c"));
+        hub.leave(&session.id, participant);
+        let (_, saved) = session.sandbox.read_file(Path::new(PATH)).unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap(), format!("cba{}", content));
+    }
+
+    #[test]
+    fn viewers_cant_edit() {
+        let janitor = Janitor::default();
+        let session = create_session(&janitor);
+        let hub = Hub::new(4);
+        let (viewer, messages) = join(&hub, &session, false);
+        open(&hub, &session, viewer);
+
+        edit(&hub, &session, viewer, 0, insert_at(0, 0, "x"));
+        assert!(messages.try_iter().any(|message| matches!(
+            message,
+            ServerMessage::Error {
+                error: protocol::Error::NotSessionOwner
+            }
+        )));
+    }
+
+    #[test]
+    fn external_writes_resync_the_document() {
+        let janitor = Janitor::default();
+        let session = create_session(&janitor);
+        let hub = Hub::new(1);
+        let (participant, messages) = join(&hub, &session, true);
+        open(&hub, &session, participant);
+        assert!(hub.join(&session, true, mpsc::channel().0).is_err());
+
+        session
+            .sandbox
+            .write_to_file(Path::new(PATH), b"saved", &Precondition::Any)
+            .unwrap();
+        hub.file_written(&session, Path::new(PATH));
+
+        let last = messages.try_iter().last().unwrap();
+        assert!(matches!(
+            last,
+            ServerMessage::Document { revision: 1, ref content, .. } if content == "saved"
+        ));
+    }
+}
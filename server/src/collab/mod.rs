@@ -0,0 +1,36 @@
+//! Real-time collaboration on the files of a session.
+//!
+//! Participants connect to a WebSocket served on a separate port because
+//! Rocket can't upgrade connections. Edits are merged using operational
+//! transformation and written to the sandbox in batches. The API writes the
+//! pending edits of a session before it uses its files, so the sandbox stays
+//! the source of truth.
+
+pub use hub::Hub;
+use serde::Deserialize;
+pub use socket::spawn_server;
+use std::net::SocketAddr;
+
+mod hub;
+mod socket;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CollabConfig {
+    /// Address the WebSocket server listens on.
+    pub address: SocketAddr,
+    /// Maximum number of participants connected to a session at once.
+    pub max_participants: usize,
+    /// Maximum number of connections handled at once across all sessions.
+    /// Every connection is handled by its own thread.
+    pub max_connections: usize,
+}
+impl Default for CollabConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 8001)),
+            max_participants: 16,
+            max_connections: 256,
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! WebSocket connections of the participants.
+//!
+//! Clients connect to `/collab/{session id}`. The owner token can be passed
+//! as the `token` query parameter to be able to edit the files.
+
+use super::{CollabConfig, Hub};
+use crate::{
+    janitor::{Janitor, SessionRef},
+    rate_limit::{self, LimitKind, RateLimiter},
+};
+use protocol::{ClientMessage, FileChange, ParticipantId, ServerMessage};
+use std::{
+    io,
+    net::{IpAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header, HeaderValue, StatusCode},
+    protocol::WebSocketConfig,
+    Message,
+    WebSocket,
+};
+use uuid::Uuid;
+
+/// Time a client has to complete the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time between checking for outgoing messages.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Maximum size of a message sent by a client.
+const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Time between writing the edits to the sandboxes.
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Start accepting connections in the background.
+pub fn spawn_server(
+    config: &CollabConfig,
+    hub: Arc<Hub>,
+    janitor: Arc<Janitor>,
+    limiter: Arc<RateLimiter>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(config.address)?;
+    tracing::info!("collaboration server listening on {}", config.address);
+
+    let saving_hub = Arc::clone(&hub);
+    thread::Builder::new()
+        .name(String::from("collab-save"))
+        .spawn(move || loop {
+            thread::sleep(SAVE_INTERVAL);
+            saving_hub.save_all();
+        })?;
+
+    let max_connections = config.max_connections;
+    let connections = Arc::new(AtomicUsize::new(0));
+    thread::Builder::new()
+        .name(String::from("collab"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        tracing::warn!("failed to accept collaboration connection: {}", err);
+                        continue;
+                    }
+                };
+                let slot = match ConnectionSlot::acquire(&connections, max_connections) {
+                    Some(slot) => slot,
+                    None => {
+                        tracing::warn!("rejecting collaboration connection, too many are open");
+                        continue;
+                    }
+                };
+
+                let hub = Arc::clone(&hub);
+                let janitor = Arc::clone(&janitor);
+                let limiter = Arc::clone(&limiter);
+                thread::spawn(move || {
+                    let _slot = slot;
+                    if let Err(err) = handle_connection(stream, &hub, &janitor, &limiter) {
+                        tracing::debug!("collaboration connection failed: {}", err);
+                    }
+                });
+            }
+        })?;
+    Ok(())
+}
+
+/// Counts towards the open connections until it's dropped.
+struct ConnectionSlot(Arc<AtomicUsize>);
+impl ConnectionSlot {
+    fn acquire(connections: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                if open < max {
+                    Some(open + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(connections)))
+    }
+}
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Session and owner token taken from the request uri.
+fn parse_target(request: &Request) -> Option<(Uuid, Option<String>)> {
+    let uri = request.uri();
+    let id = uri.path().strip_prefix("/collab/")?;
+    let id = Uuid::parse_str(id.trim_end_matches('/')).ok()?;
+    let token = uri.query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .map(String::from)
+    });
+    Some((id, token))
+}
+
+fn error_response(status: StatusCode) -> ErrorResponse {
+    let mut response = ErrorResponse::new(None);
+    *response.status_mut() = status;
+    response
+}
+
+fn rate_limited_response(retry_after: Duration) -> ErrorResponse {
+    let mut response = error_response(StatusCode::TOO_MANY_REQUESTS);
+    let seconds = rate_limit::retry_after_secs(retry_after);
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
+
+/// Take a token from the bucket of the client which opened the connection.
+/// Returns the time until the client may connect again if it's rate limited.
+fn acquire_connection(
+    limiter: &RateLimiter,
+    remote: IpAddr,
+    request: &Request,
+) -> Result<(), Duration> {
+    let forwarded_for: Vec<&str> = request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    let client = limiter.client_addr(remote, forwarded_for.into_iter());
+
+    limiter
+        .acquire(LimitKind::Connections, client)
+        .inspect_err(|_| tracing::debug!("rate limited collaboration connection of {}", client))
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    hub: &Hub,
+    janitor: &Janitor,
+    limiter: &RateLimiter,
+) -> tungstenite::Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let remote = stream.peer_addr()?.ip();
+    let mut target = None;
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_MESSAGE_SIZE),
+        ..WebSocketConfig::default()
+    };
+    let mut socket = tungstenite::server::accept_hdr_with_config(
+        stream,
+        |request: &Request, response: Response| {
+            if janitor.is_shutting_down() {
+                return Err(error_response(StatusCode::SERVICE_UNAVAILABLE));
+            }
+            acquire_connection(limiter, remote, request).map_err(rate_limited_response)?;
+            let session = parse_target(request)
+                .and_then(|(id, token)| janitor.get_session(&id).map(|session| (session, token)));
+            match session {
+                Some(session) => {
+                    target = Some(session);
+                    Ok(response)
+                }
+                None => Err(error_response(StatusCode::NOT_FOUND)),
+            }
+        },
+        Some(config),
+    )
+    .map_err(|err| match err {
+        tungstenite::HandshakeError::Failure(err) => err,
+        tungstenite::HandshakeError::Interrupted(_) => {
+            tungstenite::Error::Io(io::ErrorKind::WouldBlock.into())
+        }
+    })?;
+    let (session, token) = target.expect("handshake succeeded without a session");
+
+    let can_edit = token.is_some_and(|token| session.is_owner(&token));
+    let (sender, receiver) = mpsc::channel();
+    let changes = session.sandbox.subscribe_changes();
+    let participant = match hub.join(&session, can_edit, sender) {
+        Ok(participant) => participant,
+        Err(err) => {
+            tracing::debug!("rejecting participant: {}", err);
+            return socket.close(None);
+        }
+    };
+
+    let result = socket
+        .get_mut()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(tungstenite::Error::Io)
+        .and_then(|_| {
+            let receivers = Receivers {
+                messages: &receiver,
+                changes: &changes,
+            };
+            run(&mut socket, hub, janitor, &session, participant, receivers)
+        });
+    hub.leave(&session.id, participant);
+    result
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, message: &ServerMessage) -> tungstenite::Result<()> {
+    let text = serde_json::to_string(message).expect("failed to serialize message");
+    socket.write_message(Message::Text(text))
+}
+
+/// Sources of the messages sent to a participant.
+struct Receivers<'a> {
+    /// Messages of the hub.
+    messages: &'a Receiver<ServerMessage>,
+    /// Changes to the files of the session.
+    changes: &'a Receiver<Vec<FileChange>>,
+}
+
+fn run(
+    socket: &mut WebSocket<TcpStream>,
+    hub: &Hub,
+    janitor: &Janitor,
+    session: &SessionRef,
+    participant: ParticipantId,
+    receivers: Receivers,
+) -> tungstenite::Result<()> {
+    loop {
+        match socket.read_message() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(message) => hub.handle(session, participant, message),
+                Err(err) => {
+                    let error = protocol::Error::InvalidRequest {
+                        message: err.to_string(),
+                    };
+                    send(socket, &ServerMessage::Error { error })?;
+                }
+            },
+            // pings are answered automatically
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(err) => return Err(err),
+        }
+
+        // the session expired or the server is shutting down
+        if !janitor.is_known_session(&session.id) {
+            return socket.close(None);
+        }
+
+        for message in receivers.messages.try_iter() {
+            send(socket, &message)?;
+        }
+        for changes in receivers.changes.try_iter() {
+            send(socket, &ServerMessage::FilesChanged { changes })?;
+        }
+        socket.write_pending()?;
+    }
+}
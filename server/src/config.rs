@@ -0,0 +1,135 @@
+use crate::{
+    admin::AdminConfig,
+    collab::CollabConfig,
+    metrics::MetricsConfig,
+    rate_limit::RateLimitConfig,
+    sandbox::{
+        self,
+        AuditConfig,
+        Backend,
+        Context,
+        DockerBackend,
+        DockerConfig,
+        LocalBackend,
+        Quota,
+    },
+};
+use rocket::config::{Table, Value};
+use serde::Deserialize;
+use std::{env, path::PathBuf, sync::Arc, thread};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid configuration: {0}")]
+    Invalid(String),
+    #[error("unable to create the backend: {0}")]
+    UnableToCreateBackend(#[from] sandbox::Error),
+}
+
+/// Backend used to execute the tools.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    Docker,
+    Local,
+}
+impl Default for BackendKind {
+    fn default() -> Self {
+        Self::Docker
+    }
+}
+
+/// Configuration for `BackendKind::Local`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LocalConfig {
+    /// Manifest used for the playground crate.
+    pub manifest_path: PathBuf,
+    /// Target directory shared by all sandboxes.
+    pub target_dir: PathBuf,
+}
+impl Default for LocalConfig {
+    fn default() -> Self {
+        Self {
+            manifest_path: PathBuf::from("docker/base/Cargo.toml"),
+            target_dir: PathBuf::from("target/playground-local"),
+        }
+    }
+}
+
+/// Server configuration.
+/// The values are read from the extras of the Rocket config so they can be
+/// set in `Rocket.toml` or using `ROCKET_` environment variables.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub backend: BackendKind,
+    /// Directory for the files of the sandboxes.
+    /// Every instance uses a subdirectory named after `docker.instance` in
+    /// which the files of unknown sessions are removed on startup.
+    pub scratch_dir: PathBuf,
+    pub quota: Quota,
+    /// Maximum number of tool invocations which run at once, the others wait
+    /// for one of them to finish.
+    pub max_running_invocations: usize,
+    pub rate_limit: RateLimitConfig,
+    pub collab: CollabConfig,
+    pub metrics: MetricsConfig,
+    pub admin: AdminConfig,
+    pub audit: AuditConfig,
+    pub docker: DockerConfig,
+    pub local: LocalConfig,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            backend: BackendKind::default(),
+            scratch_dir: env::temp_dir().join("yew-playground"),
+            quota: Quota::default(),
+            max_running_invocations: thread::available_parallelism().map_or(1, usize::from),
+            rate_limit: RateLimitConfig::default(),
+            collab: CollabConfig::default(),
+            metrics: MetricsConfig::default(),
+            admin: AdminConfig::default(),
+            audit: AuditConfig::default(),
+            docker: DockerConfig::default(),
+            local: LocalConfig::default(),
+        }
+    }
+}
+impl Config {
+    pub fn from_rocket(config: &rocket::Config) -> Result<Self, Error> {
+        let extras: Table = config
+            .extras
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Value::Table(extras)
+            .try_into()
+            .map_err(|err| Error::Invalid(err.to_string()))
+    }
+
+    pub fn create_context(&self) -> Result<Arc<Context>, Error> {
+        let context = Context::new(
+            self.create_backend()?,
+            self.scratch_dir.join(&self.docker.instance),
+            self.quota.clone(),
+        )?
+        .with_audit(self.audit.clone())
+        .with_max_running(self.max_running_invocations);
+        Ok(Arc::new(context))
+    }
+
+    fn create_backend(&self) -> Result<Arc<dyn Backend>, Error> {
+        Ok(match self.backend {
+            BackendKind::Docker => Arc::new(DockerBackend::new(self.docker.clone())?),
+            BackendKind::Local => {
+                tracing::warn!("using the local backend, code is executed without any isolation!");
+                Arc::new(LocalBackend::new(
+                    self.local.manifest_path.clone(),
+                    self.local.target_dir.clone(),
+                )?)
+            }
+        })
+    }
+}
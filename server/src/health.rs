@@ -0,0 +1,24 @@
+//! Checks whether the server is able to handle requests.
+
+use crate::sandbox::Context;
+use std::fs;
+
+/// Directory new sandboxes are copied from.
+pub const TEMPLATE_DIR: &str = "template";
+/// Directory containing the frontend.
+pub const STATIC_DIR: &str = "www";
+
+/// Run all readiness checks.
+/// Returns a description of every check which failed.
+pub fn check_readiness(context: &Context) -> Vec<String> {
+    let mut failures = Vec::new();
+    if let Err(err) = context.check_ready() {
+        failures.push(err.to_string());
+    }
+    for dir in &[TEMPLATE_DIR, STATIC_DIR] {
+        if let Err(err) = fs::read_dir(dir) {
+            failures.push(format!("unable to read directory {}: {}", dir, err));
+        }
+    }
+    failures
+}
@@ -3,7 +3,12 @@ use std::{
     borrow::Borrow,
     collections::HashSet,
     hash::{Hash, Hasher},
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+        RwLock,
+    },
     time::Instant,
 };
 use uuid::Uuid;
@@ -15,11 +20,37 @@ pub struct Session {
     pub id: Uuid,
     pub sandbox: Sandbox,
     pub created_at: Instant,
+    last_active: Mutex<Instant>,
+    /// Secret which grants write access to the session.
+    owner_token: String,
 }
 impl Session {
     pub fn get_id_string(&self) -> String {
         self.id.to_simple().to_string()
     }
+
+    /// Time the session was last accessed.
+    pub fn last_active(&self) -> Instant {
+        *self.last_active.lock().unwrap()
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    pub fn owner_token(&self) -> &str {
+        &self.owner_token
+    }
+
+    /// Check whether the token is the owner token of the session.
+    pub fn is_owner(&self, token: &str) -> bool {
+        constant_time_eq(self.owner_token.as_bytes(), token.as_bytes())
+    }
+}
+
+/// Compare two byte strings in time which only depends on their length.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 /// Helper type to compare `Session` based on the id.
@@ -48,11 +79,15 @@ impl Hash for SessionById {
 }
 
 /// Keeps track of active sessions.
+// sessions are hashed by their id, which never changes
+#[allow(clippy::mutable_key_type)]
 #[derive(Debug, Default)]
 pub struct Janitor {
     sessions: RwLock<HashSet<SessionById>>,
+    shutting_down: AtomicBool,
 }
 impl Janitor {
+    #[allow(clippy::mutable_key_type)]
     fn new_session_id(sessions: &HashSet<SessionById>) -> Uuid {
         loop {
             let id = Uuid::new_v4();
@@ -62,24 +97,81 @@ impl Janitor {
         }
     }
 
-    /// Create a new session for the sandbox.
-    pub fn create_session(&self, sandbox: Sandbox) -> SessionRef {
+    /// Create a new session.
+    /// The sandbox is created using the id of the session.
+    pub fn create_session<E>(
+        &self,
+        create_sandbox: impl FnOnce(Uuid) -> Result<Sandbox, E>,
+    ) -> Result<SessionRef, E> {
         let mut sessions = self.sessions.write().unwrap();
         let id = Self::new_session_id(&sessions);
         let session = Session {
             id,
-            sandbox,
+            sandbox: create_sandbox(id)?,
             created_at: Instant::now(),
+            last_active: Mutex::new(Instant::now()),
+            owner_token: Uuid::new_v4().to_simple().to_string(),
         };
-        log::debug!("created new session {}", session.id);
-        sessions
+        tracing::debug!("created new session {}", session.id);
+        Ok(sessions
             .get_or_insert(SessionById(Arc::new(session)))
-            .to_session_ref()
+            .to_session_ref())
+    }
+
+    /// Number of live sessions.
+    pub fn session_count(&self) -> usize {
+        self.sessions.read().unwrap().len()
+    }
+
+    pub fn is_known_session(&self, id: &Uuid) -> bool {
+        self.sessions.read().unwrap().contains(id)
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Stop accepting new jobs and drop all sessions.
+    /// Sandboxes which are still in use are removed once the last reference
+    /// is gone.
+    /// The audit logs are exported right away because the server may exit
+    /// before that.
+    pub fn shut_down(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let sessions: Vec<_> = self.sessions.write().unwrap().drain().collect();
+        tracing::info!("dropping {} session(s)", sessions.len());
+        for session in &sessions {
+            session.0.sandbox.export_audit_log();
+        }
     }
 
     /// Get a session by its id.
+    /// This counts as activity in the session.
     pub fn get_session(&self, id: &Uuid) -> Option<SessionRef> {
         let sessions = self.sessions.read().unwrap();
-        sessions.get(id).map(SessionById::to_session_ref)
+        let session = sessions.get(id)?;
+        session.0.touch();
+        Some(session.to_session_ref())
+    }
+
+    /// Get all live sessions, oldest first.
+    pub fn sessions(&self) -> Vec<SessionRef> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .read()
+            .unwrap()
+            .iter()
+            .map(SessionById::to_session_ref)
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        sessions
+    }
+
+    /// Drop a session.
+    /// The sandbox is removed once the last reference is gone.
+    pub fn remove_session(&self, id: &Uuid) -> Option<SessionRef> {
+        let session = self.sessions.write().unwrap().take(id)?;
+        tracing::info!("removed session {}", session.0.id);
+        Some(session.0)
     }
 }
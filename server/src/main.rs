@@ -1,56 +1,115 @@
 #![feature(decl_macro, hash_set_entry, never_type, proc_macro_hygiene)]
 
+use collab::Hub;
+use config::Config;
 use janitor::{Janitor, SessionRef};
 use protocol::{
+    ApiVersion,
+    Capabilities,
+    Channel,
+    CheckRequest,
+    CheckResponse,
     ClippyRequest,
-    ClippyResponse,
     CompileRequest,
     CompileResponse,
+    Edition,
+    EmitRequest,
+    EmitResponse,
+    EmitTarget,
     FormatRequest,
-    FormatResponse,
     MacroExpandRequest,
-    MacroExpandResponse,
     SandboxStructure,
     SessionDetails,
+    SizeRequest,
+    SizeResponse,
+    ToolOutput,
     ToolVersions,
+    PROTOCOL_VERSION,
 };
+use rate_limit::{RateLimiter, RetryAfter, SessionPermit, ToolPermit};
 use response::Content;
 use rocket::{
-    http::{ContentType, Status},
-    response::{self, NamedFile, Responder},
+    fairing::AdHoc,
+    http::{ContentType, Header, Status},
+    request::{self, FromRequest},
+    response::{self, status, Responder},
+    Data,
+    Outcome,
     Response,
     State,
 };
 use rocket_contrib::{json::Json, uuid::Uuid as UuidParam};
-use sandbox::Sandbox;
+use sandbox::{content_version, entity_tag, Context, Precondition, Sandbox};
+use serde_json::Value;
 use serve::SPAStaticFiles;
-use std::path::{Path, PathBuf};
+use signal_hook::iterator::Signals;
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    thread,
+};
 
+mod admin;
+mod collab;
+mod config;
+mod health;
 mod janitor;
+mod metrics;
+mod openapi;
+mod rate_limit;
 mod sandbox;
 mod serve;
+#[cfg(test)]
+mod tests;
+mod trace;
 
 #[derive(Debug)]
 struct Error(Status, protocol::Error);
 impl From<sandbox::Error> for Error {
     fn from(err: sandbox::Error) -> Self {
-        log::error!("internal sandbox error: {:?}", err);
-        Self::from(protocol::Error::InternalError(err.to_string()))
+        let err = protocol::Error::from(err);
+        if let protocol::Error::InternalError { message } = &err {
+            tracing::error!("internal sandbox error: {}", message);
+        }
+        Self::from(err)
     }
 }
 impl From<protocol::Error> for Error {
     fn from(err: protocol::Error) -> Self {
         use protocol::Error::*;
         match err {
-            InternalError(_) => Self(Status::InternalServerError, err),
+            InternalError { .. } => Self(Status::InternalServerError, err),
+            InvalidRequest { .. } | InvalidPath => Self(Status::BadRequest, err),
             SessionNotFound | SandboxFileNotFound => Self(Status::NotFound, err),
+            NotSessionOwner | NotAdmin => Self(Status::Forbidden, err),
+            FileConflict { .. } => Self(Status::PreconditionFailed, err),
+            PreconditionRequired => Self(Status::PreconditionRequired, err),
+            ShuttingDown => Self(Status::ServiceUnavailable, err),
+            QuotaExceeded { .. } => Self(Status::PayloadTooLarge, err),
+            RateLimited { .. } => Self(Status::TooManyRequests, err),
+            ToolUnavailable => Self(Status::ServiceUnavailable, err),
+            ToolTimedOut { .. } => Self(Status::GatewayTimeout, err),
+            BuildMissing => Self(Status::Conflict, err),
         }
     }
 }
 impl<'r> Responder<'r> for Error {
     fn respond_to(self, request: &rocket::Request) -> response::Result<'r> {
         let Error(status, error) = self;
-        Response::build()
+        let mut response = Response::build();
+        match &error {
+            protocol::Error::RateLimited { retry_after } => {
+                response.header(Header::new("Retry-After", retry_after.to_string()));
+            }
+            protocol::Error::FileConflict { etag: Some(etag) } => {
+                response.header(Header::new("ETag", etag.clone()));
+            }
+            _ => {}
+        }
+        response
             .merge(Json(error).respond_to(request)?)
             .status(status)
             .ok()
@@ -59,24 +118,236 @@ impl<'r> Responder<'r> for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Response with an `ETag` header.
+#[derive(Debug)]
+struct WithETag<R>(String, R);
+impl<'r, R: Responder<'r>> Responder<'r> for WithETag<R> {
+    fn respond_to(self, request: &rocket::Request) -> response::Result<'r> {
+        let WithETag(etag, inner) = self;
+        Response::build_from(inner.respond_to(request)?)
+            .header(Header::new("ETag", etag))
+            .ok()
+    }
+}
+
+/// Protection of user content served from the app origin.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Isolation {
+    /// Files of the preview.
+    /// Scripts run in a unique origin so they can't access the storage of
+    /// the app, which holds the owner tokens.
+    /// Its requests are cross-origin, so it's allowed to read its own files.
+    Preview,
+    /// Files read through the API.
+    /// Nothing in them is executed, see `inert_content_type`.
+    Inert { attachment: bool },
+}
+
+#[derive(Debug)]
+struct Isolated<R>(Isolation, R);
+impl<'r, R: Responder<'r>> Responder<'r> for Isolated<R> {
+    fn respond_to(self, request: &rocket::Request) -> response::Result<'r> {
+        let Isolated(isolation, inner) = self;
+        let mut response = Response::build_from(inner.respond_to(request)?);
+        response.header(Header::new("X-Content-Type-Options", "nosniff"));
+        match isolation {
+            Isolation::Preview => {
+                response
+                    .header(Header::new(
+                        "Content-Security-Policy",
+                        "sandbox allow-scripts allow-forms",
+                    ))
+                    .header(Header::new("Access-Control-Allow-Origin", "*"));
+            }
+            Isolation::Inert { attachment } => {
+                response.header(Header::new("Content-Security-Policy", "sandbox"));
+                if attachment {
+                    response.header(Header::new("Content-Disposition", "attachment"));
+                }
+            }
+        }
+        response.ok()
+    }
+}
+
+/// Number of bytes inspected to decide whether a file is text.
+const CONTENT_SNIFF_LENGTH: usize = 8 * 1024;
+
+/// Determine the content type of a sandbox file.
+/// Well-known extensions are mapped directly, everything else is served as
+/// plain text if `sample`, the start of the content, is valid UTF-8 and as
+/// binary otherwise.
+fn content_type_for(path: &Path, sample: &[u8]) -> ContentType {
+    let from_extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(ContentType::from_extension);
+    if let Some(content_type) = from_extension {
+        return content_type;
+    }
+
+    let sample = &sample[..sample.len().min(CONTENT_SNIFF_LENGTH)];
+    let is_text = match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        // the sample may end in the middle of a character
+        Err(err) => err.error_len().is_none(),
+    };
+
+    if is_text {
+        ContentType::Plain
+    } else {
+        ContentType::Binary
+    }
+}
+
+/// Content type a file read through the API is served with.
+/// Images, fonts and plain text keep their type so the frontend can display
+/// them. Everything else, including SVG images which can contain scripts, is
+/// served as a download.
+/// Returns whether the file must be served as an attachment.
+fn inert_content_type(content_type: ContentType) -> (ContentType, bool) {
+    if content_type.is_svg() {
+        // `<img>` only renders SVG with its own type and never runs its
+        // scripts
+        return (content_type, true);
+    }
+
+    let is_inert =
+        content_type.is_plain() || matches!(content_type.top().as_str(), "image" | "font");
+    if is_inert {
+        (content_type, false)
+    } else {
+        (ContentType::Binary, true)
+    }
+}
+
+/// Determine the content type of an opened sandbox file.
+/// See `content_type_for`.
+fn content_type_for_file(path: &Path, file: &mut File) -> ContentType {
+    let mut sample = Vec::with_capacity(CONTENT_SNIFF_LENGTH);
+    let read = (&mut *file)
+        .take(CONTENT_SNIFF_LENGTH as u64)
+        .read_to_end(&mut sample)
+        .and_then(|_| file.seek(SeekFrom::Start(0)));
+    if read.is_err() {
+        return ContentType::Binary;
+    }
+
+    content_type_for(path, &sample)
+}
+
+#[rocket::catch(429)]
+fn too_many_requests(request: &rocket::Request) -> Error {
+    let RetryAfter(retry_after) = request.local_cache(RetryAfter::default);
+    let retry_after = retry_after.map_or(1, rate_limit::retry_after_secs);
+    Error::from(protocol::Error::RateLimited { retry_after })
+}
+
+/// Requests whose body or parameters can't be parsed.
+#[rocket::catch(400)]
+fn bad_request() -> Error {
+    Error::from(protocol::Error::InvalidRequest {
+        message: String::from("the request is malformed"),
+    })
+}
+
+/// Requests whose body has the wrong shape.
+#[rocket::catch(422)]
+fn unprocessable_entity() -> Error {
+    Error::from(protocol::Error::InvalidRequest {
+        message: String::from("the request body is invalid"),
+    })
+}
+
+#[rocket::catch(500)]
+fn internal_server_error() -> Error {
+    Error::from(protocol::Error::InternalError {
+        message: String::from("the request couldn't be handled"),
+    })
+}
+
 #[rocket::post("/sandbox")]
-fn api_create_sandbox(janitor: State<Janitor>) -> Result<Json<SessionDetails>> {
-    // TODO configurable template
-    let sandbox = Sandbox::create_from_template(Path::new("template"))?;
-    let session = janitor.create_session(sandbox);
+fn api_create_sandbox(
+    _permit: SessionPermit,
+    janitor: State<Arc<Janitor>>,
+    context: State<Arc<Context>>,
+) -> Result<Json<SessionDetails>> {
+    if janitor.is_shutting_down() {
+        return Err(Error::from(protocol::Error::ShuttingDown));
+    }
+
+    let session = janitor.create_session(|id| {
+        // TODO configurable template
+        Sandbox::create_from_template(Path::new(health::TEMPLATE_DIR), id, Arc::clone(&context))
+    })?;
     Ok(Json(SessionDetails {
         id: session.get_id_string(),
         // TODO actual public url
         public_url: format!("http://localhost:8000/proxy/{}/", session.get_id_string()),
+        owner_token: session.owner_token().to_owned(),
     }))
 }
 
+/// Owner token sent as a bearer token in the `Authorization` header.
+#[derive(Debug)]
+struct OwnerToken(Option<String>);
+impl<'a, 'r> FromRequest<'a, 'r> for OwnerToken {
+    type Error = !;
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<Self, Self::Error> {
+        Outcome::Success(Self(bearer_token(request)))
+    }
+}
+
+/// Get the token from the `Authorization: Bearer {token}` header.
+fn bearer_token(request: &rocket::Request) -> Option<String> {
+    request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_owned())
+}
+
+/// Precondition for writing a file taken from the `If-Match` or
+/// `If-None-Match: *` header.
+#[derive(Debug)]
+struct WritePrecondition(Option<Precondition>);
+impl<'a, 'r> FromRequest<'a, 'r> for WritePrecondition {
+    type Error = !;
+
+    fn from_request(request: &'a rocket::Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let precondition = if let Some(value) = headers.get_one("If-Match") {
+            Some(Precondition::from_if_match(value))
+        } else if headers.get_one("If-None-Match").map(str::trim) == Some("*") {
+            Some(Precondition::Missing)
+        } else {
+            None
+        };
+        Outcome::Success(Self(precondition))
+    }
+}
+
 fn get_session(janitor: &Janitor, id: &UuidParam) -> Result<SessionRef> {
+    if janitor.is_shutting_down() {
+        return Err(Error::from(protocol::Error::ShuttingDown));
+    }
+
+    trace::record_session(id);
     janitor
         .get_session(id)
         .ok_or_else(|| Error::from(protocol::Error::SessionNotFound))
 }
 
+/// Get a session which the client owns.
+fn get_owned_session(janitor: &Janitor, id: &UuidParam, owner: &OwnerToken) -> Result<SessionRef> {
+    let session = get_session(janitor, id)?;
+    match &owner.0 {
+        Some(token) if session.is_owner(token) => Ok(session),
+        _ => Err(Error::from(protocol::Error::NotSessionOwner)),
+    }
+}
+
 fn with_session_wrap_result<R, E>(
     janitor: &Janitor,
     id: &UuidParam,
@@ -88,9 +359,28 @@ where
     f(get_session(janitor, id)?).map(Json).map_err(Into::into)
 }
 
+#[rocket::get("/version")]
+fn api_version() -> Json<ApiVersion> {
+    Json(ApiVersion {
+        protocol: PROTOCOL_VERSION.to_owned(),
+        server: env!("CARGO_PKG_VERSION").to_owned(),
+        capabilities: Capabilities {
+            channels: Channel::ALL.to_vec(),
+            editions: Edition::ALL.to_vec(),
+            emit_targets: EmitTarget::ALL.to_vec(),
+            tools: sandbox::tool_names(),
+        },
+    })
+}
+
+#[rocket::get("/openapi.json")]
+fn api_openapi() -> Json<Value> {
+    Json(openapi::document())
+}
+
 #[rocket::get("/<sandbox>/tools")]
 fn api_get_tool_versions(
-    janitor: State<Janitor>,
+    janitor: State<Arc<Janitor>>,
     sandbox: UuidParam,
 ) -> Result<Json<ToolVersions>> {
     with_session_wrap_result(&janitor, &sandbox, |session| {
@@ -100,7 +390,7 @@ fn api_get_tool_versions(
 
 #[rocket::get("/<sandbox>/files")]
 fn api_get_structure(
-    janitor: State<Janitor>,
+    janitor: State<Arc<Janitor>>,
     sandbox: UuidParam,
 ) -> Result<Json<SandboxStructure>> {
     let session = get_session(&janitor, &sandbox)?;
@@ -110,114 +400,321 @@ fn api_get_structure(
 
 #[rocket::get("/<sandbox>/files/<path..>")]
 fn api_get_file(
-    janitor: State<Janitor>,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
     sandbox: UuidParam,
     path: PathBuf,
-) -> Result<Content<NamedFile>> {
+) -> Result<Isolated<WithETag<Content<Vec<u8>>>>> {
     let session = get_session(&janitor, &sandbox)?;
-    let file = session
-        .sandbox
-        .get_file_path(&path)
-        .ok()
-        .and_then(|path| NamedFile::open(path).ok())
-        .ok_or_else(|| Error::from(protocol::Error::SandboxFileNotFound))?;
+    hub.save(&session);
+    let (path, content) = session.sandbox.read_file(&path).map_err(|err| {
+        tracing::debug!("unable to read sandbox file: {}", err);
+        Error::from(protocol::Error::SandboxFileNotFound)
+    })?;
 
-    Ok(Content(ContentType::Plain, file))
+    let etag = entity_tag(&content_version(&content));
+    let (content_type, attachment) = inert_content_type(content_type_for(&path, &content));
+    Ok(Isolated(
+        Isolation::Inert { attachment },
+        WithETag(etag, Content(content_type, content)),
+    ))
 }
 
-#[rocket::put("/<sandbox>/files/<path..>", data = "<code>")]
+#[rocket::put("/<sandbox>/files/<path..>", data = "<data>")]
 fn api_upload_file(
-    janitor: State<Janitor>,
+    owner: OwnerToken,
+    precondition: WritePrecondition,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
     sandbox: UuidParam,
     path: PathBuf,
-    code: String,
-) -> Result<()> {
-    let session = get_session(&janitor, &sandbox)?;
-    session.sandbox.write_to_file(&path, &code)?;
-    Ok(())
+    data: Data,
+) -> Result<WithETag<()>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    // the precondition has to be checked against the latest edits
+    hub.save(&session);
+    // blind writes would silently discard changes made in other tabs
+    let precondition = precondition
+        .0
+        .ok_or_else(|| Error::from(protocol::Error::PreconditionRequired))?;
+
+    // read one byte more than the limit so the sandbox rejects oversized
+    // uploads
+    let limit = session.sandbox.quota().max_file_size;
+    let mut content = Vec::new();
+    data.open()
+        .take(limit + 1)
+        .read_to_end(&mut content)
+        .map_err(sandbox::Error::UnableToReadFile)?;
+
+    let version = session
+        .sandbox
+        .write_to_file(&path, &content, &precondition)?;
+    hub.file_written(&session, &path);
+    Ok(WithETag(entity_tag(&version), ()))
 }
 
 #[rocket::post("/<sandbox>/compile", data = "<req>")]
 fn api_compile(
-    janitor: State<Janitor>,
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
     sandbox: UuidParam,
     req: Json<CompileRequest>,
 ) -> Result<Json<CompileResponse>> {
-    with_session_wrap_result(&janitor, &sandbox, |session| session.sandbox.compile(&*req))
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    Ok(Json(session.sandbox.compile(&req)?))
+}
+#[rocket::post("/<sandbox>/check", data = "<req>")]
+fn api_check(
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
+    sandbox: UuidParam,
+    req: Json<CheckRequest>,
+) -> Result<Json<CheckResponse>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    Ok(Json(session.sandbox.check(&req)?))
+}
+#[rocket::post("/<sandbox>/emit", data = "<req>")]
+fn api_emit(
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
+    sandbox: UuidParam,
+    req: Json<EmitRequest>,
+) -> Result<Json<EmitResponse>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    Ok(Json(session.sandbox.emit(&req)?))
+}
+#[rocket::post("/<sandbox>/size", data = "<req>")]
+fn api_size(
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
+    sandbox: UuidParam,
+    req: Json<SizeRequest>,
+) -> Result<Json<SizeResponse>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    Ok(Json(session.sandbox.size(&req)?))
 }
 #[rocket::post("/<sandbox>/format", data = "<req>")]
 fn api_format(
-    janitor: State<Janitor>,
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
     sandbox: UuidParam,
     req: Json<FormatRequest>,
-) -> Result<Json<FormatResponse>> {
-    with_session_wrap_result(&janitor, &sandbox, |session| session.sandbox.format(&*req))
+) -> Result<Json<ToolOutput>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    let resp = session.sandbox.format(&req)?;
+    hub.files_changed(&session);
+    Ok(Json(resp))
 }
 #[rocket::post("/<sandbox>/clippy", data = "<req>")]
 fn api_clippy(
-    janitor: State<Janitor>,
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
     sandbox: UuidParam,
     req: Json<ClippyRequest>,
-) -> Result<Json<ClippyResponse>> {
-    with_session_wrap_result(&janitor, &sandbox, |session| session.sandbox.clippy(&*req))
+) -> Result<Json<ToolOutput>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    Ok(Json(session.sandbox.clippy(&req)?))
 }
 #[rocket::post("/<sandbox>/macro-expand", data = "<req>")]
 fn api_macro_expand(
-    janitor: State<Janitor>,
+    _permit: ToolPermit,
+    owner: OwnerToken,
+    janitor: State<Arc<Janitor>>,
+    hub: State<Arc<Hub>>,
     sandbox: UuidParam,
     req: Json<MacroExpandRequest>,
-) -> Result<Json<MacroExpandResponse>> {
-    with_session_wrap_result(&janitor, &sandbox, |session| {
-        session.sandbox.macro_expand(&*req)
-    })
+) -> Result<Json<ToolOutput>> {
+    let session = get_owned_session(&janitor, &sandbox, &owner)?;
+    hub.save(&session);
+    Ok(Json(session.sandbox.macro_expand(&req)?))
 }
 
 #[rocket::get("/<sandbox>")]
 fn sandbox_get_index(
-    janitor: State<Janitor>,
+    janitor: State<Arc<Janitor>>,
     sandbox: UuidParam,
-) -> std::result::Result<NamedFile, Status> {
+) -> std::result::Result<Isolated<Content<File>>, Status> {
     sandbox_get_file(janitor, sandbox, "index.html".into())
 }
 
 #[rocket::get("/<sandbox>/<path..>")]
 fn sandbox_get_file(
-    janitor: State<Janitor>,
+    janitor: State<Arc<Janitor>>,
     sandbox: UuidParam,
     path: PathBuf,
-) -> std::result::Result<NamedFile, Status> {
+) -> std::result::Result<Isolated<Content<File>>, Status> {
     let session = get_session(&janitor, &sandbox).map_err(|_| Status::BadRequest)?;
 
-    session
-        .sandbox
-        .get_serve_path(&path)
-        .ok()
-        .and_then(|path| NamedFile::open(path).ok())
-        .ok_or(Status::NotFound)
+    let (path, mut file) = session.sandbox.open_serve_file(&path).map_err(|err| {
+        tracing::debug!("unable to open served file: {}", err);
+        Status::NotFound
+    })?;
+
+    let content_type = content_type_for_file(&path, &mut file);
+    Ok(Isolated(Isolation::Preview, Content(content_type, file)))
+}
+
+/// Liveness probe.
+#[rocket::get("/healthz")]
+fn healthz() -> &'static str {
+    "ok\n"
+}
+
+/// Readiness probe.
+/// Fails while shutting down or if the tools can't be run.
+#[rocket::get("/readyz")]
+fn readyz(janitor: State<Arc<Janitor>>, context: State<Arc<Context>>) -> status::Custom<String> {
+    if janitor.is_shutting_down() {
+        return status::Custom(Status::ServiceUnavailable, "shutting down\n".to_owned());
+    }
+
+    let failures = health::check_readiness(&context);
+    if failures.is_empty() {
+        status::Custom(Status::Ok, "ok\n".to_owned())
+    } else {
+        status::Custom(Status::ServiceUnavailable, failures.join("\n") + "\n")
+    }
+}
+
+/// Stop accepting jobs, drop all sessions and kill the running invocations.
+fn shut_down(janitor: &Janitor, context: &Context) {
+    tracing::info!("shutting down");
+    janitor.shut_down();
+    if let Err(err) = context.kill_invocations(|_| false) {
+        tracing::error!("{}", err);
+    }
+}
+
+fn spawn_signal_handler(janitor: Arc<Janitor>, context: Arc<Context>) -> io::Result<()> {
+    let signals = Signals::new([signal_hook::SIGTERM, signal_hook::SIGINT])?;
+    thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            shut_down(&janitor, &context);
+            process::exit(0);
+        }
+    });
+    Ok(())
 }
 
 fn main() {
+    trace::init_logging();
+    let janitor = Arc::new(Janitor::default());
+    let signal_janitor = Arc::clone(&janitor);
+
     rocket::ignite()
-        .manage(Janitor::default())
+        .attach(trace::RequestTracing)
+        .attach(AdHoc::on_attach("Config", move |rocket| {
+            let setup = Config::from_rocket(rocket.config()).and_then(|config| {
+                let context = config.create_context()?;
+                Ok((config, context))
+            });
+            let (config, context) = match setup {
+                Ok(setup) => setup,
+                Err(err) => {
+                    tracing::error!("{}", err);
+                    return Err(rocket);
+                }
+            };
+
+            let failures = health::check_readiness(&context);
+            if !failures.is_empty() {
+                for failure in failures {
+                    tracing::error!("readiness check failed: {}", failure);
+                }
+                return Err(rocket);
+            }
+
+            // clean up after a previous run which didn't shut down properly
+            if let Err(err) = context.reconcile(|id| signal_janitor.is_known_session(id)) {
+                tracing::error!("failed to reconcile sandboxes: {}", err);
+            }
+            if let Err(err) =
+                spawn_signal_handler(Arc::clone(&signal_janitor), Arc::clone(&context))
+            {
+                tracing::error!("failed to register signal handler: {}", err);
+                return Err(rocket);
+            }
+
+            let hub = Arc::new(Hub::new(config.collab.max_participants));
+            let limiter = Arc::new(RateLimiter::new(config.rate_limit));
+            if let Err(err) = collab::spawn_server(
+                &config.collab,
+                Arc::clone(&hub),
+                Arc::clone(&signal_janitor),
+                Arc::clone(&limiter),
+            ) {
+                tracing::error!("failed to start the collaboration server: {}", err);
+                return Err(rocket);
+            }
+            if let Some(address) = config.metrics.address {
+                if let Err(err) = metrics::spawn_server(
+                    address,
+                    Arc::clone(&signal_janitor),
+                    Arc::clone(&context),
+                ) {
+                    tracing::error!("failed to start the metrics server: {}", err);
+                    return Err(rocket);
+                }
+            }
+
+            Ok(rocket
+                .manage(context)
+                .manage(hub)
+                .manage(limiter)
+                .manage(config.admin))
+        }))
+        .manage(janitor)
         .mount(
             "/api",
             rocket::routes![
+                api_version,
+                api_openapi,
                 api_create_sandbox,
                 api_get_tool_versions,
                 api_get_structure,
                 api_get_file,
                 api_upload_file,
                 api_compile,
+                api_check,
+                api_emit,
+                api_size,
                 api_format,
                 api_clippy,
                 api_macro_expand,
             ],
         )
+        .mount("/api/admin", admin::routes())
+        .register(rocket::catchers![
+            bad_request,
+            unprocessable_entity,
+            too_many_requests,
+            internal_server_error,
+        ])
         .mount(
             "/proxy",
             rocket::routes![sandbox_get_index, sandbox_get_file],
         )
+        .mount("/", rocket::routes![healthz, readyz])
         // TODO make static location configurable
-        .mount("/", SPAStaticFiles::new("www"))
+        .mount("/", SPAStaticFiles::new(health::STATIC_DIR))
         .launch();
 }
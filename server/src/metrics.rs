@@ -0,0 +1,184 @@
+//! Prometheus metrics.
+//!
+//! The metrics are served on a separate address so that they aren't exposed
+//! to the public together with the API.
+
+use crate::{
+    janitor::Janitor,
+    sandbox::{Context, DURATION_BUCKETS},
+};
+use serde::Deserialize;
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// Time a client has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Address the metrics are served on.
+    /// The metrics are disabled if this isn't set.
+    pub address: Option<SocketAddr>,
+}
+
+/// Start serving the metrics in the background.
+pub fn spawn_server(
+    address: SocketAddr,
+    janitor: Arc<Janitor>,
+    context: Arc<Context>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    tracing::info!("serving metrics on {}", address);
+
+    thread::Builder::new()
+        .name(String::from("metrics"))
+        .spawn(move || {
+            // scrapes are rare, so they're answered one at a time
+            for stream in listener.incoming() {
+                let result =
+                    stream.and_then(|stream| handle_connection(stream, &janitor, &context));
+                if let Err(err) = result {
+                    tracing::debug!("metrics request failed: {}", err);
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, janitor: &Janitor, context: &Context) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the headers aren't needed but have to be read before responding
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(janitor, context)),
+        _ => ("404 Not Found", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+         {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Write the header of a metric.
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Render the metrics in the Prometheus text format.
+fn render(janitor: &Janitor, context: &Context) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "playground_sessions",
+        "gauge",
+        "Number of live sessions.",
+    );
+    let _ = writeln!(out, "playground_sessions {}", janitor.session_count());
+
+    let stats = context.stats();
+    header(
+        &mut out,
+        "playground_invocations_running",
+        "gauge",
+        "Number of tool invocations which are currently running.",
+    );
+    let _ = writeln!(out, "playground_invocations_running {}", stats.running());
+
+    header(
+        &mut out,
+        "playground_invocations_queued",
+        "gauge",
+        "Number of tool invocations which are waiting to run.",
+    );
+    let _ = writeln!(out, "playground_invocations_queued {}", stats.queued());
+
+    let tools = stats.tools();
+    header(
+        &mut out,
+        "playground_invocations_total",
+        "counter",
+        "Number of tool invocations by tool and outcome.",
+    );
+    for (tool, stats) in &tools {
+        for (outcome, count) in &stats.outcomes {
+            let _ = writeln!(
+                out,
+                "playground_invocations_total{{tool=\"{}\",outcome=\"{}\"}} {}",
+                tool,
+                outcome.as_str(),
+                count
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "playground_invocation_duration_seconds",
+        "histogram",
+        "Duration of the tool invocations.",
+    );
+    for (tool, stats) in &tools {
+        let durations = &stats.durations;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&durations.buckets) {
+            let _ = writeln!(
+                out,
+                "playground_invocation_duration_seconds_bucket{{tool=\"{}\",le=\"{}\"}} {}",
+                tool, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "playground_invocation_duration_seconds_bucket{{tool=\"{}\",le=\"+Inf\"}} {}",
+            tool, durations.count
+        );
+        let _ = writeln!(
+            out,
+            "playground_invocation_duration_seconds_sum{{tool=\"{}\"}} {}",
+            tool, durations.sum
+        );
+        let _ = writeln!(
+            out,
+            "playground_invocation_duration_seconds_count{{tool=\"{}\"}} {}",
+            tool, durations.count
+        );
+    }
+
+    match context.disk_usage() {
+        Ok(bytes) => {
+            header(
+                &mut out,
+                "playground_sandbox_disk_bytes",
+                "gauge",
+                "Number of bytes stored by all sandboxes.",
+            );
+            let _ = writeln!(out, "playground_sandbox_disk_bytes {}", bytes);
+        }
+        Err(err) => tracing::warn!("unable to measure the disk usage: {}", err),
+    }
+
+    out
+}
@@ -0,0 +1,259 @@
+//! OpenAPI description of the `/api` routes generated from the protocol types.
+
+use protocol::{
+    ApiVersion,
+    AuditEntry,
+    CheckRequest,
+    CheckResponse,
+    ClippyRequest,
+    CompileRequest,
+    CompileResponse,
+    EmitRequest,
+    EmitResponse,
+    Error,
+    FormatRequest,
+    JobDetails,
+    MacroExpandRequest,
+    SandboxStructure,
+    SessionDetails,
+    SessionStatus,
+    SizeRequest,
+    SizeResponse,
+    ToolOutput,
+    ToolVersions,
+    PROTOCOL_VERSION,
+};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+/// Version of the OpenAPI specification the document follows.
+const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Token an operation requires.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Auth {
+    Public,
+    Owner,
+    Admin,
+}
+impl Auth {
+    fn security(self) -> Option<Value> {
+        match self {
+            Self::Public => None,
+            Self::Owner => Some(json!([{ "ownerToken": [] }])),
+            Self::Admin => Some(json!([{ "adminToken": [] }])),
+        }
+    }
+}
+
+struct Builder {
+    generator: SchemaGenerator,
+    paths: Map<String, Value>,
+}
+impl Builder {
+    fn new() -> Self {
+        Self {
+            generator: SchemaSettings::openapi3().into_generator(),
+            paths: Map::new(),
+        }
+    }
+
+    fn schema<T: JsonSchema>(&mut self) -> Value {
+        let schema = self.generator.subschema_for::<T>();
+        serde_json::to_value(schema).expect("schemas are always valid JSON")
+    }
+
+    /// Add an operation whose successful response is `response`.
+    /// Every path parameter is a string.
+    fn operation(
+        &mut self,
+        method: &str,
+        path: &str,
+        summary: &str,
+        auth: Auth,
+        request: Option<Value>,
+        response: Value,
+    ) {
+        let parameters: Vec<Value> = path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        let error = self.schema::<Error>();
+
+        let mut operation = json!({
+            "summary": summary,
+            "parameters": parameters,
+            "responses": {
+                "default": {
+                    "description": "Error, see the `type` for details",
+                    "content": { "application/json": { "schema": error } },
+                },
+            },
+        });
+        operation["responses"]
+            .as_object_mut()
+            .expect("responses is an object")
+            .extend(response.as_object().cloned().unwrap_or_default());
+        if let Some(request) = request {
+            operation["requestBody"] = request;
+        }
+        if let Some(security) = auth.security() {
+            operation["security"] = security;
+        }
+
+        self.paths
+            .entry(path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .expect("paths are objects")
+            .insert(method.to_owned(), operation);
+    }
+
+    /// Add an operation which sends and receives JSON.
+    fn json_operation<Resp: JsonSchema>(
+        &mut self,
+        method: &str,
+        path: &str,
+        summary: &str,
+        auth: Auth,
+        request: Option<Value>,
+    ) {
+        let request = request.map(|schema| {
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            })
+        });
+        let response = self.schema::<Resp>();
+        let response = json!({
+            "200": {
+                "description": "Success",
+                "content": { "application/json": { "schema": response } },
+            },
+        });
+        self.operation(method, path, summary, auth, request, response);
+    }
+
+    fn post<Req: JsonSchema, Resp: JsonSchema>(&mut self, path: &str, summary: &str, auth: Auth) {
+        let request = self.schema::<Req>();
+        self.json_operation::<Resp>("post", path, summary, auth, Some(request));
+    }
+
+    fn get<Resp: JsonSchema>(&mut self, path: &str, summary: &str, auth: Auth) {
+        self.json_operation::<Resp>("get", path, summary, auth, None);
+    }
+
+    fn build(self) -> Value {
+        let schemas = serde_json::to_value(self.generator.definitions())
+            .expect("schemas are always valid JSON");
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": {
+                "title": "Yew Playground",
+                "version": PROTOCOL_VERSION,
+            },
+            "servers": [{ "url": "/api" }],
+            "paths": self.paths,
+            "components": {
+                "schemas": schemas,
+                "securitySchemes": {
+                    "ownerToken": {
+                        "type": "http",
+                        "scheme": "bearer",
+                        "description": "Owner token returned when the session is created",
+                    },
+                    "adminToken": {
+                        "type": "http",
+                        "scheme": "bearer",
+                        "description": "Token configured as `admin.token`",
+                    },
+                },
+            },
+        })
+    }
+}
+
+/// Generate the OpenAPI document describing the API.
+pub fn document() -> Value {
+    use Auth::*;
+
+    let mut builder = Builder::new();
+    builder.get::<ApiVersion>("/version", "Protocol version and capabilities", Public);
+    builder.operation(
+        "get",
+        "/openapi.json",
+        "This document",
+        Public,
+        None,
+        json!({ "200": { "description": "OpenAPI document" } }),
+    );
+    builder.json_operation::<SessionDetails>("post", "/sandbox", "Create a session", Public, None);
+    builder.get::<ToolVersions>("/{sandbox}/tools", "Versions of the tools", Public);
+    builder.get::<SandboxStructure>("/{sandbox}/files", "Files of the session", Public);
+    builder.operation(
+        "get",
+        "/{sandbox}/files/{path}",
+        "Content of a file",
+        Public,
+        None,
+        json!({
+            "200": {
+                "description": "Content of the file with its entity tag in the `ETag` header",
+                "content": { "*/*": { "schema": { "type": "string", "format": "binary" } } },
+            },
+        }),
+    );
+    builder.operation(
+        "put",
+        "/{sandbox}/files/{path}",
+        "Write a file. Requires either `If-Match` or `If-None-Match: *`",
+        Owner,
+        Some(json!({
+            "required": true,
+            "content": { "*/*": { "schema": { "type": "string", "format": "binary" } } },
+        })),
+        json!({
+            "200": { "description": "Entity tag of the new content in the `ETag` header" },
+        }),
+    );
+    builder.post::<CompileRequest, CompileResponse>("/{sandbox}/compile", "Build the crate", Owner);
+    builder.post::<CheckRequest, CheckResponse>("/{sandbox}/check", "Check the code", Owner);
+    builder.post::<EmitRequest, EmitResponse>("/{sandbox}/emit", "Emit compiler output", Owner);
+    builder.post::<SizeRequest, SizeResponse>("/{sandbox}/size", "Analyse the binary size", Owner);
+    builder.post::<FormatRequest, ToolOutput>("/{sandbox}/format", "Format the code", Owner);
+    builder.post::<ClippyRequest, ToolOutput>("/{sandbox}/clippy", "Run clippy", Owner);
+    builder.post::<MacroExpandRequest, ToolOutput>(
+        "/{sandbox}/macro-expand",
+        "Expand the macros",
+        Owner,
+    );
+
+    builder.get::<Vec<SessionStatus>>("/admin/sessions", "List the sessions", Admin);
+    builder.operation(
+        "delete",
+        "/admin/sessions/{id}",
+        "Remove a session and kill its running jobs",
+        Admin,
+        None,
+        json!({ "204": { "description": "The session was removed" } }),
+    );
+    builder.get::<Vec<AuditEntry>>(
+        "/admin/sessions/{id}/audit",
+        "Audit log of a session",
+        Admin,
+    );
+    builder.get::<Vec<JobDetails>>("/admin/jobs", "List the running jobs", Admin);
+
+    builder.build()
+}
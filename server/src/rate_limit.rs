@@ -0,0 +1,320 @@
+//! Per-client rate limiting using token buckets.
+
+use rocket::{
+    http::Status,
+    request::{self, FromRequest, Request},
+    Outcome,
+    State,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Number of buckets after which the full ones are dropped.
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Parameters of a token bucket.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Limit {
+    /// Number of requests which can be made at once.
+    pub burst: u32,
+    /// Number of requests which become available again per minute.
+    /// Values below 1 are treated as 1.
+    pub per_minute: u32,
+}
+impl Limit {
+    fn capacity(self) -> f64 {
+        f64::from(self.burst)
+    }
+
+    /// Tokens added per second.
+    fn rate(self) -> f64 {
+        f64::from(self.per_minute.max(1)) / 60.0
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Addresses of reverse proxies whose `X-Forwarded-For` header is trusted.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Limit for creating sessions.
+    pub sessions: Limit,
+    /// Limit for running tools (ex. compiling).
+    pub tools: Limit,
+    /// Limit for opening collaboration connections.
+    pub connections: Limit,
+}
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            trusted_proxies: Vec::new(),
+            sessions: Limit {
+                burst: 5,
+                per_minute: 2,
+            },
+            tools: Limit {
+                burst: 20,
+                per_minute: 60,
+            },
+            connections: Limit {
+                burst: 10,
+                per_minute: 30,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LimitKind {
+    Sessions,
+    Tools,
+    Connections,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+impl Bucket {
+    fn full(limit: Limit, now: Instant) -> Self {
+        Self {
+            tokens: limit.capacity(),
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate()).min(limit.capacity());
+        self.updated = now;
+    }
+}
+
+/// Keeps track of the requests made by every client.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(LimitKind, IpAddr), Bucket>>,
+}
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::default(),
+        }
+    }
+
+    fn limit(&self, kind: LimitKind) -> Limit {
+        match kind {
+            LimitKind::Sessions => self.config.sessions,
+            LimitKind::Tools => self.config.tools,
+            LimitKind::Connections => self.config.connections,
+        }
+    }
+
+    /// Take a token from the client's bucket.
+    /// If there are none left the time until the next one becomes available
+    /// is returned.
+    pub fn acquire(&self, kind: LimitKind, client: IpAddr) -> Result<(), Duration> {
+        self.acquire_at(kind, client, Instant::now())
+    }
+
+    fn acquire_at(&self, kind: LimitKind, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // full buckets are indistinguishable from new ones
+            buckets.retain(|(kind, _), bucket| {
+                let limit = self.limit(*kind);
+                bucket.refill(limit, now);
+                bucket.tokens < limit.capacity()
+            });
+        }
+
+        let limit = self.limit(kind);
+        let bucket = buckets
+            .entry((kind, client))
+            .or_insert_with(|| Bucket::full(limit, now));
+        bucket.refill(limit, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / limit.rate()))
+        }
+    }
+
+    pub fn is_trusted_proxy(&self, addr: &IpAddr) -> bool {
+        self.config.trusted_proxies.contains(addr)
+    }
+
+    /// Determine the address of the client.
+    /// `forwarded_for` contains the entries of the `X-Forwarded-For` headers,
+    /// which are only used if the request comes from a trusted proxy.
+    /// The entries are walked from the right and the first one which isn't a
+    /// trusted proxy is the client.
+    pub fn client_addr<'a>(
+        &self,
+        remote: IpAddr,
+        forwarded_for: impl DoubleEndedIterator<Item = &'a str>,
+    ) -> IpAddr {
+        let mut client = remote;
+        for entry in forwarded_for.rev() {
+            if !self.is_trusted_proxy(&client) {
+                break;
+            }
+            match entry.trim().parse() {
+                Ok(addr) => client = addr,
+                Err(_) => {
+                    tracing::debug!("invalid X-Forwarded-For entry: {:?}", entry);
+                    break;
+                }
+            }
+        }
+
+        client
+    }
+}
+
+/// Whole seconds a client has to wait, rounded up so it doesn't retry too
+/// early.
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)
+}
+
+/// Time a rate limited client has to wait.
+/// Stored in the request's local cache so the catcher can report it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryAfter(pub Option<Duration>);
+
+fn acquire_for_request(request: &Request, kind: LimitKind) -> request::Outcome<(), Duration> {
+    let limiter = match request.guard::<State<Arc<RateLimiter>>>() {
+        Outcome::Success(limiter) => limiter,
+        _ => {
+            tracing::error!("rate limiter isn't managed");
+            return Outcome::Failure((Status::InternalServerError, Duration::default()));
+        }
+    };
+    let remote = match request.remote() {
+        Some(remote) => remote.ip(),
+        // only happens for local requests
+        None => return Outcome::Success(()),
+    };
+
+    let forwarded_for: Vec<&str> = request
+        .headers()
+        .get("X-Forwarded-For")
+        .flat_map(|value| value.split(','))
+        .collect();
+    let client = limiter.client_addr(remote, forwarded_for.into_iter());
+
+    match limiter.acquire(kind, client) {
+        Ok(()) => Outcome::Success(()),
+        Err(retry_after) => {
+            tracing::debug!("rate limited {} for {:?}", client, kind);
+            request.local_cache(|| RetryAfter(Some(retry_after)));
+            Outcome::Failure((Status::TooManyRequests, retry_after))
+        }
+    }
+}
+
+/// Request guard which counts towards `LimitKind::Sessions`.
+#[derive(Debug)]
+pub struct SessionPermit(());
+impl<'a, 'r> FromRequest<'a, 'r> for SessionPermit {
+    type Error = Duration;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        acquire_for_request(request, LimitKind::Sessions).map(Self)
+    }
+}
+
+/// Request guard which counts towards `LimitKind::Tools`.
+#[derive(Debug)]
+pub struct ToolPermit(());
+impl<'a, 'r> FromRequest<'a, 'r> for ToolPermit {
+    type Error = Duration;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        acquire_for_request(request, LimitKind::Tools).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(trusted_proxies: Vec<IpAddr>) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            trusted_proxies,
+            sessions: Limit {
+                burst: 2,
+                per_minute: 60,
+            },
+            ..RateLimitConfig::default()
+        })
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let limiter = limiter(Vec::new());
+        let client = ip("192.0.2.1");
+        let now = Instant::now();
+
+        assert!(limiter.acquire_at(LimitKind::Sessions, client, now).is_ok());
+        assert!(limiter.acquire_at(LimitKind::Sessions, client, now).is_ok());
+        let retry_after = limiter
+            .acquire_at(LimitKind::Sessions, client, now)
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+
+        // other clients and limits are independent
+        assert!(limiter
+            .acquire_at(LimitKind::Sessions, ip("192.0.2.2"), now)
+            .is_ok());
+        assert!(limiter.acquire_at(LimitKind::Tools, client, now).is_ok());
+
+        let later = now + Duration::from_secs(1);
+        assert!(limiter
+            .acquire_at(LimitKind::Sessions, client, later)
+            .is_ok());
+        assert!(limiter
+            .acquire_at(LimitKind::Sessions, client, later)
+            .is_err());
+    }
+
+    #[test]
+    fn forwarded_for_is_only_trusted_from_proxies() {
+        let proxy = ip("10.0.0.1");
+        let limiter = limiter(vec![proxy]);
+        let header = ["198.51.100.7", "192.0.2.1"];
+
+        assert_eq!(
+            limiter.client_addr(ip("192.0.2.9"), header.iter().copied()),
+            ip("192.0.2.9")
+        );
+        assert_eq!(
+            limiter.client_addr(proxy, header.iter().copied()),
+            ip("192.0.2.1")
+        );
+        // chained trusted proxies are skipped
+        assert_eq!(
+            limiter.client_addr(proxy, ["192.0.2.1", "10.0.0.1"].iter().copied()),
+            ip("192.0.2.1")
+        );
+        assert_eq!(
+            limiter.client_addr(proxy, ["garbage"].iter().copied()),
+            proxy
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Append-only log of the tool invocations of a sandbox.
+
+use super::{Error, Result};
+use protocol::AuditEntry;
+use serde::Deserialize;
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Length of the hash of the "src" directory in bytes.
+const HASH_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Directory the audit log of a session is copied to when the session
+    /// expires.
+    /// The logs are discarded with the session if this isn't set.
+    pub export_dir: Option<PathBuf>,
+}
+
+/// Audit log stored as one JSON object per line.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    /// Serializes appends so lines can't interleave.
+    lock: Mutex<()>,
+}
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::default(),
+        }
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry).map_err(Error::UnableToParseAuditLog)?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().expect("audit log lock poisoned");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(Error::UnableToWriteFile)?;
+        file.write_all(&line).map_err(Error::UnableToWriteFile)
+    }
+
+    /// Read all entries, oldest first.
+    pub fn read(&self) -> Result<Vec<AuditEntry>> {
+        let _guard = self.lock.lock().expect("audit log lock poisoned");
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(Error::UnableToReadFile(err)),
+        };
+
+        BufReader::new(file)
+            .lines()
+            .map(|line| {
+                let line = line.map_err(Error::UnableToReadFile)?;
+                serde_json::from_str(&line).map_err(Error::UnableToParseAuditLog)
+            })
+            .collect()
+    }
+
+    /// Copy the log to `path`.
+    /// Nothing is written if no invocation was recorded.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let _guard = self.lock.lock().expect("audit log lock poisoned");
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(Error::UnableToWriteFile)?;
+        }
+        fs::copy(&self.path, path).map_err(Error::UnableToWriteFile)?;
+        Ok(())
+    }
+}
+
+/// Hash the paths and contents of all files in the directory.
+/// The result is the same for directories with the same files, regardless of
+/// the order in which they're listed.
+pub fn hash_dir(dir: &Path) -> io::Result<String> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let mut state = blake2b_simd::Params::new()
+        .hash_length(HASH_LENGTH)
+        .to_state();
+    for path in files {
+        let rel = path.strip_prefix(dir).unwrap_or(&path);
+        let content = fs::read(&path)?;
+        // lengths are included so that no two trees produce the same input
+        state.update(&(rel.as_os_str().len() as u64).to_le_bytes());
+        state.update(rel.as_os_str().as_bytes());
+        state.update(&(content.len() as u64).to_le_bytes());
+        state.update(&content);
+    }
+
+    Ok(state.finalize().to_hex().to_string())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
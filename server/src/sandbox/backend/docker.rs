@@ -0,0 +1,427 @@
+use super::{
+    run_with_timeout,
+    Backend,
+    Execution,
+    Invocation,
+    ResourceUsage,
+    Tool,
+    PROCESS_TIMEOUT_SOFT,
+};
+use crate::sandbox::{helpers, set_permissions_open, Error, Result};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    time::Duration,
+};
+use tempdir::TempDir;
+use uuid::Uuid;
+
+/// Directory of the crate inside the containers.
+const CONTAINER_WORKDIR: &str = "/playground";
+/// Prefix of the names of all containers created by the server.
+const CONTAINER_NAME_PREFIX: &str = "playground";
+/// Container name segment used for invocations without a session or request.
+const NONE_SEGMENT: &str = "none";
+/// Label containing the instance which created the container.
+const INSTANCE_LABEL: &str = "rs.yew.playground.instance";
+/// Label containing the session the container belongs to.
+const SESSION_LABEL: &str = "rs.yew.playground.session";
+/// Label containing the request which started the container.
+const REQUEST_LABEL: &str = "rs.yew.playground.request";
+/// Label containing the image name without prefix.
+const TOOL_LABEL: &str = "rs.yew.playground.tool";
+/// Directory inside the containers to which the entrypoint reports the
+/// resources used by the tool.
+const CONTAINER_USAGE_DIR: &str = "/playground/.usage";
+const USAGE_FILE_NAME: &str = "usage";
+/// Line the entrypoint adds to the usage report if the tool timed out.
+const TIMED_OUT_MARKER: &str = "timed-out";
+
+/// Configuration for the docker backend.
+/// This also applies to compatible runtimes like Podman.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DockerConfig {
+    /// Binary of the container runtime.
+    pub runtime: String,
+    /// Prefix added to the names of the images.
+    pub image_prefix: String,
+    /// Images to use instead of the prefixed default name.
+    /// The keys are the names of the images without prefix (ex.
+    /// `compiler-stable`).
+    pub images: BTreeMap<String, String>,
+    /// Name of this server instance.
+    /// Used to tell apart the containers of servers sharing a runtime.
+    pub instance: String,
+    pub cap_add: Vec<String>,
+    pub security_opt: Vec<String>,
+    /// Seccomp profile applied to the containers.
+    /// Relative paths are resolved against the working directory of the
+    /// server. `None` uses the default profile of the runtime.
+    pub seccomp_profile: Option<PathBuf>,
+    /// Mount the root filesystem of the container as read-only.
+    pub read_only: bool,
+    /// Values for `--tmpfs` (ex. `/tmp:size=64m`).
+    pub tmpfs: Vec<String>,
+    /// Paths in the image which need to be writable even if the root
+    /// filesystem is read-only.
+    /// Each of them gets an anonymous volume which is initialized with the
+    /// content of the image and removed together with the container.
+    pub writable_paths: Vec<String>,
+    pub cpus: String,
+    pub memory: String,
+    pub memory_swap: String,
+    pub pids_limit: u32,
+    /// Additional arguments passed to `run` (ex. `--userns=keep-id`).
+    pub extra_args: Vec<String>,
+}
+impl Default for DockerConfig {
+    fn default() -> Self {
+        Self {
+            runtime: "docker".to_owned(),
+            image_prefix: "yewstack/playground_".to_owned(),
+            images: BTreeMap::new(),
+            instance: "default".to_owned(),
+            cap_add: Vec::new(),
+            security_opt: vec!["no-new-privileges".to_owned()],
+            seccomp_profile: Some(PathBuf::from("docker/seccomp.json")),
+            read_only: true,
+            tmpfs: vec!["/tmp:rw,exec,nosuid,size=64m".to_owned()],
+            writable_paths: vec!["/playground/target".to_owned()],
+            cpus: "1".to_owned(),
+            memory: "256m".to_owned(),
+            memory_swap: "320m".to_owned(),
+            pids_limit: 512,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+fn image_name_for_tool(tool: Tool) -> &'static str {
+    use Tool::*;
+
+    match tool {
+        Compiler(channel) => helpers::container_name_for_channel(channel),
+        Rustfmt => "rustfmt",
+        Clippy => "clippy",
+        CargoExpand => "cargo-expand",
+    }
+}
+
+fn check_status(output: &Output) -> Result<()> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(Error::UnableToKillInvocations(io::Error::new(
+            io::ErrorKind::Other,
+            stderr.trim().to_owned(),
+        )))
+    }
+}
+
+/// Parse the resource usage reported by the entrypoint.
+/// The first line of the report contains the user and system CPU time in
+/// seconds and the peak resident memory in kibibytes.
+fn parse_usage(report: &str) -> Option<ResourceUsage> {
+    let mut fields = report.lines().next()?.split_whitespace();
+    let mut next_seconds = || {
+        fields
+            .next()?
+            .parse::<f64>()
+            .ok()
+            .filter(|secs| secs.is_finite() && *secs >= 0.0)
+            .map(Duration::from_secs_f64)
+    };
+    let cpu_time = next_seconds()? + next_seconds()?;
+    let peak_memory = fields.next()?.parse::<u64>().ok()? * 1024;
+
+    Some(ResourceUsage {
+        cpu_time: Some(cpu_time),
+        peak_memory: Some(peak_memory),
+    })
+}
+
+/// Check whether the report says that the entrypoint stopped the tool because
+/// it exceeded the soft timeout.
+fn stopped_by_environment(report: &str) -> bool {
+    report.lines().any(|line| line.trim() == TIMED_OUT_MARKER)
+}
+
+fn mount_volume(cmd: &mut Command, host_path: &Path, container_path: &str, read_only: bool) {
+    let mut volume = OsString::from(host_path);
+    volume.push(":");
+    volume.push(container_path);
+    if read_only {
+        volume.push(":ro");
+    }
+
+    cmd.arg("--volume").arg(volume);
+}
+
+/// Runs every invocation in a new container of the tool's image.
+#[derive(Debug, Default)]
+pub struct DockerBackend {
+    config: DockerConfig,
+}
+impl DockerBackend {
+    pub fn new(mut config: DockerConfig) -> Result<Self> {
+        // a missing profile would only be noticed by the first invocation
+        if let Some(profile) = &mut config.seccomp_profile {
+            *profile = profile
+                .canonicalize()
+                .map_err(|err| Error::UnableToReadSeccompProfile(profile.clone(), err))?;
+        }
+        Ok(Self { config })
+    }
+
+    fn image_for_tool(&self, tool: Tool) -> String {
+        let name = image_name_for_tool(tool);
+        self.config
+            .images
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| format!("{}{}", self.config.image_prefix, name))
+    }
+
+    /// Prefix of the names of all containers created by this instance.
+    fn container_name_prefix(&self) -> String {
+        format!("{}-{}-", CONTAINER_NAME_PREFIX, self.config.instance)
+    }
+
+    /// Get the session from the name of a container.
+    /// Names have the form `playground-{instance}-{session}-{request}-{id}`.
+    fn session_from_container_name(&self, name: &str) -> Option<Uuid> {
+        let rest = name.strip_prefix(&self.container_name_prefix())?;
+        let session = rest.split('-').next()?;
+        Uuid::parse_str(session).ok()
+    }
+
+    /// Create a unique name for the container of the invocation.
+    fn container_name(&self, invocation: &Invocation) -> String {
+        let session = invocation.session.map_or_else(
+            || NONE_SEGMENT.to_owned(),
+            |session| session.to_simple().to_string(),
+        );
+        let request_id = invocation.request_id.as_deref().unwrap_or(NONE_SEGMENT);
+        // a request can start multiple containers
+        format!(
+            "{}{}-{}-{}",
+            self.container_name_prefix(),
+            session,
+            request_id,
+            &Uuid::new_v4().to_simple().to_string()[..8]
+        )
+    }
+
+    fn docker_run(&self, invocation: &Invocation, container_name: &str) -> Command {
+        let config = &self.config;
+        let session = invocation.session.map_or_else(
+            || NONE_SEGMENT.to_owned(),
+            |session| session.to_simple().to_string(),
+        );
+        let request_id = invocation.request_id.as_deref().unwrap_or(NONE_SEGMENT);
+
+        let mut cmd = Command::new(&config.runtime);
+        cmd.arg("run")
+            .arg("--rm")
+            .arg("--tty")
+            .args(["--name", container_name])
+            .args([
+                "--label",
+                &format!("{}={}", INSTANCE_LABEL, config.instance),
+            ])
+            .args(["--label", &format!("{}={}", SESSION_LABEL, session)])
+            .args(["--label", &format!("{}={}", REQUEST_LABEL, request_id)])
+            .args([
+                "--label",
+                &format!("{}={}", TOOL_LABEL, image_name_for_tool(invocation.tool)),
+            ])
+            .arg("--cap-drop=ALL");
+        for cap in &config.cap_add {
+            cmd.arg(format!("--cap-add={}", cap));
+        }
+        for opt in &config.security_opt {
+            cmd.arg(format!("--security-opt={}", opt));
+        }
+        if let Some(profile) = &config.seccomp_profile {
+            let mut opt = OsString::from("--security-opt=seccomp=");
+            opt.push(profile);
+            cmd.arg(opt);
+        }
+        if config.read_only {
+            cmd.arg("--read-only");
+        }
+        for tmpfs in &config.tmpfs {
+            cmd.args(["--tmpfs", tmpfs]);
+        }
+        for path in &config.writable_paths {
+            cmd.args(["--volume", path]);
+        }
+        cmd.args(["--workdir", CONTAINER_WORKDIR])
+            .args(["--net", "none"])
+            .args(["--cpus", &config.cpus])
+            .args(["--memory", &config.memory])
+            .args(["--memory-swap", &config.memory_swap])
+            .args([
+                "--env",
+                &format!("PLAYGROUND_TIMEOUT={}", PROCESS_TIMEOUT_SOFT.as_secs()),
+            ])
+            .args(["--pids-limit", &config.pids_limit.to_string()])
+            .args(&config.extra_args);
+
+        cmd
+    }
+
+    fn remove_containers(&self, names: &[&str]) -> Result<()> {
+        let output = Command::new(&self.config.runtime)
+            .args(["rm", "--force"])
+            .args(names)
+            .output()
+            .map_err(Error::UnableToKillInvocations)?;
+        check_status(&output)
+    }
+
+    /// `usage_dir` receives the resource usage reported by the entrypoint.
+    fn command(&self, invocation: &Invocation, container_name: &str, usage_dir: &Path) -> Command {
+        let mut cmd = self.docker_run(invocation, container_name);
+        for mount in &invocation.mounts {
+            let container_path = format!("{}/{}", CONTAINER_WORKDIR, mount.name);
+            mount_volume(&mut cmd, &mount.host_path, &container_path, mount.read_only);
+        }
+        mount_volume(&mut cmd, usage_dir, CONTAINER_USAGE_DIR, false);
+        for (key, value) in &invocation.env {
+            cmd.arg("--env").arg(format!("{}={}", key, value));
+        }
+        cmd.arg("--env").arg(format!(
+            "PLAYGROUND_USAGE_FILE={}/{}",
+            CONTAINER_USAGE_DIR, USAGE_FILE_NAME
+        ));
+
+        cmd.arg(self.image_for_tool(invocation.tool))
+            .args(&invocation.args);
+
+        cmd
+    }
+}
+impl Backend for DockerBackend {
+    fn execute(&self, invocation: &Invocation) -> Result<Execution> {
+        // the usage of the runtime's client says nothing about the container
+        let usage_dir = TempDir::new("playground-usage").map_err(Error::UnableToPrepareDir)?;
+        set_permissions_open(usage_dir.path())?;
+
+        let container_name = self.container_name(invocation);
+        let cmd = self.command(invocation, &container_name, usage_dir.path());
+        tracing::debug!(command = ?cmd, "starting container");
+        let execution = run_with_timeout(cmd)?;
+        if execution.timed_out {
+            // killing the client leaves the container running
+            if let Err(err) = self.remove_containers(&[&container_name]) {
+                tracing::warn!("unable to remove container {}: {}", container_name, err);
+            }
+        }
+
+        let report = fs::read_to_string(usage_dir.path().join(USAGE_FILE_NAME));
+        let usage = match report.as_deref().map(parse_usage) {
+            Ok(Some(usage)) => usage,
+            Ok(None) => {
+                tracing::warn!(report = ?report, "invalid resource usage report");
+                ResourceUsage::default()
+            }
+            // the container didn't get as far as the tool
+            Err(_) => ResourceUsage::default(),
+        };
+        let timed_out =
+            execution.timed_out || report.is_ok_and(|report| stopped_by_environment(&report));
+        Ok(Execution {
+            output: execution.output,
+            usage,
+            timed_out,
+        })
+    }
+
+    fn kill(&self, keep: &dyn Fn(Option<&Uuid>) -> bool) -> Result<()> {
+        let output = Command::new(&self.config.runtime)
+            .args(["ps", "--all", "--format", "{{.Names}}"])
+            .arg("--filter")
+            .arg(format!("label={}={}", INSTANCE_LABEL, self.config.instance))
+            .output()
+            .map_err(Error::UnableToKillInvocations)?;
+        check_status(&output)?;
+
+        let names: Vec<&str> = std::str::from_utf8(&output.stdout)
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .filter(|name| !keep(self.session_from_container_name(name).as_ref()))
+            .collect();
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        tracing::info!("killing {} container(s)", names.len());
+        self.remove_containers(&names)
+    }
+
+    fn check_ready(&self) -> Result<()> {
+        let output = Command::new(&self.config.runtime)
+            .arg("version")
+            .output()
+            .map_err(|err| Error::RuntimeUnavailable(err.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::RuntimeUnavailable(stderr.trim().to_owned()));
+        }
+
+        let mut missing = Vec::new();
+        for &tool in Tool::ALL {
+            let image = self.image_for_tool(tool);
+            let exists = Command::new(&self.config.runtime)
+                .args(["image", "inspect", "--format", "{{.Id}}", &image])
+                .output()
+                .map_err(|err| Error::RuntimeUnavailable(err.to_string()))?
+                .status
+                .success();
+            if !exists {
+                missing.push(image);
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ImagesMissing(missing))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_report_is_parsed() {
+        let usage = parse_usage("1.52 0.25 183424\n").unwrap();
+        assert_eq!(usage.cpu_time, Some(Duration::from_millis(1770)));
+        assert_eq!(usage.peak_memory, Some(183424 * 1024));
+
+        assert_eq!(parse_usage(""), None);
+        assert_eq!(parse_usage("1.52 0.25"), None);
+        assert_eq!(parse_usage("Command exited with non-zero status 1"), None);
+        assert_eq!(parse_usage("-1.00 0.25 1024"), None);
+    }
+
+    #[test]
+    fn timeouts_are_read_from_the_report() {
+        let report = "9.87 0.12 40960\ntimed-out\n";
+        assert!(parse_usage(report).is_some());
+        assert!(stopped_by_environment(report));
+        assert!(!stopped_by_environment("0.52 0.04 40960\n"));
+    }
+}
@@ -0,0 +1,58 @@
+use super::{Backend, Execution, Invocation};
+use crate::sandbox::Result;
+use std::{
+    os::unix::process::ExitStatusExt,
+    process::{ExitStatus, Output},
+    sync::Mutex,
+};
+
+type Handler = dyn Fn(&Invocation) -> Execution + Send + Sync;
+
+/// Create an output with the given exit code.
+pub fn output(code: i32, stdout: &str, stderr: &str) -> Output {
+    Output {
+        status: ExitStatus::from_raw(code << 8),
+        stdout: stdout.as_bytes().to_vec(),
+        stderr: stderr.as_bytes().to_vec(),
+    }
+}
+
+/// Backend which records invocations instead of executing them.
+/// The output is produced by the handler, which returns either an `Output`
+/// or an `Execution`.
+pub struct FakeBackend {
+    handler: Box<Handler>,
+    invocations: Mutex<Vec<Invocation>>,
+}
+impl FakeBackend {
+    pub fn new<E: Into<Execution>>(
+        handler: impl Fn(&Invocation) -> E + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            handler: Box::new(move |invocation| handler(invocation).into()),
+            invocations: Mutex::default(),
+        }
+    }
+
+    /// Backend where every invocation succeeds without output.
+    pub fn succeeding() -> Self {
+        Self::new(|_| output(0, "", ""))
+    }
+
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.invocations.lock().unwrap().clone()
+    }
+}
+impl std::fmt::Debug for FakeBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FakeBackend")
+            .field("invocations", &self.invocations)
+            .finish()
+    }
+}
+impl Backend for FakeBackend {
+    fn execute(&self, invocation: &Invocation) -> Result<Execution> {
+        self.invocations.lock().unwrap().push(invocation.clone());
+        Ok((self.handler)(invocation))
+    }
+}
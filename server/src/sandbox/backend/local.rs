@@ -0,0 +1,107 @@
+use super::{run_with_timeout, Backend, Execution, Invocation, Tool};
+use crate::sandbox::{helpers, Error, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::symlink,
+    path::PathBuf,
+    process::Command,
+};
+use tempdir::TempDir;
+
+/// Runs the tools installed on the host directly.
+///
+/// Every invocation gets a temporary crate with a copy of the playground
+/// manifest and the mounts symlinked into it. The target directory is shared
+/// so that dependencies are only compiled once.
+///
+/// This provides no isolation whatsoever and must only be used for trusted
+/// development.
+#[derive(Debug)]
+pub struct LocalBackend {
+    manifest_path: PathBuf,
+    target_dir: PathBuf,
+}
+impl LocalBackend {
+    pub fn new(manifest_path: PathBuf, target_dir: PathBuf) -> Result<Self> {
+        // the tools run in a different working directory
+        let manifest_path = manifest_path
+            .canonicalize()
+            .map_err(Error::UnableToPrepareDir)?;
+        fs::create_dir_all(&target_dir).map_err(Error::UnableToPrepareDir)?;
+        let target_dir = target_dir
+            .canonicalize()
+            .map_err(Error::UnableToPrepareDir)?;
+
+        Ok(Self {
+            manifest_path,
+            target_dir,
+        })
+    }
+
+    fn prepare_workdir(&self, invocation: &Invocation) -> Result<TempDir> {
+        let workdir = TempDir::new("playground-local").map_err(Error::UnableToPrepareDir)?;
+        let manifest_path = workdir.path().join("Cargo.toml");
+        fs::copy(&self.manifest_path, &manifest_path).map_err(Error::UnableToPrepareDir)?;
+
+        // this is done by the entrypoint for the docker images
+        if let Some(wasm_opt) = invocation.get_env("PLAYGROUND_WASM_OPT") {
+            let profile = invocation.get_env("PLAYGROUND_PROFILE").unwrap_or("dev");
+            let wasm_opt = if wasm_opt == "false" {
+                wasm_opt.to_owned()
+            } else {
+                format!("[\"{}\"]", wasm_opt)
+            };
+            let mut manifest = OpenOptions::new()
+                .append(true)
+                .open(&manifest_path)
+                .map_err(Error::UnableToPrepareDir)?;
+            write!(
+                manifest,
+                "\n[package.metadata.wasm-pack.profile.{}]\nwasm-opt = {}\n",
+                profile, wasm_opt
+            )
+            .map_err(Error::UnableToPrepareDir)?;
+        }
+
+        // read-only mounts can't be enforced with symlinks
+        for mount in &invocation.mounts {
+            symlink(&mount.host_path, workdir.path().join(&mount.name))
+                .map_err(Error::UnableToPrepareDir)?;
+        }
+
+        Ok(workdir)
+    }
+}
+impl Backend for LocalBackend {
+    fn execute(&self, invocation: &Invocation) -> Result<Execution> {
+        let (program, args) = invocation.args.split_first().ok_or_else(|| {
+            Error::UnableToExecuteCompiler(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invocation without a program",
+            ))
+        })?;
+        let workdir = self.prepare_workdir(invocation)?;
+
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .current_dir(workdir.path())
+            .envs(invocation.env.iter().map(|(k, v)| (k, v)))
+            .env("CARGO_TARGET_DIR", &self.target_dir);
+        if let Tool::Compiler(channel) = invocation.tool {
+            cmd.env(
+                "RUSTUP_TOOLCHAIN",
+                helpers::rustup_toolchain_for_channel(channel),
+            );
+        }
+
+        tracing::debug!(command = ?cmd, "starting local process");
+        run_with_timeout(cmd)
+    }
+
+    fn check_ready(&self) -> Result<()> {
+        // the tools themselves are only found when they're executed
+        fs::metadata(&self.manifest_path).map_err(Error::UnableToReadFile)?;
+        Ok(())
+    }
+}
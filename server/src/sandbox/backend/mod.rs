@@ -0,0 +1,304 @@
+//! Backends which execute the tools for a sandbox.
+
+use super::{Error, Result};
+use crate::trace;
+pub use docker::{DockerBackend, DockerConfig};
+pub use local::LocalBackend;
+use protocol::Channel;
+use std::{
+    fmt::Debug,
+    io::{self, Read},
+    mem,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+mod docker;
+#[cfg(test)]
+pub mod fake;
+mod local;
+
+/// Timeout after which the tool is killed from within the environment.
+pub const PROCESS_TIMEOUT_SOFT: Duration = Duration::from_secs(10);
+/// Timeout after which the backend gives up on the process.
+const PROCESS_TIMEOUT_HARD: Duration = Duration::from_secs(12);
+/// Interval in which the backend checks whether the process exited.
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Toolset an invocation requires.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tool {
+    Compiler(Channel),
+    Rustfmt,
+    Clippy,
+    CargoExpand,
+}
+impl Tool {
+    /// Every tool an invocation can require.
+    pub const ALL: &'static [Tool] = &[
+        Tool::Compiler(Channel::Stable),
+        Tool::Compiler(Channel::Nightly),
+        Tool::Rustfmt,
+        Tool::Clippy,
+        Tool::CargoExpand,
+    ];
+
+    /// Name used in logs and metrics.
+    pub fn name(self) -> &'static str {
+        use Tool::*;
+        match self {
+            Compiler(_) => "compiler",
+            Rustfmt => "rustfmt",
+            Clippy => "clippy",
+            CargoExpand => "cargo_expand",
+        }
+    }
+}
+
+/// Directory which is made available to the invocation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mount {
+    pub host_path: PathBuf,
+    /// Name of the directory relative to the crate root.
+    pub name: String,
+    pub read_only: bool,
+}
+
+/// Description of a single tool execution independent of the backend.
+/// All arguments are relative to the crate root which contains the
+/// playground manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Invocation {
+    pub tool: Tool,
+    /// Session the invocation is executed for.
+    pub session: Option<Uuid>,
+    /// Request which started the invocation.
+    pub request_id: Option<String>,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub mounts: Vec<Mount>,
+}
+impl Invocation {
+    pub fn new(tool: Tool) -> Self {
+        Self {
+            tool,
+            session: None,
+            request_id: trace::current_request_id(),
+            args: Vec::new(),
+            env: Vec::new(),
+            mounts: Vec::new(),
+        }
+    }
+
+    pub fn for_session(tool: Tool, session: Uuid) -> Self {
+        Self {
+            session: Some(session),
+            ..Self::new(tool)
+        }
+    }
+
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    pub fn env(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn mount(&mut self, host_path: &Path, name: &str) -> &mut Self {
+        self.push_mount(host_path, name, false)
+    }
+
+    /// Mount a directory which the tool can't modify.
+    pub fn mount_read_only(&mut self, host_path: &Path, name: &str) -> &mut Self {
+        self.push_mount(host_path, name, true)
+    }
+
+    fn push_mount(&mut self, host_path: &Path, name: &str, read_only: bool) -> &mut Self {
+        self.mounts.push(Mount {
+            host_path: host_path.to_owned(),
+            name: name.to_owned(),
+            read_only,
+        });
+        self
+    }
+
+    /// Get the value of an environment variable set for the invocation.
+    pub fn get_env(&self, key: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Resources used by an invocation.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ResourceUsage {
+    /// User and system CPU time.
+    pub cpu_time: Option<Duration>,
+    /// Peak resident memory in bytes.
+    pub peak_memory: Option<u64>,
+}
+
+/// Result of an invocation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Execution {
+    pub output: Output,
+    /// `None` fields weren't measured by the backend.
+    pub usage: ResourceUsage,
+    /// The tool was stopped because it exceeded the timeout.
+    pub timed_out: bool,
+}
+impl From<Output> for Execution {
+    fn from(output: Output) -> Self {
+        Self {
+            output,
+            usage: ResourceUsage::default(),
+            timed_out: false,
+        }
+    }
+}
+
+/// Executes invocations.
+pub trait Backend: Debug + Send + Sync {
+    fn execute(&self, invocation: &Invocation) -> Result<Execution>;
+
+    /// Kill the running invocations for which `keep` returns `false`.
+    /// `keep` receives the session of the invocation.
+    /// This also applies to invocations left behind by a previous run of the
+    /// server.
+    fn kill(&self, keep: &dyn Fn(Option<&Uuid>) -> bool) -> Result<()> {
+        let _ = keep;
+        Ok(())
+    }
+
+    /// Check that every tool in `Tool::ALL` can be executed.
+    fn check_ready(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Run the command and kill it, together with all of its descendants, once
+/// it exceeds the hard timeout.
+fn run_with_timeout(mut command: Command) -> Result<Execution> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // SAFETY: `setpgid` is async-signal-safe
+    unsafe {
+        // the process gets its own group so that it can be killed together
+        // with the processes it started
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        });
+    }
+    let mut child = command.spawn().map_err(Error::UnableToExecuteCompiler)?;
+    // both pipes have to be drained at the same time or the process may block
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + PROCESS_TIMEOUT_HARD;
+    let pid = child.id();
+    let (status, usage, timed_out) =
+        match wait_with_usage(pid, Some(deadline)).map_err(Error::UnableToExecuteCompiler)? {
+            Some((status, usage)) => (status, usage, false),
+            None => {
+                tracing::warn!("killing process {} after {:?}", pid, PROCESS_TIMEOUT_HARD);
+                // SAFETY: the process hasn't been waited for, so its id
+                // still refers to the group
+                unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+                let (status, usage) = wait_with_usage(pid, None)
+                    .map_err(Error::UnableToExecuteCompiler)?
+                    .expect("waiting without a deadline always returns the status");
+                (status, usage, true)
+            }
+        };
+
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    Ok(Execution {
+        output,
+        usage,
+        timed_out,
+    })
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            if let Err(err) = pipe.read_to_end(&mut buf) {
+                tracing::warn!("unable to read the output of the tool: {}", err);
+            }
+        }
+        buf
+    })
+}
+
+/// Wait for the process to exit and get the resources it used.
+/// Returns `None` if the process is still running at the deadline.
+/// `Child::wait` can't be used because it doesn't report the usage.
+fn wait_with_usage(
+    pid: u32,
+    deadline: Option<Instant>,
+) -> io::Result<Option<(ExitStatus, ResourceUsage)>> {
+    let options = if deadline.is_some() { libc::WNOHANG } else { 0 };
+    let mut status = 0;
+    // SAFETY: `rusage` is a plain C struct for which all zeroes is valid
+    let mut rusage: libc::rusage = unsafe { mem::zeroed() };
+    loop {
+        // SAFETY: the pointers are valid for the duration of the call
+        let ret = unsafe { libc::wait4(pid as libc::pid_t, &mut status, options, &mut rusage) };
+        if ret > 0 {
+            break;
+        }
+        if ret == 0 {
+            // still running
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+            thread::sleep(PROCESS_POLL_INTERVAL);
+            continue;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    let timeval_to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    let usage = ResourceUsage {
+        cpu_time: Some(timeval_to_duration(rusage.ru_utime) + timeval_to_duration(rusage.ru_stime)),
+        // reported in kibibytes
+        peak_memory: Some(rusage.ru_maxrss as u64 * 1024),
+    };
+    Ok(Some((ExitStatus::from_raw(status), usage)))
+}
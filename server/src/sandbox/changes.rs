@@ -0,0 +1,53 @@
+//! Feed of the files created and removed in a sandbox.
+
+use protocol::{File, FileChange};
+use std::{
+    collections::BTreeSet,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct ChangeFeed {
+    /// Files the subscribers know about.
+    files: Mutex<BTreeSet<String>>,
+    subscribers: Mutex<Vec<Sender<Vec<FileChange>>>>,
+}
+impl ChangeFeed {
+    /// Receive the changes published from now on.
+    /// The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<Vec<FileChange>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .expect("change feed lock poisoned")
+            .push(sender);
+        receiver
+    }
+
+    /// Publish the differences between `files` and the previously known
+    /// files.
+    pub fn update(&self, files: BTreeSet<String>) {
+        // holding the lock keeps the changes in order
+        let mut known = self.files.lock().expect("change feed lock poisoned");
+        let mut changes: Vec<FileChange> = known
+            .difference(&files)
+            .map(|path| FileChange::Removed { path: path.clone() })
+            .collect();
+        changes.extend(files.difference(&known).map(|path| FileChange::Created {
+            file: File::from_path(path),
+        }));
+        *known = files;
+
+        if !changes.is_empty() {
+            self.publish(changes);
+        }
+    }
+
+    fn publish(&self, changes: Vec<FileChange>) {
+        let mut subscribers = self.subscribers.lock().expect("change feed lock poisoned");
+        subscribers.retain(|subscriber| subscriber.send(changes.clone()).is_ok());
+    }
+}
@@ -1,97 +1,87 @@
 use super::{
-    helpers::{self, BacktraceRequest, EditionRequest},
+    backend::{Backend, Invocation, Tool},
+    helpers::{self, BacktraceRequest, EditionRequest, ProfileRequest},
     Channel,
     Error,
     Mode,
     Result,
 };
-use protocol::{ToolVersions, Version};
-use std::{
-    collections::BTreeMap,
-    process::{Command, Output},
-    time::Duration,
-};
-
-const DOCKER_PROCESS_TIMEOUT_SOFT: Duration = Duration::from_secs(10);
-const DOCKER_PROCESS_TIMEOUT_HARD: Duration = Duration::from_secs(12);
+use protocol::{EmitTarget, ToolVersions, Version};
+use std::collections::BTreeMap;
 
-pub trait DockerCommandExt {
+pub trait InvocationExt {
     fn apply_edition(&mut self, req: impl EditionRequest);
     fn apply_backtrace(&mut self, req: impl BacktraceRequest);
+    fn apply_profile(&mut self, req: impl ProfileRequest);
 }
 
-impl DockerCommandExt for Command {
+impl InvocationExt for Invocation {
     fn apply_edition(&mut self, req: impl EditionRequest) {
         if let Some(edition) = req.edition() {
-            self.args(&[
-                "--env",
-                &format!(
-                    "PLAYGROUND_EDITION={}",
-                    helpers::cargo_ident_for_edition(edition)
-                ),
-            ]);
+            self.env(
+                "PLAYGROUND_EDITION",
+                helpers::cargo_ident_for_edition(edition),
+            );
         }
     }
 
     fn apply_backtrace(&mut self, req: impl BacktraceRequest) {
         if req.backtrace() {
-            self.args(&["--env", "RUST_BACKTRACE=1"]);
+            self.env("RUST_BACKTRACE", "1");
         }
     }
-}
 
-pub fn docker_run() -> Command {
-    let mut cmd = Command::new("docker");
-    cmd.arg("run")
-        .arg("--rm")
-        .arg("--tty")
-        .arg("--cap-drop=ALL")
-        // Needed to allow overwriting the file
-        .arg("--cap-add=DAC_OVERRIDE")
-        .arg("--security-opt=no-new-privileges")
-        .args(&["--workdir", "/playground"])
-        .args(&["--net", "none"])
-        .args(&["--memory", "256m"])
-        .args(&["--memory-swap", "320m"])
-        .args(&[
-            "--env",
-            &format!(
-                "PLAYGROUND_TIMEOUT={}",
-                DOCKER_PROCESS_TIMEOUT_SOFT.as_secs()
-            ),
-        ])
-        .args(&["--pids-limit", "512"]);
+    fn apply_profile(&mut self, req: impl ProfileRequest) {
+        let profile = helpers::cargo_profile_for_mode(req.mode());
+        let env_prefix = format!("CARGO_PROFILE_{}", profile.to_uppercase());
 
-    cmd
-}
-
-pub fn run_with_timeout(mut command: Command) -> Result<Output> {
-    // TODO handle timeout
-    let _ = DOCKER_PROCESS_TIMEOUT_HARD;
-    command.output().map_err(Error::UnableToExecuteCompiler)
+        if let Some(level) = req.opt_level() {
+            self.env(
+                format!("{}_OPT_LEVEL", env_prefix),
+                helpers::cargo_ident_for_opt_level(level),
+            );
+        }
+        if let Some(lto) = req.lto() {
+            self.env(
+                format!("{}_LTO", env_prefix),
+                helpers::cargo_ident_for_lto(lto),
+            );
+        }
+        if let Some(units) = req.codegen_units() {
+            self.env(format!("{}_CODEGEN_UNITS", env_prefix), units.to_string());
+        }
+        // wasm-pack only reads this from the manifest which is updated by the
+        // entrypoint.
+        if let Some(level) = req.wasm_opt() {
+            self.env("PLAYGROUND_PROFILE", profile);
+            self.env("PLAYGROUND_WASM_OPT", helpers::wasm_opt_ident(level));
+        }
+    }
 }
 
-pub fn set_execution_environment(cmd: &mut Command, req: impl EditionRequest + BacktraceRequest) {
-    cmd.apply_edition(&req);
-    cmd.apply_backtrace(&req);
+pub fn set_execution_environment(
+    invocation: &mut Invocation,
+    req: impl EditionRequest + BacktraceRequest,
+) {
+    invocation.apply_edition(&req);
+    invocation.apply_backtrace(&req);
 }
 
-pub fn get_tool_versions(channel: Channel) -> Result<ToolVersions> {
+pub fn get_tool_versions(backend: &dyn Backend, channel: Channel) -> Result<ToolVersions> {
     Ok(ToolVersions {
-        rustc: version_rustc(channel)?,
-        rustfmt: version_rustfmt()?,
-        clippy: version_clippy()?,
-        cargo_expand: version_cargo_expand()?,
+        rustc: version_rustc(backend, channel)?,
+        rustfmt: version_rustfmt(backend)?,
+        clippy: version_clippy(backend)?,
+        cargo_expand: version_cargo_expand(backend)?,
     })
 }
 
-pub fn version_rustc(channel: Channel) -> Result<Version> {
-    let mut command = docker_run();
-    command.args(&[helpers::container_name_for_channel(channel)]);
-    command.args(&["rustc", "--version", "--verbose"]);
+pub fn version_rustc(backend: &dyn Backend, channel: Channel) -> Result<Version> {
+    let mut invocation = Invocation::new(Tool::Compiler(channel));
+    invocation.args(["rustc", "--version", "--verbose"]);
 
-    let output = run_with_timeout(command)?;
-    let version_output = helpers::string_from_utf8_vec(output.stdout)?;
+    let output = backend.execute(&invocation)?.output;
+    let version_output = helpers::string_from_utf8_lossy(output.stdout);
 
     let mut info: BTreeMap<String, String> = version_output
         .lines()
@@ -120,28 +110,28 @@ pub fn version_rustc(channel: Channel) -> Result<Version> {
     })
 }
 
-pub fn version_rustfmt() -> Result<Version> {
-    let mut command = docker_run();
-    command.args(&["rustfmt", "cargo", "fmt", "--version"]);
-    cargo_tool_version(command)
+pub fn version_rustfmt(backend: &dyn Backend) -> Result<Version> {
+    let mut invocation = Invocation::new(Tool::Rustfmt);
+    invocation.args(["cargo", "fmt", "--version"]);
+    cargo_tool_version(backend, &invocation)
 }
 
-pub fn version_clippy() -> Result<Version> {
-    let mut command = docker_run();
-    command.args(&["clippy", "cargo", "clippy", "--version"]);
-    cargo_tool_version(command)
+pub fn version_clippy(backend: &dyn Backend) -> Result<Version> {
+    let mut invocation = Invocation::new(Tool::Clippy);
+    invocation.args(["cargo", "clippy", "--version"]);
+    cargo_tool_version(backend, &invocation)
 }
 
-pub fn version_cargo_expand() -> Result<Version> {
-    let mut command = docker_run();
-    command.args(&["cargo-expand", "cargo", "expand", "--version"]);
-    cargo_tool_version(command)
+pub fn version_cargo_expand(backend: &dyn Backend) -> Result<Version> {
+    let mut invocation = Invocation::new(Tool::CargoExpand);
+    invocation.args(["cargo", "expand", "--version"]);
+    cargo_tool_version(backend, &invocation)
 }
 
 // Parses versions of the shape `toolname 0.0.0 (0000000 0000-00-00)`
-fn cargo_tool_version(command: Command) -> Result<Version> {
-    let output = run_with_timeout(command)?;
-    let version_output = helpers::string_from_utf8_vec(output.stdout)?;
+fn cargo_tool_version(backend: &dyn Backend, invocation: &Invocation) -> Result<Version> {
+    let output = backend.execute(invocation)?.output;
+    let version_output = helpers::string_from_utf8_lossy(output.stdout);
     let mut parts = version_output.split_whitespace().fuse().skip(1);
 
     let release = parts.next().unwrap_or("").into();
@@ -168,3 +158,66 @@ pub fn wasm_pack_build(_channel: Channel, mode: Mode, out_dir: &'static str) ->
 
     cmd
 }
+
+pub fn cargo_check(_channel: Channel) -> Vec<&'static str> {
+    let mut cmd = vec!["cargo", "check"];
+    cmd.extend(&["--target", "wasm32-unknown-unknown"]);
+    cmd.push("--message-format=json");
+
+    cmd
+}
+
+/// Command to emit the intermediate compiler output of the given type to
+/// `output_path`.
+/// `EmitTarget::Wat` isn't produced by the compiler, use `wasm2wat` for it.
+///
+/// The crate is compiled as a single codegen unit because rustc writes one
+/// file per unit otherwise and ignores `output_path`.
+pub fn cargo_rustc_emit(
+    _channel: Channel,
+    mode: Mode,
+    target: EmitTarget,
+    output_path: &str,
+) -> Vec<String> {
+    let mut cmd = vec!["cargo", "rustc", "--lib"];
+    cmd.extend(&["--target", "wasm32-unknown-unknown"]);
+    if let Mode::Release = mode {
+        cmd.push("--release");
+    }
+    cmd.push("--");
+
+    cmd.extend(&["-C", "codegen-units=1"]);
+
+    let mut cmd: Vec<String> = cmd.into_iter().map(String::from).collect();
+    cmd.push(format!(
+        "--emit={}={}",
+        helpers::rustc_ident_for_emit_target(target),
+        output_path
+    ));
+
+    cmd
+}
+
+pub fn wasm2wat(wasm_path: &str) -> Vec<String> {
+    vec!["wasm2wat".into(), wasm_path.into()]
+}
+
+pub fn twiggy_top(wasm_path: &str) -> Vec<String> {
+    vec![
+        "twiggy".into(),
+        "top".into(),
+        "--format".into(),
+        "json".into(),
+        wasm_path.into(),
+    ]
+}
+
+pub fn twiggy_dominators(wasm_path: &str) -> Vec<String> {
+    vec![
+        "twiggy".into(),
+        "dominators".into(),
+        "--format".into(),
+        "json".into(),
+        wasm_path.into(),
+    ]
+}
@@ -0,0 +1,205 @@
+use super::{Error, Result};
+use protocol::{Diagnostic, DiagnosticLevel, DiagnosticSpan};
+use serde::Deserialize;
+
+/// A single line of output produced by `cargo --message-format=json`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum CargoMessage {
+    CompilerMessage {
+        message: RustcDiagnostic,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    code: Option<RustcDiagnosticCode>,
+    level: String,
+    spans: Vec<RustcDiagnosticSpan>,
+    rendered: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustcDiagnosticCode {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RustcDiagnosticSpan {
+    file_name: String,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    label: Option<String>,
+}
+
+fn diagnostic_level(level: &str) -> DiagnosticLevel {
+    match level {
+        "warning" => DiagnosticLevel::Warning,
+        "note" | "failure-note" => DiagnosticLevel::Note,
+        "help" => DiagnosticLevel::Help,
+        // "error" and "error: internal compiler error"
+        _ => DiagnosticLevel::Error,
+    }
+}
+
+impl From<RustcDiagnostic> for Diagnostic {
+    fn from(diagnostic: RustcDiagnostic) -> Self {
+        Self {
+            level: diagnostic_level(&diagnostic.level),
+            message: diagnostic.message,
+            code: diagnostic.code.map(|code| code.code),
+            spans: diagnostic
+                .spans
+                .into_iter()
+                .map(|span| DiagnosticSpan {
+                    file_name: span.file_name,
+                    line_start: span.line_start,
+                    line_end: span.line_end,
+                    column_start: span.column_start,
+                    column_end: span.column_end,
+                    is_primary: span.is_primary,
+                    label: span.label,
+                })
+                .collect(),
+            rendered: diagnostic.rendered,
+        }
+    }
+}
+
+/// Output of `cargo --message-format=json` split into the compiler
+/// diagnostics and everything else.
+#[derive(Debug, Default)]
+pub struct CargoMessages {
+    pub diagnostics: Vec<Diagnostic>,
+    /// Lines which aren't JSON objects (ex. messages printed by the entrypoint
+    /// or cargo's stderr when the container is run with a TTY).
+    pub other_output: String,
+}
+
+/// Extract the compiler diagnostics from the JSON output of cargo.
+pub fn parse_cargo_messages(output: &str) -> Result<CargoMessages> {
+    let mut messages = CargoMessages::default();
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') {
+            messages.other_output.push_str(line);
+            messages.other_output.push('\n');
+            continue;
+        }
+
+        let message = serde_json::from_str(trimmed).map_err(Error::UnableToParseDiagnostics)?;
+        if let CargoMessage::CompilerMessage { message } = message {
+            messages.diagnostics.push(Diagnostic::from(message));
+        }
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of `cargo check --message-format=json` for a crate with an
+    /// unused variable, with lines which aren't JSON in between.
+    const WARNING_OUTPUT: &str = r##"    Blocking waiting for file lock on package cache
+{"reason":"compiler-message","package_id":"path+file:///playground#playground@0.1.0","manifest_path":"/playground/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"playground","src_path":"/playground/src/lib.rs","edition":"2018","doc":true,"doctest":true,"test":true},"message":{"rendered":"warning: unused variable: `x`\n --> src/lib.rs:2:9\n  |\n2 |     let x = 5;\n  |         ^ help: if this is intentional, prefix it with an underscore: `_x`\n  |\n  = note: `#[warn(unused_variables)]` (part of `#[warn(unused)]`) on by default\n\n","$message_type":"diagnostic","children":[{"children":[],"code":null,"level":"note","message":"`#[warn(unused_variables)]` (part of `#[warn(unused)]`) on by default","rendered":null,"spans":[]},{"children":[],"code":null,"level":"help","message":"if this is intentional, prefix it with an underscore","rendered":null,"spans":[{"byte_end":37,"byte_start":36,"column_end":10,"column_start":9,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","text":[{"highlight_end":10,"highlight_start":9,"text":"    let x = 5;"}]}]}],"level":"warning","message":"unused variable: `x`","spans":[{"byte_end":37,"byte_start":36,"column_end":10,"column_start":9,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":null,"line_end":2,"line_start":2,"suggested_replacement":null,"suggestion_applicability":null,"text":[{"highlight_end":10,"highlight_start":9,"text":"    let x = 5;"}]}],"code":{"code":"unused_variables","explanation":null}}}
+
+{"reason":"compiler-artifact","package_id":"path+file:///playground#playground@0.1.0","manifest_path":"/playground/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"playground","src_path":"/playground/src/lib.rs","edition":"2018","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/playground/target/debug/deps/libplayground-c06c567a9d1cab53.rmeta"],"executable":null,"fresh":false}
+{"reason":"build-finished","success":true}
+"##;
+
+    /// Output for a crate with a type error.
+    const ERROR_OUTPUT: &str = r##"{"reason":"compiler-message","package_id":"path+file:///playground#playground@0.1.0","manifest_path":"/playground/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"playground","src_path":"/playground/src/lib.rs","edition":"2018","doc":true,"doctest":true,"test":true},"message":{"rendered":"error[E0308]: mismatched types\n --> src/lib.rs:3:18\n  |\n3 |     let y: u32 = \"1\";\n  |            ---   ^^^ expected `u32`, found `&str`\n  |            |\n  |            expected due to this\n\n","$message_type":"diagnostic","children":[],"level":"error","message":"mismatched types","spans":[{"byte_end":63,"byte_start":60,"column_end":21,"column_start":18,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":"expected `u32`, found `&str`","line_end":3,"line_start":3,"suggested_replacement":null,"suggestion_applicability":null,"text":[{"highlight_end":21,"highlight_start":18,"text":"    let y: u32 = \"1\";"}]},{"byte_end":57,"byte_start":54,"column_end":15,"column_start":12,"expansion":null,"file_name":"src/lib.rs","is_primary":false,"label":"expected due to this","line_end":3,"line_start":3,"suggested_replacement":null,"suggestion_applicability":null,"text":[{"highlight_end":15,"highlight_start":12,"text":"    let y: u32 = \"1\";"}]}],"code":{"code":"E0308","explanation":"Expected type did not match the received type.\n"}}}
+{"reason":"compiler-message","package_id":"path+file:///playground#playground@0.1.0","manifest_path":"/playground/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"playground","src_path":"/playground/src/lib.rs","edition":"2018","doc":true,"doctest":true,"test":true},"message":{"rendered":"For more information about this error, try `rustc --explain E0308`.\n","$message_type":"diagnostic","children":[],"level":"failure-note","message":"For more information about this error, try `rustc --explain E0308`.","spans":[],"code":null}}
+{"reason":"build-finished","success":false}
+"##;
+
+    #[test]
+    fn compiler_messages_are_extracted() {
+        let diagnostics = parse_cargo_messages(WARNING_OUTPUT).unwrap().diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        let warning = &diagnostics[0];
+        assert_eq!(warning.level, DiagnosticLevel::Warning);
+        assert_eq!(warning.message, "unused variable: `x`");
+        assert_eq!(warning.code.as_deref(), Some("unused_variables"));
+        assert_eq!(
+            warning.spans,
+            [DiagnosticSpan {
+                file_name: "src/lib.rs".to_owned(),
+                line_start: 2,
+                line_end: 2,
+                column_start: 9,
+                column_end: 10,
+                is_primary: true,
+                label: None,
+            }]
+        );
+        assert!(warning
+            .rendered
+            .as_deref()
+            .unwrap()
+            .starts_with("warning: unused variable: `x`\n --> src/lib.rs:2:9"));
+    }
+
+    #[test]
+    fn errors_keep_their_spans_and_notes() {
+        let diagnostics = parse_cargo_messages(ERROR_OUTPUT).unwrap().diagnostics;
+        let levels: Vec<_> = diagnostics.iter().map(|d| d.level).collect();
+        assert_eq!(levels, [DiagnosticLevel::Error, DiagnosticLevel::Note]);
+
+        let error = &diagnostics[0];
+        assert_eq!(error.message, "mismatched types");
+        assert_eq!(error.code.as_deref(), Some("E0308"));
+        let labels: Vec<_> = error
+            .spans
+            .iter()
+            .map(|span| (span.is_primary, span.column_start, span.label.as_deref()))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                (true, 18, Some("expected `u32`, found `&str`")),
+                (false, 12, Some("expected due to this")),
+            ]
+        );
+
+        let note = &diagnostics[1];
+        assert_eq!(note.code, None);
+        assert!(note.spans.is_empty());
+    }
+
+    #[test]
+    fn other_output_is_kept() {
+        let messages = parse_cargo_messages("").unwrap();
+        assert!(messages.diagnostics.is_empty());
+        assert_eq!(messages.other_output, "");
+
+        let messages = parse_cargo_messages("error: could not find `Cargo.toml`\r\n").unwrap();
+        assert!(messages.diagnostics.is_empty());
+        assert_eq!(
+            messages.other_output,
+            "error: could not find `Cargo.toml`\n"
+        );
+
+        let messages = parse_cargo_messages(WARNING_OUTPUT).unwrap();
+        assert_eq!(
+            messages.other_output,
+            "    Blocking waiting for file lock on package cache\n\n"
+        );
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        assert!(matches!(
+            parse_cargo_messages("{\"reason\":\"compiler-message\"}"),
+            Err(Error::UnableToParseDiagnostics(_))
+        ));
+    }
+}
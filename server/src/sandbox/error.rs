@@ -1,4 +1,5 @@
-use std::{io, path::PathBuf, string::FromUtf8Error, time::Duration};
+use protocol::QuotaKind;
+use std::{io, path::PathBuf, time::Duration};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -12,21 +13,39 @@ pub enum Error {
     UnableToReadFile(#[source] io::Error),
     #[error("unable to write file: {0}")]
     UnableToWriteFile(#[source] io::Error),
+    #[error("unable to read the seccomp profile {}: {1}", .0.display())]
+    UnableToReadSeccompProfile(PathBuf, #[source] io::Error),
 
+    #[error("{quota:?} quota of {limit} exceeded")]
+    QuotaExceeded { quota: QuotaKind, limit: u64 },
+    #[error("file was modified, current version is {current:?}")]
+    VersionMismatch { current: Option<String> },
     #[error("path is invalid: {0}")]
     InvalidPath(PathBuf),
+    #[error("codegen units must be between 1 and {max}, got {0}", max = super::MAX_CODEGEN_UNITS)]
+    InvalidCodegenUnits(u32),
     #[error("sandbox directory is corrupted")]
     CorruptSandboxDir,
 
     #[error("unable to execute the compiler: {0}")]
     UnableToExecuteCompiler(#[source] io::Error),
+    #[error("unable to kill running invocations: {0}")]
+    UnableToKillInvocations(#[source] io::Error),
+    #[error("container runtime is unavailable: {0}")]
+    RuntimeUnavailable(String),
+    #[error("images are missing: {}", .0.join(", "))]
+    ImagesMissing(Vec<String>),
     #[error("compiler execution took longer than {0}ms", timeout.as_millis())]
     CompilerExecutionTimedOut { timeout: Duration },
 
     #[error("Unable to read crate information: {0}")]
     UnableToParseCrateInformation(#[source] serde_json::Error),
-    #[error("output was not valid UTF-8: {0}")]
-    OutputNotUtf8(#[source] FromUtf8Error),
+    #[error("unable to parse compiler diagnostics: {0}")]
+    UnableToParseDiagnostics(#[source] serde_json::Error),
+    #[error("unable to parse size analysis: {0}")]
+    UnableToParseSizes(#[source] serde_json::Error),
+    #[error("unable to parse the audit log: {0}")]
+    UnableToParseAuditLog(#[source] serde_json::Error),
 
     #[error("output was missing")]
     OutputMissing,
@@ -37,3 +56,31 @@ pub enum Error {
     #[error("commit date was missing from the version output")]
     VersionDateMissing,
 }
+
+/// Errors which are the server's fault are reported as `InternalError` with
+/// the message, all others are reported with their own type.
+impl From<Error> for protocol::Error {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            QuotaExceeded { quota, limit } => Self::QuotaExceeded { quota, limit },
+            VersionMismatch { current } => Self::FileConflict {
+                etag: current.as_deref().map(super::entity_tag),
+            },
+            InvalidPath(_) => Self::InvalidPath,
+            InvalidCodegenUnits(_) => Self::InvalidRequest {
+                message: err.to_string(),
+            },
+            UnableToExecuteCompiler(_) | RuntimeUnavailable(_) | ImagesMissing(_) => {
+                Self::ToolUnavailable
+            }
+            CompilerExecutionTimedOut { timeout } => Self::ToolTimedOut {
+                timeout_ms: timeout.as_millis() as u64,
+            },
+            OutputMissing => Self::BuildMissing,
+            err => Self::InternalError {
+                message: err.to_string(),
+            },
+        }
+    }
+}
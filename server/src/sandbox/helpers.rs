@@ -1,15 +1,43 @@
-use super::{Error, Result};
 use protocol::{
     Channel,
+    CheckRequest,
     ClippyRequest,
     CompileRequest,
     Edition,
+    EmitRequest,
+    EmitTarget,
     FormatRequest,
+    Lto,
     MacroExpandRequest,
+    Mode,
+    OptLevel,
+    WasmOptLevel,
 };
 
-pub fn string_from_utf8_vec(v: Vec<u8>) -> Result<String> {
-    String::from_utf8(v).map_err(Error::OutputNotUtf8)
+/// Decode the output of a tool, replacing invalid UTF-8 sequences.
+pub fn string_from_utf8_lossy(v: Vec<u8>) -> String {
+    match String::from_utf8(v) {
+        Ok(s) => s,
+        Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+    }
+}
+
+/// Decode the output of a tool like `string_from_utf8_lossy` and cut it off
+/// after `limit` bytes.
+/// Returns whether the output was truncated.
+pub fn truncated_string_from_utf8_lossy(mut v: Vec<u8>, limit: u64) -> (String, bool) {
+    let truncated = v.len() as u64 > limit;
+    if truncated {
+        v.truncate(limit as usize);
+        // don't leave a character which was cut in half at the end
+        if let Err(err) = std::str::from_utf8(&v) {
+            if err.error_len().is_none() {
+                v.truncate(err.valid_up_to());
+            }
+        }
+    }
+
+    (string_from_utf8_lossy(v), truncated)
 }
 
 pub fn container_name_for_channel(channel: Channel) -> &'static str {
@@ -21,6 +49,15 @@ pub fn container_name_for_channel(channel: Channel) -> &'static str {
     }
 }
 
+pub fn rustup_toolchain_for_channel(channel: Channel) -> &'static str {
+    use Channel::*;
+
+    match channel {
+        Stable => "stable",
+        Nightly => "nightly",
+    }
+}
+
 pub fn cargo_ident_for_edition(edition: Edition) -> &'static str {
     use Edition::*;
 
@@ -29,6 +66,66 @@ pub fn cargo_ident_for_edition(edition: Edition) -> &'static str {
     }
 }
 
+pub fn cargo_profile_for_mode(mode: Mode) -> &'static str {
+    use Mode::*;
+
+    match mode {
+        Debug => "dev",
+        Release => "release",
+    }
+}
+
+pub fn cargo_ident_for_opt_level(level: OptLevel) -> &'static str {
+    use OptLevel::*;
+
+    match level {
+        Zero => "0",
+        One => "1",
+        Two => "2",
+        Three => "3",
+        S => "s",
+        Z => "z",
+    }
+}
+
+pub fn cargo_ident_for_lto(lto: Lto) -> &'static str {
+    use Lto::*;
+
+    match lto {
+        Off => "off",
+        Thin => "thin",
+        Fat => "fat",
+    }
+}
+
+/// Value for the `wasm-opt` key of the wasm-pack profile.
+/// The entrypoint treats `false` specially, everything else is a flag.
+pub fn wasm_opt_ident(level: WasmOptLevel) -> &'static str {
+    use WasmOptLevel::*;
+
+    match level {
+        Disabled => "false",
+        O1 => "-O1",
+        O2 => "-O2",
+        O3 => "-O3",
+        O4 => "-O4",
+        Os => "-Os",
+        Oz => "-Oz",
+    }
+}
+
+pub fn rustc_ident_for_emit_target(target: EmitTarget) -> &'static str {
+    use EmitTarget::*;
+
+    match target {
+        Mir => "mir",
+        LlvmIr => "llvm-ir",
+        Asm => "asm",
+        // not emitted by rustc but this is what it would be called
+        Wat => "wat",
+    }
+}
+
 pub trait EditionRequest {
     fn edition(&self) -> Option<Edition>;
 }
@@ -47,6 +144,35 @@ impl<R: BacktraceRequest> BacktraceRequest for &'_ R {
     }
 }
 
+pub trait ProfileRequest {
+    fn mode(&self) -> Mode;
+    fn opt_level(&self) -> Option<OptLevel>;
+    fn lto(&self) -> Option<Lto>;
+    fn codegen_units(&self) -> Option<u32>;
+    fn wasm_opt(&self) -> Option<WasmOptLevel>;
+}
+impl<R: ProfileRequest> ProfileRequest for &'_ R {
+    fn mode(&self) -> Mode {
+        (*self).mode()
+    }
+
+    fn opt_level(&self) -> Option<OptLevel> {
+        (*self).opt_level()
+    }
+
+    fn lto(&self) -> Option<Lto> {
+        (*self).lto()
+    }
+
+    fn codegen_units(&self) -> Option<u32> {
+        (*self).codegen_units()
+    }
+
+    fn wasm_opt(&self) -> Option<WasmOptLevel> {
+        (*self).wasm_opt()
+    }
+}
+
 impl EditionRequest for CompileRequest {
     fn edition(&self) -> Option<Edition> {
         self.edition
@@ -57,6 +183,39 @@ impl BacktraceRequest for CompileRequest {
         self.backtrace
     }
 }
+impl ProfileRequest for CompileRequest {
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn opt_level(&self) -> Option<OptLevel> {
+        self.opt_level
+    }
+
+    fn lto(&self) -> Option<Lto> {
+        self.lto
+    }
+
+    fn codegen_units(&self) -> Option<u32> {
+        self.codegen_units
+    }
+
+    fn wasm_opt(&self) -> Option<WasmOptLevel> {
+        self.wasm_opt
+    }
+}
+
+impl EditionRequest for CheckRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+impl EditionRequest for EmitRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
 
 impl EditionRequest for FormatRequest {
     fn edition(&self) -> Option<Edition> {
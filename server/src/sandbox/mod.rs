@@ -1,41 +1,216 @@
-use commands::DockerCommandExt;
+pub use audit::AuditConfig;
+use audit::AuditLog;
+#[cfg(test)]
+pub use backend::fake::FakeBackend;
+pub use backend::{Backend, DockerBackend, DockerConfig, LocalBackend};
+use backend::{Execution, Invocation, ResourceUsage, Tool, PROCESS_TIMEOUT_SOFT};
+use changes::ChangeFeed;
+use commands::InvocationExt;
 pub use error::{Error, Result};
-use helpers::{BacktraceRequest, EditionRequest};
+use helpers::{BacktraceRequest, EditionRequest, ProfileRequest};
 use protocol::{
+    AuditEntry,
     Channel,
+    CheckRequest,
+    CheckResponse,
     ClippyRequest,
-    ClippyResponse,
     CompileRequest,
     CompileResponse,
+    EmitRequest,
+    EmitResponse,
+    EmitTarget,
+    FileChange,
+    FileContent,
     FormatRequest,
-    FormatResponse,
     MacroExpandRequest,
-    MacroExpandResponse,
     Mode,
+    QuotaKind,
     SandboxStructure,
+    SizeRequest,
+    SizeResponse,
+    ToolOutput,
     ToolVersions,
 };
+pub use quota::Quota;
+use quota::Usage;
+use slots::Slots;
+pub use stats::{InvocationStats, Outcome, RunningInvocation, DURATION_BUCKETS};
 use std::{
     borrow::Cow,
-    collections::VecDeque,
-    fs::{self, Permissions},
-    io,
-    os::unix::fs::PermissionsExt,
+    collections::{BTreeSet, VecDeque},
+    fs::{self, File, OpenOptions, Permissions},
+    io::{self, Read, Write},
+    mem,
+    os::unix::{
+        fs::{OpenOptionsExt, PermissionsExt},
+        io::AsRawFd,
+        process::ExitStatusExt,
+    },
     path::{Component, Path, PathBuf},
-    process::Command,
+    process::Output,
+    sync::{mpsc::Receiver, Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tempdir::TempDir;
+use uuid::Uuid;
+pub use version::{content_version, entity_tag, Precondition};
 
+mod audit;
+mod backend;
+mod changes;
 mod commands;
+mod diagnostics;
 mod error;
 mod helpers;
+mod quota;
+mod size;
+mod slots;
+mod stats;
+#[cfg(test)]
+mod tests;
+mod version;
 
 const PUBLIC_DIR_NAME: &str = "public";
 const SRC_DIR_NAME: &str = "src";
 const BUILD_DIR_NAME: &str = "build";
+const EMIT_DIR_NAME: &str = "output";
+const EMIT_FILE_NAME: &str = "compilation";
+const AUDIT_LOG_FILE_NAME: &str = "audit.jsonl";
+
+/// Maximum number of bytes of emitted compiler output returned to the client.
+const MAX_EMIT_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Upper limit for the `codegen-units` override.
+const MAX_CODEGEN_UNITS: u32 = 256;
+
+/// Names of the tools a sandbox can run.
+/// These are the API operations, which can differ from the tools the backend
+/// executes for them.
+pub fn tool_names() -> Vec<String> {
+    [
+        "compile",
+        "check",
+        "emit",
+        "size",
+        "format",
+        "clippy",
+        "macro-expand",
+    ]
+    .iter()
+    .map(|&name| name.to_owned())
+    .collect()
+}
+
+/// Resources shared by all sandboxes.
+#[derive(Debug)]
+pub struct Context {
+    backend: Arc<dyn Backend>,
+    /// Directory containing the scratch directories of all sandboxes.
+    /// Every entry is named `{session}-{suffix}` with the simple form of the
+    /// id of the session it belongs to.
+    scratch_dir: PathBuf,
+    quota: Quota,
+    audit: AuditConfig,
+    stats: InvocationStats,
+    /// Invocations wait for a free slot before they're executed.
+    slots: Slots,
+}
+impl Context {
+    pub fn new(backend: Arc<dyn Backend>, scratch_dir: PathBuf, quota: Quota) -> Result<Self> {
+        fs::create_dir_all(&scratch_dir).map_err(Error::UnableToPrepareDir)?;
+        // paths are confined to the scratch directory by comparing them
+        let scratch_dir = scratch_dir
+            .canonicalize()
+            .map_err(Error::UnableToPrepareDir)?;
+        Ok(Self {
+            backend,
+            scratch_dir,
+            quota,
+            audit: AuditConfig::default(),
+            stats: InvocationStats::default(),
+            slots: Slots::new(usize::MAX),
+        })
+    }
+
+    pub fn with_audit(self, audit: AuditConfig) -> Self {
+        Self { audit, ..self }
+    }
+
+    /// Limit the number of invocations which run at once.
+    pub fn with_max_running(self, max_running: usize) -> Self {
+        Self {
+            slots: Slots::new(max_running),
+            ..self
+        }
+    }
+
+    pub fn stats(&self) -> &InvocationStats {
+        &self.stats
+    }
+
+    /// Number of bytes stored in the scratch directory.
+    pub fn disk_usage(&self) -> Result<u64> {
+        let usage = Usage::of_dir(&self.scratch_dir).map_err(Error::UnableToReadFile)?;
+        Ok(usage.bytes)
+    }
+
+    /// Create a scratch directory for the session.
+    fn create_scratch_dir(&self, session: &Uuid, suffix: &str) -> Result<TempDir> {
+        let prefix = format!("{}-{}", session.to_simple(), suffix);
+        TempDir::new_in(&self.scratch_dir, &prefix).map_err(Error::UnableToPrepareDir)
+    }
+
+    /// Check that the backend is able to run the tools.
+    pub fn check_ready(&self) -> Result<()> {
+        self.backend.check_ready()
+    }
+
+    /// Kill the running invocations for which `keep` returns `false`.
+    pub fn kill_invocations(&self, keep: impl Fn(Option<&Uuid>) -> bool) -> Result<()> {
+        self.backend.kill(&keep)
+    }
+
+    /// Kill the invocations and remove the scratch directories which don't
+    /// belong to a known session.
+    /// These are left behind if the server doesn't shut down properly.
+    /// Entries which weren't created by `create_scratch_dir` are left alone.
+    pub fn reconcile(&self, is_known: impl Fn(&Uuid) -> bool) -> Result<()> {
+        self.kill_invocations(|session| session.is_some_and(&is_known))?;
+
+        for entry in self
+            .scratch_dir
+            .read_dir()
+            .map_err(Error::UnableToPrepareDir)?
+        {
+            let entry = entry.map_err(Error::UnableToPrepareDir)?;
+            let session = match entry.file_name().to_str().and_then(scratch_dir_session) {
+                Some(session) => session,
+                None => continue,
+            };
+            if is_known(&session) {
+                continue;
+            }
+
+            tracing::info!("removing orphaned scratch directory {:?}", entry.path());
+            let path = entry.path();
+            let result = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            if let Err(err) = result {
+                tracing::warn!("failed to remove {:?}: {}", path, err);
+            }
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct Sandbox {
+    id: Uuid,
+    context: Arc<Context>,
     _scratch: TempDir,
     root_dir: PathBuf,
     // other files like index.html
@@ -44,11 +219,15 @@ pub struct Sandbox {
     src_dir: PathBuf,
     // build artefacts
     build_dir: PathBuf,
+    /// Serializes writes so preconditions can't be raced.
+    write_lock: Mutex<()>,
+    changes: ChangeFeed,
+    audit: AuditLog,
 }
 impl Sandbox {
     /// Creates a Sandbox with only the directory structure.
-    fn create_empty() -> Result<Self> {
-        let scratch = TempDir::new("playground").map_err(Error::UnableToPrepareDir)?;
+    fn create_empty(id: Uuid, context: Arc<Context>) -> Result<Self> {
+        let scratch = context.create_scratch_dir(&id, "sandbox")?;
         let root_dir = scratch
             .path()
             .canonicalize()
@@ -64,24 +243,35 @@ impl Sandbox {
         fs::create_dir(&build_dir).map_err(Error::UnableToPrepareDir)?;
         set_permissions_open(&build_dir)?;
 
-        log::debug!("created new sandbox (dir: {:?})", scratch);
+        tracing::debug!("created new sandbox (dir: {:?})", scratch);
 
+        let audit = AuditLog::new(root_dir.join(AUDIT_LOG_FILE_NAME));
         Ok(Self {
+            id,
+            context,
             _scratch: scratch,
             root_dir,
             public_dir,
             src_dir,
             build_dir,
+            write_lock: Mutex::default(),
+            changes: ChangeFeed::default(),
+            audit,
         })
     }
 
-    pub fn create_from_template(template_path: &Path) -> Result<Self> {
-        let sandbox = Self::create_empty()?;
+    pub fn create_from_template(
+        template_path: &Path,
+        id: Uuid,
+        context: Arc<Context>,
+    ) -> Result<Self> {
+        let sandbox = Self::create_empty(id, context)?;
 
         copy_dir(&template_path.join(PUBLIC_DIR_NAME), &sandbox.public_dir)
             .map_err(Error::UnableToPrepareDir)?;
         copy_dir(&template_path.join(SRC_DIR_NAME), &sandbox.src_dir)
             .map_err(Error::UnableToPrepareDir)?;
+        sandbox.track_changes();
 
         Ok(sandbox)
     }
@@ -93,23 +283,135 @@ impl Sandbox {
         })
     }
 
-    pub fn write_to_file(&self, path: &Path, content: &str) -> Result<()> {
+    /// Invocations recorded for the sandbox, oldest first.
+    pub fn audit_log(&self) -> Result<Vec<AuditEntry>> {
+        self.audit.read()
+    }
+
+    /// Receive the files created and removed from now on.
+    pub fn subscribe_changes(&self) -> Receiver<Vec<FileChange>> {
+        self.changes.subscribe()
+    }
+
+    /// Publish the files created and removed since the last call.
+    /// Must be called with the write lock held so that no temporary files are
+    /// picked up.
+    fn track_changes(&self) {
+        let files = self.get_structure().map(|structure| {
+            structure
+                .file_paths()
+                .into_iter()
+                .map(String::from)
+                .collect::<BTreeSet<_>>()
+        });
+        match files {
+            Ok(files) => self.changes.update(files),
+            Err(err) => tracing::warn!("unable to track changes of sandbox {}: {}", self.id, err),
+        }
+    }
+
+    /// Number of bytes stored by the sandbox including the build output.
+    pub fn disk_usage(&self) -> Result<u64> {
+        let usage = Usage::of_dir(&self.root_dir).map_err(Error::UnableToReadFile)?;
+        Ok(usage.bytes)
+    }
+
+    pub fn quota(&self) -> &Quota {
+        &self.context.quota
+    }
+
+    /// Usage of the files which count towards the quota.
+    fn usage(&self) -> Result<Usage> {
+        let public = Usage::of_dir(&self.public_dir).map_err(Error::UnableToReadFile)?;
+        let src = Usage::of_dir(&self.src_dir).map_err(Error::UnableToReadFile)?;
+        Ok(public + src)
+    }
+
+    /// Make sure that writing `size` bytes to `path` doesn't exceed the
+    /// quota.
+    fn check_write_quota(&self, path: &Path, size: u64) -> Result<()> {
+        let quota = self.quota();
+        quota.check(QuotaKind::FileSize, size)?;
+
+        let mut usage = self.usage()?;
+        match fs::symlink_metadata(path) {
+            // the existing file is replaced, it may have grown since the
+            // usage was measured
+            Ok(metadata) => usage.bytes = usage.bytes.saturating_sub(metadata.len()),
+            Err(_) => usage.files += 1,
+        }
+        usage.bytes += size;
+
+        quota.check(QuotaKind::FileCount, usage.files)?;
+        quota.check(QuotaKind::TotalSize, usage.bytes)
+    }
+
+    /// Replace the content of a file if `precondition` holds.
+    /// The content is written to a temporary file which is then renamed, so
+    /// readers never see a partially written file.
+    /// Returns the version of the new content.
+    pub fn write_to_file(
+        &self,
+        path: &Path,
+        content: &[u8],
+        precondition: &Precondition,
+    ) -> Result<String> {
         let path = self.get_file_path(path)?;
-        fs::write(&path, content).map_err(Error::UnableToWriteFile)?;
+        let _guard = self.write_lock.lock().expect("sandbox write lock poisoned");
 
-        log::debug!(
-            "wrote {} bytes of source to {}",
-            content.len(),
-            path.display()
-        );
-        Ok(())
+        let current = self.current_version(&path)?;
+        if !precondition.holds(current.as_deref()) {
+            return Err(Error::VersionMismatch { current });
+        }
+        self.check_write_quota(&path, content.len() as u64)?;
+
+        let temp_path = temp_path_for(&path)?;
+        let result = self
+            .write_new_file(&temp_path, content)
+            .and_then(|_| fs::rename(&temp_path, &path).map_err(Error::UnableToWriteFile));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result?;
+
+        tracing::debug!("wrote {} bytes to {}", content.len(), path.display());
+        if current.is_none() {
+            self.track_changes();
+        }
+        Ok(content_version(content))
+    }
+
+    /// Create a new file with the given content.
+    fn write_new_file(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(path)
+            .map_err(Error::UnableToWriteFile)?;
+        verify_opened_file(&self.root_dir, &file)?;
+        set_file_permissions_writable(&file)?;
+        file.write_all(content).map_err(Error::UnableToWriteFile)
+    }
+
+    /// Version of the file's current content or `None` if it doesn't exist.
+    fn current_version(&self, path: &Path) -> Result<Option<String>> {
+        match fs::symlink_metadata(path) {
+            Ok(_) => {
+                let content = read_confined(&self.root_dir, path)?;
+                Ok(Some(content_version(&content)))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::UnableToReadFile(err)),
+        }
     }
 
     /// Get a path for either the "public" or "src" directory.
     /// The only guarantee is that the resulting path will be an absolute path
-    /// to a location within one of the two directories.
+    /// to a location within one of the two directories which doesn't pass
+    /// through a symlink.
     pub fn get_file_path(&self, path: &Path) -> Result<PathBuf> {
-        let path = safe_join_path(&self.root_dir, path)?;
+        let path = resolve_path(&self.root_dir, path)?;
 
         // make absolutely sure that the file is in either "public" or "src".
         if path.starts_with(&self.public_dir) || path.starts_with(&self.src_dir) {
@@ -119,138 +421,546 @@ impl Sandbox {
         }
     }
 
-    pub fn get_serve_path(&self, path: &Path) -> Result<PathBuf> {
-        let mut public_path = safe_join_path(&self.public_dir, path)?;
+    /// Read a file in either the "public" or "src" directory.
+    pub fn read_file(&self, path: &Path) -> Result<(PathBuf, Vec<u8>)> {
+        let path = self.get_file_path(path)?;
+        let content = read_confined(&self.root_dir, &path)?;
+        Ok((path, content))
+    }
+
+    /// Open a file served to the browser.
+    /// Files in the "public" directory take precedence over the build output.
+    pub fn open_serve_file(&self, path: &Path) -> Result<(PathBuf, File)> {
+        let mut public_path = resolve_path(&self.public_dir, path)?;
         if public_path.is_dir() {
             public_path.push("index.html");
         }
         if public_path.is_file() {
-            return Ok(public_path);
+            let file = open_confined(&self.public_dir, &public_path)?;
+            return Ok((public_path, file));
         }
-        safe_join_path(&self.build_dir, path)
+
+        // the build directory is written by the untrusted code
+        let build_path = resolve_path(&self.build_dir, path)?;
+        let file = open_confined(&self.build_dir, &build_path)?;
+        Ok((build_path, file))
     }
 
     pub fn get_tool_versions(&self) -> Result<ToolVersions> {
         // TODO use correct channel
-        commands::get_tool_versions(Channel::Stable)
+        commands::get_tool_versions(&*self.context.backend, Channel::Stable)
     }
 
     pub fn compile(&self, req: &CompileRequest) -> Result<CompileResponse> {
-        let command = self.compile_command(req.channel, req.mode, req);
-        let output = commands::run_with_timeout(command)?;
+        if let Some(units) = req.codegen_units {
+            if units == 0 || units > MAX_CODEGEN_UNITS {
+                return Err(Error::InvalidCodegenUnits(units));
+            }
+        }
+
+        let invocation = self.compile_invocation(req.channel, req);
+        let run = self.execute(&invocation)?;
+        self.check_build_quota()?;
+
+        let output = self.tool_output(run);
+        let wasm_size = if output.success {
+            self.find_build_wasm()
+                .ok()
+                .and_then(|path| fs::metadata(path).ok())
+                .map(|metadata| metadata.len())
+        } else {
+            None
+        };
 
-        let stdout = helpers::string_from_utf8_vec(output.stdout)?;
-        let stderr = helpers::string_from_utf8_vec(output.stderr)?;
+        Ok(CompileResponse { output, wasm_size })
+    }
+
+    pub fn check(&self, req: &CheckRequest) -> Result<CheckResponse> {
+        let overlay = self.create_src_overlay(&req.files)?;
+        let invocation =
+            self.check_invocation(req.channel, &overlay.path().join(SRC_DIR_NAME), req);
+        let output = self.execute_reporting_timeout(&invocation)?.output;
 
-        Ok(CompileResponse {
+        let stdout = helpers::string_from_utf8_lossy(output.stdout);
+        let messages = diagnostics::parse_cargo_messages(&stdout)?;
+
+        // the container runs with a TTY which merges stderr into stdout
+        let mut stderr = messages.other_output.into_bytes();
+        stderr.extend(output.stderr);
+        Ok(CheckResponse {
             success: output.status.success(),
-            stdout,
-            stderr,
+            diagnostics: messages.diagnostics,
+            stderr: self.truncated_output(stderr),
         })
     }
 
-    pub fn format(&self, req: &FormatRequest) -> Result<FormatResponse> {
-        let command = self.format_command(req);
-        let output = commands::run_with_timeout(command)?;
+    pub fn emit(&self, req: &EmitRequest) -> Result<EmitResponse> {
+        match req.target {
+            EmitTarget::Wat => self.emit_wat(req),
+            _ => self.emit_compiler_output(req),
+        }
+    }
 
-        Ok(FormatResponse {
-            success: output.status.success(),
-            stdout: helpers::string_from_utf8_vec(output.stdout)?,
-            stderr: helpers::string_from_utf8_vec(output.stderr)?,
+    fn emit_compiler_output(&self, req: &EmitRequest) -> Result<EmitResponse> {
+        let scratch = self.context.create_scratch_dir(&self.id, "emit")?;
+        let output_dir = scratch.path().join(EMIT_DIR_NAME);
+        fs::create_dir(&output_dir).map_err(Error::UnableToPrepareDir)?;
+        set_permissions_open(&output_dir)?;
+
+        let invocation = self.emit_invocation(req, &output_dir);
+        let output = self.tool_output(self.execute_reporting_timeout(&invocation)?);
+
+        let (code, code_truncated) = if output.success {
+            let file = open_confined(&output_dir, &output_dir.join(EMIT_FILE_NAME))?;
+            // read one byte more than the limit to find out whether it's truncated
+            let mut code = Vec::new();
+            file.take(MAX_EMIT_SIZE + 1)
+                .read_to_end(&mut code)
+                .map_err(Error::UnableToReadFile)?;
+            helpers::truncated_string_from_utf8_lossy(code, MAX_EMIT_SIZE)
+        } else {
+            (String::new(), false)
+        };
+
+        Ok(EmitResponse {
+            code,
+            code_truncated,
+            output,
+        })
+    }
+
+    fn emit_wat(&self, req: &EmitRequest) -> Result<EmitResponse> {
+        let wasm_path = self.find_build_wasm()?;
+        let invocation = self.build_output_invocation(
+            req.channel,
+            &commands::wasm2wat(&build_relative_path(&wasm_path)),
+        );
+        let mut run = self.execute_reporting_timeout(&invocation)?;
+
+        // the code isn't truncated, it's limited by the build quota instead
+        let code = helpers::string_from_utf8_lossy(mem::take(&mut run.output.stdout));
+        Ok(EmitResponse {
+            code,
+            code_truncated: false,
+            output: self.tool_output(run),
         })
     }
 
-    pub fn clippy(&self, req: &ClippyRequest) -> Result<ClippyResponse> {
-        let command = self.clippy_command(req);
-        let output = commands::run_with_timeout(command)?;
+    pub fn size(&self, req: &SizeRequest) -> Result<SizeResponse> {
+        let wasm_path = self.find_build_wasm()?;
+        let total_size = fs::metadata(&wasm_path)
+            .map_err(Error::UnableToReadFile)?
+            .len();
+        let relative_path = build_relative_path(&wasm_path);
 
-        Ok(ClippyResponse {
-            success: output.status.success(),
-            stdout: helpers::string_from_utf8_vec(output.stdout)?,
-            stderr: helpers::string_from_utf8_vec(output.stderr)?,
+        let invocation =
+            self.build_output_invocation(req.channel, &commands::twiggy_top(&relative_path));
+        let top = self.execute_reporting_timeout(&invocation)?.output;
+        if !top.status.success() {
+            return Ok(SizeResponse {
+                success: false,
+                report: None,
+                stderr: self.truncated_output(top.stderr),
+            });
+        }
+
+        let invocation =
+            self.build_output_invocation(req.channel, &commands::twiggy_dominators(&relative_path));
+        let dominators = self.execute_reporting_timeout(&invocation)?.output;
+        if !dominators.status.success() {
+            return Ok(SizeResponse {
+                success: false,
+                report: None,
+                stderr: self.truncated_output(dominators.stderr),
+            });
+        }
+
+        let report = size::parse_twiggy_output(
+            total_size,
+            &helpers::string_from_utf8_lossy(top.stdout),
+            &helpers::string_from_utf8_lossy(dominators.stdout),
+        )?;
+
+        Ok(SizeResponse {
+            success: true,
+            report: Some(report),
+            stderr: self.truncated_output(dominators.stderr),
         })
     }
 
-    pub fn macro_expand(&self, req: &MacroExpandRequest) -> Result<MacroExpandResponse> {
-        let command = self.macro_expand_command(req);
-        let output = commands::run_with_timeout(command)?;
+    /// Find the WebAssembly binary produced by the last build.
+    fn find_build_wasm(&self) -> Result<PathBuf> {
+        for entry in self
+            .build_dir
+            .read_dir()
+            .map_err(|_| Error::CorruptSandboxDir)?
+        {
+            let entry = entry.map_err(|_| Error::CorruptSandboxDir)?;
+            let entry_type = entry.file_type().map_err(|_| Error::CorruptSandboxDir)?;
+            let path = entry.path();
+            if entry_type.is_file() && path.extension().is_some_and(|ext| ext == "wasm") {
+                return Ok(path);
+            }
+        }
+
+        Err(Error::OutputMissing)
+    }
+
+    /// Make sure that the build output doesn't exceed the quota.
+    /// Oversized output is removed so it can't be served.
+    fn check_build_quota(&self) -> Result<()> {
+        let usage = Usage::of_dir(&self.build_dir).map_err(Error::UnableToReadFile)?;
+        let result = self.quota().check(QuotaKind::BuildSize, usage.bytes);
+        if result.is_err() {
+            tracing::info!("removing build output of {} bytes", usage.bytes);
+            clear_dir(&self.build_dir).map_err(Error::UnableToWriteFile)?;
+        }
+
+        result
+    }
+
+    /// Copy the audit log to the export directory if one is configured.
+    /// This happens automatically when the sandbox is dropped.
+    pub fn export_audit_log(&self) {
+        if let Some(export_dir) = &self.context.audit.export_dir {
+            let path = export_dir.join(format!("{}.jsonl", self.id.to_simple()));
+            if let Err(err) = self.audit.export(&path) {
+                tracing::warn!("unable to export the audit log of {}: {}", self.id, err);
+            }
+        }
+    }
+
+    pub fn format(&self, req: &FormatRequest) -> Result<ToolOutput> {
+        let invocation = self.format_invocation(req);
+        Ok(self.tool_output(self.execute(&invocation)?))
+    }
+
+    pub fn clippy(&self, req: &ClippyRequest) -> Result<ToolOutput> {
+        let invocation = self.clippy_invocation(req);
+        Ok(self.tool_output(self.execute(&invocation)?))
+    }
+
+    pub fn macro_expand(&self, req: &MacroExpandRequest) -> Result<ToolOutput> {
+        let invocation = self.macro_expand_invocation(req);
+        Ok(self.tool_output(self.execute(&invocation)?))
+    }
+
+    /// Decode output which is sent to the client, cutting it off if it exceeds
+    /// the quota.
+    fn truncated_output(&self, output: Vec<u8>) -> String {
+        helpers::truncated_string_from_utf8_lossy(output, self.quota().max_output_size).0
+    }
+
+    fn tool_output(&self, run: ToolRun) -> ToolOutput {
+        let ToolRun {
+            output,
+            usage,
+            elapsed,
+            timed_out,
+        } = run;
+        let limit = self.quota().max_output_size;
+        let (stdout, stdout_truncated) =
+            helpers::truncated_string_from_utf8_lossy(output.stdout, limit);
+        let (stderr, stderr_truncated) =
+            helpers::truncated_string_from_utf8_lossy(output.stderr, limit);
 
-        Ok(MacroExpandResponse {
+        ToolOutput {
             success: output.status.success(),
-            stdout: helpers::string_from_utf8_vec(output.stdout)?,
-            stderr: helpers::string_from_utf8_vec(output.stderr)?,
+            exit_code: output.status.code(),
+            signal: output.status.signal(),
+            timed_out,
+            wall_time_ms: elapsed.as_millis() as u64,
+            cpu_time_ms: usage.cpu_time.map(|time| time.as_millis() as u64),
+            peak_memory: usage.peak_memory,
+            stdout,
+            stderr,
+            stdout_truncated,
+            stderr_truncated,
+        }
+    }
+
+    fn execute(&self, invocation: &Invocation) -> Result<ToolRun> {
+        let span = tracing::info_span!(
+            "invocation",
+            tool = invocation.tool.name(),
+            session = %self.id.to_simple(),
+        );
+        let _entered = span.enter();
+        tracing::debug!(?invocation, "executing invocation");
+
+        // hashed up front because tools like rustfmt modify the files
+        let src_hash = invocation
+            .mounts
+            .iter()
+            .find(|mount| mount.name == SRC_DIR_NAME)
+            .and_then(|mount| match audit::hash_dir(&mount.host_path) {
+                Ok(hash) => Some(hash),
+                Err(err) => {
+                    tracing::warn!("unable to hash the source files: {}", err);
+                    None
+                }
+            });
+
+        let stats = &self.context.stats;
+        let slot = {
+            let _queued = stats.queue();
+            self.context.slots.acquire()
+        };
+        let running = stats.start(invocation.tool.name(), invocation.session);
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let execution = self.context.backend.execute(invocation);
+        let elapsed = start.elapsed();
+        drop(running);
+        drop(slot);
+
+        let outcome = match &execution {
+            Ok(execution) if execution.timed_out => Outcome::Timeout,
+            Ok(Execution { output, .. }) if output.status.success() => Outcome::Success,
+            Ok(_) => Outcome::Failure,
+            Err(_) => Outcome::Error,
+        };
+        stats.record(invocation.tool.name(), outcome, elapsed);
+        tracing::info!(
+            outcome = outcome.as_str(),
+            elapsed_ms = elapsed.as_millis() as u64,
+            "invocation finished"
+        );
+        let entry = audit_entry(
+            invocation, started_at, src_hash, &execution, elapsed, outcome,
+        );
+        if let Err(err) = self.audit.append(&entry) {
+            tracing::warn!("unable to append to the audit log: {}", err);
+        }
+
+        // the tool might have created or removed files
+        let modifies_files = invocation.mounts.iter().any(|mount| {
+            !mount.read_only
+                && (mount.host_path == self.src_dir || mount.host_path == self.public_dir)
+        });
+        if modifies_files {
+            let _guard = self.write_lock.lock().expect("sandbox write lock poisoned");
+            self.track_changes();
+        }
+
+        execution.map(|execution| ToolRun {
+            output: execution.output,
+            usage: execution.usage,
+            elapsed,
+            timed_out: execution.timed_out,
         })
     }
 
-    fn compile_command(
+    /// Execute an invocation whose output is useless unless the tool
+    /// finished, turning a timeout into an error.
+    fn execute_reporting_timeout(&self, invocation: &Invocation) -> Result<ToolRun> {
+        let run = self.execute(invocation)?;
+        if run.timed_out {
+            Err(Error::CompilerExecutionTimedOut {
+                timeout: PROCESS_TIMEOUT_SOFT,
+            })
+        } else {
+            Ok(run)
+        }
+    }
+
+    fn compile_invocation(
         &self,
         channel: Channel,
-        mode: Mode,
-        req: impl EditionRequest + BacktraceRequest,
-    ) -> Command {
-        let mut cmd = self.docker_command();
-        commands::set_execution_environment(&mut cmd, &req);
+        req: impl EditionRequest + BacktraceRequest + ProfileRequest,
+    ) -> Invocation {
+        let mut invocation = self.crate_invocation(Tool::Compiler(channel));
+        invocation.mount(&self.build_dir, BUILD_DIR_NAME);
+        commands::set_execution_environment(&mut invocation, &req);
+        invocation.apply_profile(&req);
 
-        let execution_cmd = commands::wasm_pack_build(channel, mode, BUILD_DIR_NAME);
+        invocation.args(&commands::wasm_pack_build(
+            channel,
+            req.mode(),
+            BUILD_DIR_NAME,
+        ));
 
-        cmd.arg(&helpers::container_name_for_channel(channel))
-            .args(&execution_cmd);
+        invocation
+    }
 
-        log::debug!("compile command: {:?}", cmd);
+    fn check_invocation(
+        &self,
+        channel: Channel,
+        src_dir: &Path,
+        req: impl EditionRequest,
+    ) -> Invocation {
+        let mut invocation = Invocation::for_session(Tool::Compiler(channel), self.id);
+        invocation.mount_read_only(src_dir, SRC_DIR_NAME);
+        invocation.apply_edition(req);
 
-        cmd
+        invocation.args(&commands::cargo_check(channel));
+
+        invocation
     }
 
-    fn format_command(&self, req: impl EditionRequest) -> Command {
-        let mut cmd = self.docker_command();
+    fn emit_invocation(&self, req: &EmitRequest, output_dir: &Path) -> Invocation {
+        let mut invocation = self.crate_invocation(Tool::Compiler(req.channel));
+        invocation.mount(output_dir, EMIT_DIR_NAME);
+        invocation.apply_edition(req);
+
+        let output_path = format!("{}/{}", EMIT_DIR_NAME, EMIT_FILE_NAME);
+        invocation.args(commands::cargo_rustc_emit(
+            req.channel,
+            req.mode,
+            req.target,
+            &output_path,
+        ));
+
+        invocation
+    }
+
+    /// Invocation of a tool which only operates on the build output.
+    fn build_output_invocation(&self, channel: Channel, args: &[String]) -> Invocation {
+        let mut invocation = Invocation::for_session(Tool::Compiler(channel), self.id);
+        invocation.mount_read_only(&self.build_dir, BUILD_DIR_NAME);
+        invocation.args(args);
+
+        invocation
+    }
+
+    fn format_invocation(&self, req: impl EditionRequest) -> Invocation {
+        // rustfmt is the only tool which needs to modify the source code
+        let mut invocation = Invocation::for_session(Tool::Rustfmt, self.id);
+        invocation.mount(&self.src_dir, SRC_DIR_NAME);
+        invocation.apply_edition(req);
+
+        invocation.args(["cargo", "fmt"]);
 
-        cmd.apply_edition(req);
+        invocation
+    }
 
-        cmd.arg("rustfmt").arg("cargo").arg("fmt");
+    fn clippy_invocation(&self, req: impl EditionRequest) -> Invocation {
+        let mut invocation = self.crate_invocation(Tool::Clippy);
+        invocation.apply_edition(&req);
 
-        log::debug!("format command: {:?}", cmd);
+        invocation.args(["cargo", "clippy"]);
 
-        cmd
+        invocation
     }
 
-    fn clippy_command(&self, req: impl EditionRequest) -> Command {
-        let mut cmd = self.docker_command();
-        cmd.apply_edition(&req);
+    fn macro_expand_invocation(&self, req: impl EditionRequest) -> Invocation {
+        let mut invocation = self.crate_invocation(Tool::CargoExpand);
+        invocation.apply_edition(req);
+
+        invocation.args(["cargo", "expand"]);
 
-        cmd.arg("clippy").arg("cargo").arg("clippy");
+        invocation
+    }
 
-        log::debug!("clippy command: {:?}", cmd);
+    /// Invocation with the "src" directory mounted read-only.
+    fn crate_invocation(&self, tool: Tool) -> Invocation {
+        let mut invocation = Invocation::for_session(tool, self.id);
+        invocation.mount_read_only(&self.src_dir, SRC_DIR_NAME);
 
-        cmd
+        invocation
     }
 
-    fn macro_expand_command(&self, req: impl EditionRequest) -> Command {
-        let mut cmd = self.docker_command();
-        cmd.apply_edition(req);
+    /// Create a temporary copy of the "src" directory with the given files
+    /// replaced.
+    /// The copy is located in the "src" directory of the returned `TempDir`.
+    fn create_src_overlay(&self, files: &[FileContent]) -> Result<TempDir> {
+        let quota = self.quota();
+        // checked up front so that oversized requests aren't written at all
+        for file in files {
+            quota.check(QuotaKind::FileSize, file.content.len() as u64)?;
+        }
+        let request_size = files.iter().map(|file| file.content.len() as u64).sum();
+        quota.check(QuotaKind::TotalSize, request_size)?;
 
-        cmd.arg("cargo-expand").arg("cargo").arg("expand");
+        let overlay = self.context.create_scratch_dir(&self.id, "overlay")?;
+        let src_dir = overlay.path().join(SRC_DIR_NAME);
+        fs::create_dir(&src_dir).map_err(Error::UnableToPrepareDir)?;
+        copy_dir(&self.src_dir, &src_dir).map_err(Error::UnableToPrepareDir)?;
 
-        log::debug!("macro expand command: {:?}", cmd);
+        for file in files {
+            let path = safe_join_path(overlay.path(), Path::new(&file.path))?;
+            if !path.starts_with(&src_dir) {
+                return Err(Error::InvalidPath(path));
+            }
 
-        cmd
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(Error::UnableToWriteFile)?;
+            }
+            fs::write(&path, &file.content).map_err(Error::UnableToWriteFile)?;
+        }
+
+        // the overlay takes the place of the saved "src" directory
+        let public = Usage::of_dir(&self.public_dir).map_err(Error::UnableToReadFile)?;
+        let src = Usage::of_dir(&src_dir).map_err(Error::UnableToReadFile)?;
+        let usage = public + src;
+        quota.check(QuotaKind::FileCount, usage.files)?;
+        quota.check(QuotaKind::TotalSize, usage.bytes)?;
+
+        Ok(overlay)
     }
+}
+
+/// Invocation which was executed by the backend.
+#[derive(Debug)]
+struct ToolRun {
+    output: Output,
+    usage: ResourceUsage,
+    elapsed: Duration,
+    timed_out: bool,
+}
 
-    fn docker_command(&self) -> Command {
-        let mut mount_input_file = self.src_dir.as_os_str().to_os_string();
-        mount_input_file.push(":");
-        mount_input_file.push("/playground/src");
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        self.export_audit_log();
+    }
+}
 
-        let mut mount_output_dir = self.build_dir.as_os_str().to_os_string();
-        mount_output_dir.push(":");
-        mount_output_dir.push("/playground/build");
+fn audit_entry(
+    invocation: &Invocation,
+    started_at: SystemTime,
+    src_hash: Option<String>,
+    execution: &Result<Execution>,
+    elapsed: Duration,
+    outcome: Outcome,
+) -> AuditEntry {
+    let timestamp = started_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let (status, peak_memory, error) = match execution {
+        Ok(execution) => (
+            Some(execution.output.status),
+            execution.usage.peak_memory,
+            None,
+        ),
+        Err(err) => (None, None, Some(err.to_string())),
+    };
 
-        let mut cmd = commands::docker_run();
-        cmd.arg("--volume")
-            .arg(&mount_input_file)
-            .arg("--volume")
-            .arg(&mount_output_dir);
+    AuditEntry {
+        timestamp,
+        request_id: invocation.request_id.clone(),
+        tool: invocation.tool.name().to_owned(),
+        args: invocation.args.clone(),
+        env: invocation.env.iter().cloned().collect(),
+        src_hash,
+        exit_code: status.and_then(|status| status.code()),
+        signal: status.and_then(|status| status.signal()),
+        duration_ms: elapsed.as_millis() as u64,
+        peak_memory,
+        timed_out: outcome == Outcome::Timeout,
+        error,
+    }
+}
 
-        cmd
+/// Get the session from the name of a scratch directory.
+/// Returns `None` if the name doesn't have the form `{session}-{suffix}`.
+fn scratch_dir_session(name: &str) -> Option<Uuid> {
+    let (id, suffix) = name.split_at(name.find('-')?);
+    // `TempDir` adds a random string to the suffix
+    let is_simple = id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit());
+    if is_simple && suffix.len() > 1 {
+        Uuid::parse_str(id).ok()
+    } else {
+        None
     }
 }
 
@@ -269,13 +979,120 @@ fn safe_join_path(base: &Path, rel: &Path) -> Result<PathBuf> {
     }
 }
 
+/// Safely join two paths and make sure the result doesn't pass through a
+/// symlink.
+/// `base` must be canonical.
+/// Components of the path which don't exist yet are allowed.
+fn resolve_path(base: &Path, rel: &Path) -> Result<PathBuf> {
+    let path = safe_join_path(base, rel)?;
+
+    let mut current = base.to_path_buf();
+    for comp in rel.components() {
+        current.push(comp);
+        match fs::symlink_metadata(&current) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(Error::InvalidPath(path));
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+            Err(err) => return Err(Error::UnableToReadFile(err)),
+        }
+    }
+
+    match path.canonicalize() {
+        Ok(canonical) if !canonical.starts_with(base) => Err(Error::InvalidPath(path)),
+        _ => Ok(path),
+    }
+}
+
+/// Open a regular file for reading without following symlinks and make sure
+/// it's located within `base`.
+fn open_confined(base: &Path, path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .map_err(Error::UnableToReadFile)?;
+    verify_opened_file(base, &file)?;
+
+    let metadata = file.metadata().map_err(Error::UnableToReadFile)?;
+    if metadata.is_file() {
+        Ok(file)
+    } else {
+        Err(Error::InvalidPath(path.to_owned()))
+    }
+}
+
+/// Read a file opened with `open_confined`.
+fn read_confined(base: &Path, path: &Path) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    open_confined(base, path)?
+        .read_to_end(&mut content)
+        .map_err(Error::UnableToReadFile)?;
+    Ok(content)
+}
+
+/// Make sure that the opened file is located within `base`.
+/// This catches directories which were replaced by a symlink after the path
+/// was resolved.
+fn verify_opened_file(base: &Path, file: &File) -> Result<()> {
+    let fd_path = format!("/proc/self/fd/{}", file.as_raw_fd());
+    let path = fs::read_link(&fd_path).map_err(Error::UnableToReadFile)?;
+    if path.starts_with(base) {
+        Ok(())
+    } else {
+        Err(Error::InvalidPath(path))
+    }
+}
+
+/// Get a unique path for a temporary file next to `path`.
+/// Being in the same directory makes sure that it can be renamed to `path`.
+fn temp_path_for(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+    Ok(path.with_file_name(format!(".{}.{}.tmp", file_name, Uuid::new_v4().to_simple())))
+}
+
+/// Get the path of a file in the build directory relative to the crate root.
+fn build_relative_path(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    format!("{}/{}", BUILD_DIR_NAME, file_name)
+}
+
 fn set_permissions_open(path: &Path) -> Result<()> {
     fs::set_permissions(path, Permissions::from_mode(0o777)).map_err(Error::UnableToSetPermissions)
 }
 
+/// Allow the tools, which run as a different user, to modify the file.
+fn set_file_permissions_writable(file: &File) -> Result<()> {
+    file.set_permissions(Permissions::from_mode(0o666))
+        .map_err(Error::UnableToSetPermissions)
+}
+
+/// Remove everything inside of the directory.
+fn clear_dir(path: &Path) -> io::Result<()> {
+    for entry in path.read_dir()? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Copy the files from `src` to `dst`.
 /// `dst` must already exist but further subdirectories from `src` are created
 /// automatically.
+/// The copied files are writable for everyone, see
+/// `set_file_permissions_writable`.
 fn copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
     let mut queue: VecDeque<(Cow<Path>, Cow<Path>)> = VecDeque::new();
     queue.push_back((Cow::from(src), Cow::from(dst)));
@@ -287,7 +1104,8 @@ fn copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
             let dst_path = dst.join(entry.file_name());
 
             if entry_type.is_file() {
-                fs::copy(&entry.path(), &dst_path)?;
+                fs::copy(entry.path(), &dst_path)?;
+                fs::set_permissions(&dst_path, Permissions::from_mode(0o666))?;
             } else if entry_type.is_dir() {
                 fs::create_dir(&dst_path)?;
                 queue.push_back((Cow::from(entry.path()), Cow::from(dst_path)));
@@ -0,0 +1,98 @@
+//! Limits for the files of a sandbox.
+
+use super::{Error, Result};
+use protocol::QuotaKind;
+use serde::Deserialize;
+use std::{collections::VecDeque, fs, io, path::Path};
+
+/// Limits for the files and the tool output of a sandbox.
+/// All sizes are in bytes.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    /// Maximum size of a single file.
+    pub max_file_size: u64,
+    /// Maximum number of files in the "public" and "src" directory.
+    pub max_files: u64,
+    /// Maximum size of all files in the "public" and "src" directory.
+    pub max_total_size: u64,
+    /// Maximum size of the build output.
+    pub max_build_size: u64,
+    /// Maximum size of the stdout and stderr of a tool each.
+    /// Longer output is cut off.
+    pub max_output_size: u64,
+}
+impl Default for Quota {
+    fn default() -> Self {
+        const MIB: u64 = 1024 * 1024;
+        Self {
+            max_file_size: 2 * MIB,
+            max_files: 256,
+            max_total_size: 16 * MIB,
+            max_build_size: 64 * MIB,
+            max_output_size: MIB,
+        }
+    }
+}
+impl Quota {
+    fn limit(&self, quota: QuotaKind) -> u64 {
+        use QuotaKind::*;
+        match quota {
+            FileSize => self.max_file_size,
+            FileCount => self.max_files,
+            TotalSize => self.max_total_size,
+            BuildSize => self.max_build_size,
+        }
+    }
+
+    /// Make sure that `value` doesn't exceed the limit for `quota`.
+    pub fn check(&self, quota: QuotaKind, value: u64) -> Result<()> {
+        let limit = self.limit(quota);
+        if value > limit {
+            Err(Error::QuotaExceeded { quota, limit })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Files stored in a directory.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Usage {
+    pub files: u64,
+    pub bytes: u64,
+}
+impl Usage {
+    /// Measure the usage of a directory including all subdirectories.
+    /// Symlinks are counted as files but never followed.
+    pub fn of_dir(path: &Path) -> io::Result<Self> {
+        let mut usage = Self::default();
+        let mut queue = VecDeque::new();
+        queue.push_back(path.to_owned());
+
+        while let Some(dir) = queue.pop_front() {
+            for entry in dir.read_dir()? {
+                let entry = entry?;
+                let metadata = fs::symlink_metadata(entry.path())?;
+                if metadata.is_dir() {
+                    queue.push_back(entry.path());
+                } else {
+                    usage.files += 1;
+                    usage.bytes += metadata.len();
+                }
+            }
+        }
+
+        Ok(usage)
+    }
+}
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            files: self.files + other.files,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::{Error, Result};
+use protocol::{CrateSize, FunctionSize, SizeReport};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Maximum number of functions included in the report.
+const MAX_FUNCTIONS: usize = 100;
+/// Name used for items which can't be attributed to a crate.
+const OTHER_CRATE_NAME: &str = "[other]";
+
+/// Row of `twiggy top --format json`.
+#[derive(Debug, Deserialize)]
+struct TopItem {
+    name: String,
+    shallow_size: u64,
+}
+
+/// Node of `twiggy dominators --format json`.
+#[derive(Debug, Deserialize)]
+struct DominatorItem {
+    name: String,
+    retained_size: u64,
+    #[serde(default)]
+    children: Vec<DominatorItem>,
+}
+
+fn collect_retained_sizes(items: Vec<DominatorItem>, sizes: &mut HashMap<String, u64>) {
+    for item in items {
+        sizes.insert(item.name, item.retained_size);
+        collect_retained_sizes(item.children, sizes);
+    }
+}
+
+/// Check whether the item is one of the summary rows twiggy adds to its
+/// output (ex. `Σ [42 Total Rows]`).
+fn is_summary_row(name: &str) -> bool {
+    name.starts_with("Σ [") || (name.starts_with("... and ") && name.ends_with(" more."))
+}
+
+/// Get the name of the crate an item belongs to.
+/// Items are named after their demangled path (ex.
+/// `<yew::html::Scope<T> as core::clone::Clone>::clone`) so the crate is the
+/// first path segment.
+fn crate_name_for_item(name: &str) -> &str {
+    let name = name.trim_start_matches(['<', '&']);
+    match name.find("::") {
+        Some(pos) if name[..pos].chars().all(|c| c.is_alphanumeric() || c == '_') => &name[..pos],
+        _ => OTHER_CRATE_NAME,
+    }
+}
+
+/// Build the report from the output of `twiggy top` and `twiggy dominators`.
+pub fn parse_twiggy_output(total_size: u64, top: &str, dominators: &str) -> Result<SizeReport> {
+    let mut top: Vec<TopItem> = serde_json::from_str(top).map_err(Error::UnableToParseSizes)?;
+    top.retain(|item| !is_summary_row(&item.name));
+    let dominators: Vec<DominatorItem> =
+        serde_json::from_str(dominators).map_err(Error::UnableToParseSizes)?;
+
+    let mut retained_sizes = HashMap::new();
+    collect_retained_sizes(dominators, &mut retained_sizes);
+
+    let mut crate_sizes: HashMap<&str, u64> = HashMap::new();
+    for item in &top {
+        *crate_sizes
+            .entry(crate_name_for_item(&item.name))
+            .or_default() += item.shallow_size;
+    }
+    let mut crates: Vec<CrateSize> = crate_sizes
+        .into_iter()
+        .map(|(name, size)| CrateSize {
+            name: name.to_owned(),
+            size,
+        })
+        .collect();
+    crates.sort_by(|a, b| b.size.cmp(&a.size));
+
+    let mut functions: Vec<FunctionSize> = top
+        .into_iter()
+        .map(|item| FunctionSize {
+            retained_size: retained_sizes.get(&item.name).copied(),
+            name: item.name,
+            shallow_size: item.shallow_size,
+        })
+        .collect();
+    functions.sort_by(|a, b| b.shallow_size.cmp(&a.shallow_size));
+    functions.truncate(MAX_FUNCTIONS);
+
+    Ok(SizeReport {
+        total_size,
+        functions,
+        crates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of `twiggy top --format json` for a small Yew app, shortened to a
+    /// few items.
+    const TOP_OUTPUT: &str = r#"[
+{"name":"\"function names\" subsection","shallow_size":9134,"shallow_size_percent":21.5},
+{"name":"dlmalloc::dlmalloc::Dlmalloc::malloc::h0c4d2a6e1f1a9b8c","shallow_size":4521,"shallow_size_percent":10.6},
+{"name":"<yew::html::Scope<COMP> as core::clone::Clone>::clone::h5e1b7c2f6d0a3e4b","shallow_size":1208,"shallow_size_percent":2.8},
+{"name":"yew::virtual_dom::vtag::VTag::apply::h2b9c8d7e6f5a4b3c","shallow_size":3410,"shallow_size_percent":8.0},
+{"name":"core::fmt::write::h8a7b6c5d4e3f2a1b","shallow_size":1012,"shallow_size_percent":2.4},
+{"name":"code[12]","shallow_size":212,"shallow_size_percent":0.5},
+{"name":"Σ [6 Total Rows]","shallow_size":19497,"shallow_size_percent":45.9}
+]"#;
+
+    /// Output of `twiggy dominators --format json` for the same items.
+    const DOMINATORS_OUTPUT: &str = r#"[
+{"name":"table[0]","shallow_size":102,"shallow_size_percent":0.2,"retained_size":9151,"retained_size_percent":21.5,"children":[
+{"name":"yew::virtual_dom::vtag::VTag::apply::h2b9c8d7e6f5a4b3c","shallow_size":3410,"shallow_size_percent":8.0,"retained_size":8620,"retained_size_percent":20.3,"children":[
+{"name":"dlmalloc::dlmalloc::Dlmalloc::malloc::h0c4d2a6e1f1a9b8c","shallow_size":4521,"shallow_size_percent":10.6,"retained_size":4521,"retained_size_percent":10.6}
+]}
+]},
+{"name":"\"function names\" subsection","shallow_size":9134,"shallow_size_percent":21.5,"retained_size":9134,"retained_size_percent":21.5}
+]"#;
+
+    #[test]
+    fn items_are_attributed_to_crates() {
+        assert_eq!(
+            crate_name_for_item("<yew::html::Scope<COMP> as core::clone::Clone>::clone"),
+            "yew"
+        );
+        assert_eq!(
+            crate_name_for_item("<&alloc::vec::Vec<T> as core::fmt::Debug>::fmt"),
+            "alloc"
+        );
+        assert_eq!(
+            crate_name_for_item("core::fmt::write::h8a7b6c5d4e3f2a1b"),
+            "core"
+        );
+        assert_eq!(crate_name_for_item("code[12]"), OTHER_CRATE_NAME);
+        assert_eq!(
+            crate_name_for_item("\"function names\" subsection"),
+            OTHER_CRATE_NAME
+        );
+        assert_eq!(
+            crate_name_for_item("<T as core::any::Any>::type_id"),
+            OTHER_CRATE_NAME
+        );
+    }
+
+    #[test]
+    fn report_combines_top_and_dominators() {
+        let report = parse_twiggy_output(42_468, TOP_OUTPUT, DOMINATORS_OUTPUT).unwrap();
+        assert_eq!(report.total_size, 42_468);
+
+        let functions: Vec<_> = report
+            .functions
+            .iter()
+            .map(|function| (function.shallow_size, function.retained_size))
+            .collect();
+        assert_eq!(
+            functions,
+            [
+                (9134, Some(9134)),
+                (4521, Some(4521)),
+                (3410, Some(8620)),
+                (1208, None),
+                (1012, None),
+                (212, None),
+            ]
+        );
+
+        let crates: Vec<_> = report
+            .crates
+            .iter()
+            .map(|krate| (krate.name.as_str(), krate.size))
+            .collect();
+        assert_eq!(
+            crates,
+            [
+                (OTHER_CRATE_NAME, 9346),
+                ("yew", 4618),
+                ("dlmalloc", 4521),
+                ("core", 1012),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_output_is_rejected() {
+        assert!(matches!(
+            parse_twiggy_output(0, "error: not a wasm file", "[]"),
+            Err(Error::UnableToParseSizes(_))
+        ));
+        assert!(matches!(
+            parse_twiggy_output(0, "[]", "{}"),
+            Err(Error::UnableToParseSizes(_))
+        ));
+    }
+}
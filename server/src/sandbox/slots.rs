@@ -0,0 +1,66 @@
+//! Limit on the number of tool invocations which run at once.
+
+use std::sync::{Condvar, Mutex};
+
+/// Hands out a limited number of slots, blocking until one is free.
+#[derive(Debug)]
+pub struct Slots {
+    limit: usize,
+    taken: Mutex<usize>,
+    freed: Condvar,
+}
+impl Slots {
+    /// Values below 1 are treated as 1.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            taken: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Wait until a slot is free and take it.
+    /// The slot is given back once the guard is dropped.
+    pub fn acquire(&self) -> SlotGuard<'_> {
+        let taken = self.taken.lock().expect("slots lock poisoned");
+        let mut taken = self
+            .freed
+            .wait_while(taken, |taken| *taken >= self.limit)
+            .expect("slots lock poisoned");
+        *taken += 1;
+        SlotGuard { slots: self }
+    }
+}
+
+pub struct SlotGuard<'a> {
+    slots: &'a Slots,
+}
+impl Drop for SlotGuard<'_> {
+    fn drop(&mut self) {
+        *self.slots.taken.lock().expect("slots lock poisoned") -= 1;
+        self.slots.freed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread, time::Duration};
+
+    #[test]
+    fn acquire_waits_for_a_free_slot() {
+        let slots = Slots::new(1);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            let first = slots.acquire();
+            scope.spawn(|| {
+                let _second = slots.acquire();
+                sender.send(()).unwrap();
+            });
+
+            assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+            drop(first);
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        });
+    }
+}
@@ -0,0 +1,199 @@
+//! Statistics about the tool invocations.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Upper bounds of the duration buckets in seconds.
+pub const DURATION_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 15.0, 30.0];
+
+/// How an invocation ended.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Outcome {
+    /// The tool exited successfully.
+    Success,
+    /// The tool reported an error, ex. a compilation error.
+    Failure,
+    /// The tool was killed because it took too long.
+    Timeout,
+    /// The tool couldn't be executed.
+    Error,
+}
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        use Outcome::*;
+        match self {
+            Success => "success",
+            Failure => "failure",
+            Timeout => "timeout",
+            Error => "error",
+        }
+    }
+}
+
+/// Distribution of durations using `DURATION_BUCKETS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Number of observations less than or equal to the bucket's bound.
+    pub buckets: Vec<u64>,
+    /// Sum of all observations in seconds.
+    pub sum: f64,
+    pub count: u64,
+}
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; DURATION_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (count, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Statistics of a single tool.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToolStats {
+    pub outcomes: BTreeMap<Outcome, u64>,
+    pub durations: Histogram,
+}
+
+/// Invocation which is currently running.
+#[derive(Clone, Debug)]
+pub struct RunningInvocation {
+    pub tool: &'static str,
+    pub session: Option<Uuid>,
+    pub started_at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct InvocationStats {
+    queued: AtomicU64,
+    next_id: AtomicU64,
+    running: Mutex<BTreeMap<u64, RunningInvocation>>,
+    tools: Mutex<BTreeMap<&'static str, ToolStats>>,
+}
+impl InvocationStats {
+    /// Count an invocation as waiting to run until the guard is dropped.
+    pub fn queue(&self) -> QueuedGuard<'_> {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        QueuedGuard { stats: self }
+    }
+
+    /// Number of invocations which are waiting to run.
+    pub fn queued(&self) -> u64 {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Count an invocation as running until the guard is dropped.
+    pub fn start(&self, tool: &'static str, session: Option<Uuid>) -> RunningGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let invocation = RunningInvocation {
+            tool,
+            session,
+            started_at: Instant::now(),
+        };
+        self.running
+            .lock()
+            .expect("stats lock poisoned")
+            .insert(id, invocation);
+        RunningGuard { stats: self, id }
+    }
+
+    pub fn record(&self, tool: &'static str, outcome: Outcome, duration: Duration) {
+        let mut tools = self.tools.lock().expect("stats lock poisoned");
+        let stats = tools.entry(tool).or_default();
+        *stats.outcomes.entry(outcome).or_default() += 1;
+        stats.durations.observe(duration);
+    }
+
+    /// Number of invocations which are currently running.
+    pub fn running(&self) -> u64 {
+        self.running.lock().expect("stats lock poisoned").len() as u64
+    }
+
+    /// Invocations which are currently running, oldest first.
+    pub fn running_invocations(&self) -> Vec<RunningInvocation> {
+        let running = self.running.lock().expect("stats lock poisoned");
+        running.values().cloned().collect()
+    }
+
+    /// Statistics of all tools which were invoked at least once.
+    pub fn tools(&self) -> BTreeMap<&'static str, ToolStats> {
+        self.tools.lock().expect("stats lock poisoned").clone()
+    }
+}
+
+pub struct QueuedGuard<'a> {
+    stats: &'a InvocationStats,
+}
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.stats.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct RunningGuard<'a> {
+    stats: &'a InvocationStats,
+    id: u64,
+}
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        let mut running = self.stats.running.lock().expect("stats lock poisoned");
+        running.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_invocations_are_counted() {
+        let stats = InvocationStats::default();
+        let first = stats.queue();
+        let second = stats.queue();
+        assert_eq!(stats.queued(), 2);
+        drop(first);
+        assert_eq!(stats.queued(), 1);
+        drop(second);
+        assert_eq!(stats.queued(), 0);
+    }
+
+    #[test]
+    fn durations_are_bucketed() {
+        let stats = InvocationStats::default();
+        {
+            let _running = stats.start("rustfmt", None);
+            assert_eq!(stats.running(), 1);
+            assert_eq!(stats.running_invocations()[0].tool, "rustfmt");
+            stats.record("rustfmt", Outcome::Success, Duration::from_millis(200));
+        }
+        assert_eq!(stats.running(), 0);
+        stats.record("rustfmt", Outcome::Timeout, Duration::from_secs(12));
+
+        let rustfmt = &stats.tools()["rustfmt"];
+        assert_eq!(rustfmt.outcomes[&Outcome::Success], 1);
+        assert_eq!(rustfmt.outcomes[&Outcome::Timeout], 1);
+        assert_eq!(rustfmt.durations.buckets, [0, 1, 1, 1, 1, 1, 1, 2, 2]);
+        assert_eq!(rustfmt.durations.count, 2);
+        assert!((rustfmt.durations.sum - 12.2).abs() < 1e-9);
+    }
+}
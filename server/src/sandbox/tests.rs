@@ -0,0 +1,766 @@
+use super::{
+    backend::{
+        fake::{self, FakeBackend},
+        Execution,
+        Invocation,
+        ResourceUsage,
+        Tool,
+    },
+    content_version,
+    AuditConfig,
+    Context,
+    Error,
+    Precondition,
+    Quota,
+    Sandbox,
+};
+use protocol::{
+    Channel,
+    CheckRequest,
+    ClippyRequest,
+    CompileRequest,
+    EmitRequest,
+    EmitTarget,
+    File,
+    FileChange,
+    FileContent,
+    FormatRequest,
+    Mode,
+    OptLevel,
+    QuotaKind,
+};
+use std::{
+    env,
+    fs,
+    io::Read,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tempdir::TempDir;
+use uuid::Uuid;
+
+const TEMPLATE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../template");
+
+fn create_context(backend: &Arc<FakeBackend>, scratch_dir: PathBuf, quota: Quota) -> Arc<Context> {
+    let context = Context::new(Arc::clone(backend) as _, scratch_dir, quota)
+        .expect("failed to create context");
+    Arc::new(context)
+}
+
+fn create_sandbox_with_quota(backend: &Arc<FakeBackend>, quota: Quota) -> Sandbox {
+    let context = create_context(backend, env::temp_dir().join("yew-playground-test"), quota);
+    Sandbox::create_from_template(Path::new(TEMPLATE_PATH), Uuid::new_v4(), context)
+        .expect("failed to create sandbox")
+}
+
+fn create_sandbox(backend: &Arc<FakeBackend>) -> Sandbox {
+    create_sandbox_with_quota(backend, Quota::default())
+}
+
+fn compile_request() -> CompileRequest {
+    CompileRequest {
+        channel: Channel::Stable,
+        mode: Mode::Release,
+        edition: None,
+        backtrace: false,
+        opt_level: None,
+        lto: None,
+        codegen_units: None,
+        wasm_opt: None,
+    }
+}
+
+fn mount_path<'a>(invocation: &'a Invocation, name: &str) -> &'a Path {
+    &invocation
+        .mounts
+        .iter()
+        .find(|mount| mount.name == name)
+        .unwrap_or_else(|| panic!("{} isn't mounted", name))
+        .host_path
+}
+
+#[test]
+fn compile_mounts_src_and_build() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+
+    let mut req = compile_request();
+    req.opt_level = Some(OptLevel::S);
+    let resp = sandbox.compile(&req).unwrap();
+    assert!(resp.output.success);
+    assert_eq!(resp.wasm_size, None);
+
+    let invocations = backend.invocations();
+    assert_eq!(invocations.len(), 1);
+    let invocation = &invocations[0];
+    assert_eq!(invocation.tool, Tool::Compiler(Channel::Stable));
+    assert_eq!(invocation.args[..2], ["wasm-pack", "build"]);
+    assert!(invocation.args.iter().any(|arg| arg == "--release"));
+    assert_eq!(
+        invocation.get_env("CARGO_PROFILE_RELEASE_OPT_LEVEL"),
+        Some("s")
+    );
+    assert_eq!(mount_path(invocation, "src"), sandbox.src_dir);
+    assert_eq!(mount_path(invocation, "build"), sandbox.build_dir);
+}
+
+#[test]
+fn compile_reports_wasm_size() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let build_dir = mount_path(invocation, "build");
+        fs::write(build_dir.join("playground_bg.wasm"), vec![0; 42]).unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let resp = sandbox.compile(&compile_request()).unwrap();
+    assert_eq!(resp.wasm_size, Some(42));
+}
+
+#[test]
+fn compile_rejects_invalid_codegen_units() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+
+    let mut req = compile_request();
+    req.codegen_units = Some(0);
+    assert!(matches!(
+        sandbox.compile(&req),
+        Err(Error::InvalidCodegenUnits(0))
+    ));
+    assert!(backend.invocations().is_empty());
+}
+
+#[test]
+fn check_uses_unsaved_files() {
+    const DIAGNOSTIC: &str = r#"{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables"},"level":"warning","spans":[{"file_name":"src/lib.rs","line_start":1,"line_end":1,"column_start":5,"column_end":6,"is_primary":true,"label":null}],"rendered":null}}"#;
+
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let src_dir = mount_path(invocation, "src");
+        let content = fs::read_to_string(src_dir.join("lib.rs")).unwrap();
+        assert_eq!(content, "let x = 5;");
+        fake::output(0, &format!("checking\n{}\n", DIAGNOSTIC), "")
+    }));
+    let sandbox = create_sandbox(&backend);
+    let original = fs::read_to_string(sandbox.src_dir.join("lib.rs")).unwrap();
+
+    let resp = sandbox
+        .check(&CheckRequest {
+            channel: Channel::Stable,
+            edition: None,
+            files: vec![FileContent {
+                path: "src/lib.rs".into(),
+                content: "let x = 5;".into(),
+            }],
+        })
+        .unwrap();
+    assert_eq!(resp.diagnostics.len(), 1);
+    assert_eq!(
+        resp.diagnostics[0].code.as_deref(),
+        Some("unused_variables")
+    );
+    // output which isn't JSON is cargo's stderr merged in by the TTY
+    assert_eq!(resp.stderr, "checking\n");
+
+    // the saved file must not be touched
+    let content = fs::read_to_string(sandbox.src_dir.join("lib.rs")).unwrap();
+    assert_eq!(content, original);
+}
+
+#[test]
+fn check_rejects_files_outside_src() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+
+    let req = CheckRequest {
+        channel: Channel::Stable,
+        edition: None,
+        files: vec![FileContent {
+            path: "public/index.html".into(),
+            content: String::new(),
+        }],
+    };
+    assert!(matches!(sandbox.check(&req), Err(Error::InvalidPath(_))));
+    assert!(backend.invocations().is_empty());
+}
+
+#[test]
+fn emit_reads_compiler_output() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let output_dir = mount_path(invocation, "output");
+        fs::write(output_dir.join("compilation"), "; llvm ir").unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let resp = sandbox
+        .emit(&EmitRequest {
+            channel: Channel::Stable,
+            mode: Mode::Debug,
+            edition: None,
+            target: EmitTarget::LlvmIr,
+        })
+        .unwrap();
+    assert!(resp.output.success);
+    assert_eq!(resp.code, "; llvm ir");
+    assert!(backend.invocations()[0]
+        .args
+        .iter()
+        .any(|arg| arg == "--emit=llvm-ir=output/compilation"));
+}
+
+#[test]
+fn emit_compiles_a_single_codegen_unit() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        // rustc ignores the output path if it writes one file per unit
+        let single_unit = invocation
+            .args
+            .windows(2)
+            .any(|args| args == ["-C", "codegen-units=1"]);
+        if single_unit {
+            let output_dir = mount_path(invocation, "output");
+            fs::write(output_dir.join("compilation"), "; llvm ir").unwrap();
+        }
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    for &target in &[EmitTarget::LlvmIr, EmitTarget::Asm] {
+        let resp = sandbox
+            .emit(&EmitRequest {
+                channel: Channel::Stable,
+                mode: Mode::Debug,
+                edition: None,
+                target,
+            })
+            .unwrap();
+        assert_eq!(resp.code, "; llvm ir");
+    }
+
+    for invocation in backend.invocations() {
+        let separator = invocation.args.iter().position(|arg| arg == "--").unwrap();
+        assert!(invocation.args[separator..]
+            .iter()
+            .any(|arg| arg == "codegen-units=1"));
+    }
+}
+
+#[test]
+fn emit_output_is_capped() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let output_dir = mount_path(invocation, "output");
+        let mut code = vec![b'a'; super::MAX_EMIT_SIZE as usize];
+        code.extend_from_slice(b"\xff cut off");
+        fs::write(output_dir.join("compilation"), code).unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let resp = sandbox
+        .emit(&EmitRequest {
+            channel: Channel::Stable,
+            mode: Mode::Debug,
+            edition: None,
+            target: EmitTarget::LlvmIr,
+        })
+        .unwrap();
+    assert!(resp.code_truncated);
+    assert_eq!(resp.code.len() as u64, super::MAX_EMIT_SIZE);
+}
+
+#[test]
+fn emit_output_is_decoded_lossily() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let output_dir = mount_path(invocation, "output");
+        fs::write(output_dir.join("compilation"), b"; \xff").unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let resp = sandbox
+        .emit(&EmitRequest {
+            channel: Channel::Stable,
+            mode: Mode::Debug,
+            edition: None,
+            target: EmitTarget::LlvmIr,
+        })
+        .unwrap();
+    assert!(!resp.code_truncated);
+    assert_eq!(resp.code, "; \u{fffd}");
+}
+
+#[test]
+fn tool_versions_are_parsed() {
+    let backend = Arc::new(FakeBackend::new(|invocation| match invocation.tool {
+        Tool::Compiler(_) => fake::output(
+            0,
+            "rustc 1.47.0 (18bf6b4f0 2020-10-07)\nbinary: rustc\ncommit-hash: \
+             18bf6b4f0\ncommit-date: 2020-10-07\nrelease: 1.47.0\n",
+            "",
+        ),
+        _ => fake::output(0, "tool 1.4.22-stable (6e1b5e2 2020-10-05)\n", ""),
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let versions = sandbox.get_tool_versions().unwrap();
+    assert_eq!(versions.rustc.release, "1.47.0");
+    assert_eq!(versions.rustc.commit_hash, "18bf6b4f0");
+    assert_eq!(versions.rustfmt.release, "1.4.22-stable");
+    assert_eq!(versions.clippy.commit_date, "2020-10-05");
+}
+
+#[test]
+fn docker_backend_requires_the_seccomp_profile() {
+    use super::backend::{DockerBackend, DockerConfig};
+
+    let config = |profile: &str| DockerConfig {
+        seccomp_profile: Some(PathBuf::from(profile)),
+        ..Default::default()
+    };
+    assert!(matches!(
+        DockerBackend::new(config("does/not/exist.json")),
+        Err(Error::UnableToReadSeccompProfile(..))
+    ));
+    // tests run in the directory of the crate
+    assert!(DockerBackend::new(config("../docker/seccomp.json")).is_ok());
+}
+
+#[test]
+fn file_path_is_confined() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+
+    assert!(sandbox.get_file_path(Path::new("src/lib.rs")).is_ok());
+    assert!(sandbox
+        .get_file_path(Path::new("public/index.html"))
+        .is_ok());
+    assert!(sandbox.get_file_path(Path::new("build/app.wasm")).is_err());
+    assert!(sandbox.get_file_path(Path::new("../src/lib.rs")).is_err());
+    assert!(sandbox.get_file_path(Path::new("/etc/passwd")).is_err());
+}
+
+#[test]
+fn invocations_carry_the_session() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+
+    sandbox.compile(&compile_request()).unwrap();
+    assert_eq!(backend.invocations()[0].session, Some(sandbox.id));
+}
+
+#[test]
+fn reconcile_removes_orphaned_scratch_dirs() {
+    let scratch_root = TempDir::new("yew-playground-test").unwrap();
+    let backend = Arc::new(FakeBackend::succeeding());
+    let context = create_context(&backend, scratch_root.path().to_owned(), Quota::default());
+
+    let known_id = Uuid::new_v4();
+    let sandbox =
+        Sandbox::create_from_template(Path::new(TEMPLATE_PATH), known_id, Arc::clone(&context))
+            .unwrap();
+    let orphan = scratch_root
+        .path()
+        .join(format!("{}-sandbox.abc", Uuid::new_v4().to_simple()));
+    fs::create_dir(&orphan).unwrap();
+    // entries not created by the sandboxes are left alone
+    let unrelated = scratch_root.path().join("unrelated");
+    fs::write(&unrelated, "").unwrap();
+    let almost = scratch_root
+        .path()
+        .join(format!("{}-", Uuid::new_v4().to_simple()));
+    fs::create_dir(&almost).unwrap();
+
+    context.reconcile(|id| *id == known_id).unwrap();
+    assert!(sandbox.root_dir.exists());
+    assert!(!orphan.exists());
+    assert!(unrelated.exists());
+    assert!(almost.exists());
+}
+
+#[test]
+fn serve_file_prefers_public() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+    fs::write(sandbox.build_dir.join("playground.js"), "// js").unwrap();
+
+    let (path, _) = sandbox.open_serve_file(Path::new("")).unwrap();
+    assert_eq!(path, sandbox.public_dir.join("index.html"));
+    let (path, mut file) = sandbox.open_serve_file(Path::new("playground.js")).unwrap();
+    assert_eq!(path, sandbox.build_dir.join("playground.js"));
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "// js");
+}
+
+#[test]
+fn serve_file_refuses_symlinks() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+    symlink("/etc/passwd", sandbox.build_dir.join("passwd")).unwrap();
+    symlink("/", sandbox.build_dir.join("dir")).unwrap();
+
+    assert!(sandbox.open_serve_file(Path::new("passwd")).is_err());
+    assert!(sandbox
+        .open_serve_file(Path::new("dir/etc/passwd"))
+        .is_err());
+}
+
+#[test]
+fn sandbox_files_refuse_symlinks() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+    let outside = TempDir::new("yew-playground-test").unwrap();
+    let target = outside.path().join("target.rs");
+    fs::write(&target, "untouched").unwrap();
+
+    let lib_path = sandbox.src_dir.join("lib.rs");
+    fs::remove_file(&lib_path).unwrap();
+    symlink(&target, &lib_path).unwrap();
+
+    assert!(sandbox.read_file(Path::new("src/lib.rs")).is_err());
+    assert!(sandbox
+        .write_to_file(Path::new("src/lib.rs"), b"overwritten", &Precondition::Any)
+        .is_err());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "untouched");
+}
+
+#[test]
+fn emit_refuses_symlinked_output() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let output_dir = mount_path(invocation, "output");
+        symlink("/etc/passwd", output_dir.join("compilation")).unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let resp = sandbox.emit(&EmitRequest {
+        channel: Channel::Stable,
+        mode: Mode::Debug,
+        edition: None,
+        target: EmitTarget::LlvmIr,
+    });
+    assert!(resp.is_err());
+}
+
+#[test]
+fn only_rustfmt_can_modify_src() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+
+    sandbox.format(&FormatRequest { edition: None }).unwrap();
+    sandbox.clippy(&ClippyRequest { edition: None }).unwrap();
+    sandbox.compile(&compile_request()).unwrap();
+
+    let invocations = backend.invocations();
+    let src_read_only: Vec<bool> = invocations
+        .iter()
+        .map(|invocation| {
+            invocation
+                .mounts
+                .iter()
+                .find(|mount| mount.name == "src")
+                .unwrap()
+                .read_only
+        })
+        .collect();
+    assert_eq!(src_read_only, [false, true, true]);
+}
+
+#[test]
+fn writes_respect_the_quota() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox_with_quota(
+        &backend,
+        Quota {
+            max_file_size: 8,
+            ..Quota::default()
+        },
+    );
+    let template_usage = sandbox.usage().unwrap();
+
+    assert!(matches!(
+        sandbox.write_to_file(Path::new("public/big.txt"), &[0; 9], &Precondition::Any),
+        Err(Error::QuotaExceeded {
+            quota: QuotaKind::FileSize,
+            limit: 8,
+        })
+    ));
+    assert!(!sandbox.public_dir.join("big.txt").exists());
+    sandbox
+        .write_to_file(Path::new("public/small.txt"), &[0; 8], &Precondition::Any)
+        .unwrap();
+
+    let sandbox = create_sandbox_with_quota(
+        &backend,
+        Quota {
+            max_files: template_usage.files,
+            ..Quota::default()
+        },
+    );
+    assert!(matches!(
+        sandbox.write_to_file(Path::new("public/new.txt"), b"", &Precondition::Any),
+        Err(Error::QuotaExceeded {
+            quota: QuotaKind::FileCount,
+            ..
+        })
+    ));
+    // replacing a file doesn't change the number of files
+    sandbox
+        .write_to_file(Path::new("src/lib.rs"), b"fn main() {}", &Precondition::Any)
+        .unwrap();
+}
+
+#[test]
+fn check_overlay_respects_the_quota() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox_with_quota(
+        &backend,
+        Quota {
+            max_file_size: 8,
+            ..Quota::default()
+        },
+    );
+    let request = |content: &str| CheckRequest {
+        channel: Channel::Stable,
+        edition: None,
+        files: vec![FileContent {
+            path: "src/new.rs".into(),
+            content: content.into(),
+        }],
+    };
+
+    assert!(matches!(
+        sandbox.check(&request("123456789")),
+        Err(Error::QuotaExceeded {
+            quota: QuotaKind::FileSize,
+            limit: 8,
+        })
+    ));
+    assert!(backend.invocations().is_empty());
+
+    let template_usage = sandbox.usage().unwrap();
+    let sandbox = create_sandbox_with_quota(
+        &backend,
+        Quota {
+            max_files: template_usage.files,
+            ..Quota::default()
+        },
+    );
+    assert!(matches!(
+        sandbox.check(&request("")),
+        Err(Error::QuotaExceeded {
+            quota: QuotaKind::FileCount,
+            ..
+        })
+    ));
+    assert!(backend.invocations().is_empty());
+}
+
+#[test]
+fn writes_check_the_version() {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let sandbox = create_sandbox(&backend);
+    let path = Path::new("src/lib.rs");
+    let (_, original) = sandbox.read_file(path).unwrap();
+    let version = content_version(&original);
+
+    let new_version = sandbox
+        .write_to_file(path, b"mine", &Precondition::Version(version.clone()))
+        .unwrap();
+    assert_eq!(new_version, content_version(b"mine"));
+
+    // the second tab still has the original version
+    match sandbox.write_to_file(path, b"theirs", &Precondition::Version(version)) {
+        Err(Error::VersionMismatch { current }) => assert_eq!(current, Some(new_version)),
+        other => panic!("expected a version mismatch, got {:?}", other),
+    }
+    assert_eq!(sandbox.read_file(path).unwrap().1, b"mine");
+
+    assert!(matches!(
+        sandbox.write_to_file(path, b"new", &Precondition::Missing),
+        Err(Error::VersionMismatch { .. })
+    ));
+    sandbox
+        .write_to_file(Path::new("src/new.rs"), b"new", &Precondition::Missing)
+        .unwrap();
+
+    // no temporary files are left behind
+    let mut names: Vec<_> = fs::read_dir(&sandbox.src_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["lib.rs", "new.rs"]);
+}
+
+#[test]
+fn oversized_build_output_is_removed() {
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let build_dir = mount_path(invocation, "build");
+        fs::write(build_dir.join("playground_bg.wasm"), vec![0; 42]).unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox_with_quota(
+        &backend,
+        Quota {
+            max_build_size: 41,
+            ..Quota::default()
+        },
+    );
+
+    assert!(matches!(
+        sandbox.compile(&compile_request()),
+        Err(Error::QuotaExceeded {
+            quota: QuotaKind::BuildSize,
+            limit: 41,
+        })
+    ));
+    assert!(sandbox.build_dir.exists());
+    assert!(fs::read_dir(&sandbox.build_dir).unwrap().next().is_none());
+}
+
+#[test]
+fn file_changes_are_published() {
+    // the formatter replaces "new.rs" with "renamed.rs"
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        let src = mount_path(invocation, "src");
+        fs::rename(src.join("new.rs"), src.join("renamed.rs")).unwrap();
+        fake::output(0, "", "")
+    }));
+    let sandbox = create_sandbox(&backend);
+    let changes = sandbox.subscribe_changes();
+
+    sandbox
+        .write_to_file(Path::new("src/lib.rs"), b"", &Precondition::Any)
+        .unwrap();
+    assert!(changes.try_recv().is_err());
+
+    sandbox
+        .write_to_file(Path::new("src/new.rs"), b"", &Precondition::Missing)
+        .unwrap();
+    assert_eq!(
+        changes.try_recv().unwrap(),
+        [FileChange::Created {
+            file: File::from_path("src/new.rs")
+        }]
+    );
+
+    sandbox.format(&FormatRequest { edition: None }).unwrap();
+    assert_eq!(
+        changes.try_recv().unwrap(),
+        [
+            FileChange::Removed {
+                path: String::from("src/new.rs")
+            },
+            FileChange::Created {
+                file: File::from_path("src/renamed.rs")
+            },
+        ]
+    );
+    assert!(changes.try_recv().is_err());
+}
+
+#[test]
+fn invocations_are_audited() {
+    // the formatter empties "lib.rs", so the hash recorded for the next
+    // invocation changes
+    let backend = Arc::new(FakeBackend::new(|invocation| {
+        if invocation.tool == Tool::Rustfmt {
+            fs::write(mount_path(invocation, "src").join("lib.rs"), "").unwrap();
+        }
+        Execution {
+            output: fake::output(1, "", ""),
+            usage: ResourceUsage {
+                cpu_time: None,
+                peak_memory: Some(4096),
+            },
+            timed_out: false,
+        }
+    }));
+    let export_dir = TempDir::new("yew-playground-audit").unwrap();
+    let context = Context::new(
+        Arc::clone(&backend) as _,
+        env::temp_dir().join("yew-playground-test"),
+        Quota::default(),
+    )
+    .unwrap()
+    .with_audit(AuditConfig {
+        export_dir: Some(export_dir.path().to_owned()),
+    });
+    let id = Uuid::new_v4();
+    let sandbox =
+        Sandbox::create_from_template(Path::new(TEMPLATE_PATH), id, Arc::new(context)).unwrap();
+
+    sandbox.format(&FormatRequest { edition: None }).unwrap();
+    sandbox.clippy(&ClippyRequest { edition: None }).unwrap();
+
+    let entries = sandbox.audit_log().unwrap();
+    let tools = entries
+        .iter()
+        .map(|entry| entry.tool.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(tools, ["rustfmt", "clippy"]);
+    assert!(entries.iter().all(|entry| entry.exit_code == Some(1)));
+    assert!(entries.iter().all(|entry| entry.peak_memory == Some(4096)));
+    assert!(entries[0].src_hash.is_some());
+    assert_ne!(entries[0].src_hash, entries[1].src_hash);
+
+    drop(sandbox);
+    let exported =
+        fs::read_to_string(export_dir.path().join(format!("{}.jsonl", id.to_simple()))).unwrap();
+    assert_eq!(exported.lines().count(), 2);
+}
+
+#[test]
+fn tool_output_is_decoded_lossily_and_truncated() {
+    let backend = Arc::new(FakeBackend::new(|_| {
+        let mut output = fake::output(101, "", "");
+        output.stdout = b"invalid \xff".to_vec();
+        // "ä" is cut in half by the quota
+        output.stderr = "aaaaaaaaaä".as_bytes().to_vec();
+        output
+    }));
+    let sandbox = create_sandbox_with_quota(
+        &backend,
+        Quota {
+            max_output_size: 10,
+            ..Quota::default()
+        },
+    );
+
+    let output = sandbox.clippy(&ClippyRequest { edition: None }).unwrap();
+    assert!(!output.success);
+    assert_eq!(output.exit_code, Some(101));
+    assert_eq!(output.signal, None);
+    assert!(!output.timed_out);
+    assert_eq!(output.stdout, "invalid \u{fffd}");
+    assert!(!output.stdout_truncated);
+    assert_eq!(output.stderr, "aaaaaaaaa");
+    assert!(output.stderr_truncated);
+}
+
+#[test]
+fn timeouts_are_reported() {
+    let backend = Arc::new(FakeBackend::new(|_| Execution {
+        timed_out: true,
+        ..Execution::from(fake::output(124, "", ""))
+    }));
+    let sandbox = create_sandbox(&backend);
+
+    let output = sandbox.clippy(&ClippyRequest { edition: None }).unwrap();
+    assert!(!output.success);
+    assert!(output.timed_out);
+    assert!(sandbox.audit_log().unwrap()[0].timed_out);
+
+    // the diagnostics are incomplete
+    let req = CheckRequest {
+        channel: Channel::Stable,
+        edition: None,
+        files: Vec::new(),
+    };
+    assert!(matches!(
+        sandbox.check(&req),
+        Err(Error::CompilerExecutionTimedOut { .. })
+    ));
+}
@@ -0,0 +1,140 @@
+//! Versions of sandbox files used to detect conflicting writes.
+
+use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+/// Compute the version of a file's content.
+/// The hash only needs to be stable while the server is running because
+/// sandboxes don't survive a restart.
+pub fn content_version(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(content);
+    format!("{:016x}-{:x}", hasher.finish(), content.len())
+}
+
+/// Format the version of a file as a strong entity tag.
+pub fn entity_tag(version: &str) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Expected state of a file before it's written.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Precondition {
+    /// The file is written regardless of its current content.
+    Any,
+    /// The file must not exist yet.
+    Missing,
+    /// The file must exist, regardless of its content.
+    Exists,
+    /// The current content of the file must have this version.
+    Version(String),
+    /// The current content of the file must have one of these versions.
+    /// An empty list never holds.
+    OneOf(Vec<String>),
+}
+impl Precondition {
+    /// Create the precondition for the value of an `If-Match` header.
+    ///
+    /// Follows RFC 7232: `*` matches any existing file and a list of entity
+    /// tags uses the strong comparison, so weak tags and malformed values
+    /// never match.
+    pub fn from_if_match(value: &str) -> Self {
+        let value = value.trim();
+        if value == "*" {
+            return Self::Exists;
+        }
+
+        let versions = parse_entity_tags(value)
+            .map(|tags| {
+                tags.into_iter()
+                    .filter(|(weak, _)| !weak)
+                    .map(|(_, tag)| tag.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self::OneOf(versions)
+    }
+
+    /// Check the precondition against the version of the current content.
+    pub fn holds(&self, current: Option<&str>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Missing => current.is_none(),
+            Self::Exists => current.is_some(),
+            Self::Version(expected) => current == Some(expected.as_str()),
+            Self::OneOf(expected) => {
+                current.is_some_and(|current| expected.iter().any(|v| v == current))
+            }
+        }
+    }
+}
+
+/// Parse a comma-separated list of entity tags.
+/// Returns the opaque tags along with whether they're weak or `None` if the
+/// list is malformed.
+fn parse_entity_tags(mut value: &str) -> Option<Vec<(bool, &str)>> {
+    let mut tags = Vec::new();
+    loop {
+        value = value.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+        if value.is_empty() {
+            return Some(tags);
+        }
+
+        let (weak, rest) = match value.strip_prefix("W/") {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        // the opaque tag may contain commas but no quotes
+        let rest = rest.strip_prefix('"')?;
+        let end = rest.find('"')?;
+        tags.push((weak, &rest[..end]));
+
+        value = &rest[end + 1..];
+        if !value.is_empty() && !value.starts_with(|c: char| c == ',' || c.is_ascii_whitespace()) {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_match_star_requires_an_existing_file() {
+        let precondition = Precondition::from_if_match(" * ");
+        assert_eq!(precondition, Precondition::Exists);
+        assert!(precondition.holds(Some("abc")));
+        assert!(!precondition.holds(None));
+    }
+
+    #[test]
+    fn if_match_accepts_a_list_of_entity_tags() {
+        let precondition = Precondition::from_if_match(r#""abc", "d,e" ,"f""#);
+        assert_eq!(
+            precondition,
+            Precondition::OneOf(vec!["abc".to_owned(), "d,e".to_owned(), "f".to_owned()])
+        );
+        assert!(precondition.holds(Some("abc")));
+        assert!(precondition.holds(Some("d,e")));
+        assert!(!precondition.holds(Some("d")));
+        assert!(!precondition.holds(None));
+    }
+
+    #[test]
+    fn weak_entity_tags_never_match() {
+        let precondition = Precondition::from_if_match(r#"W/"abc", "def""#);
+        assert!(!precondition.holds(Some("abc")));
+        assert!(precondition.holds(Some("def")));
+
+        assert!(!Precondition::from_if_match(r#"W/"abc""#).holds(Some("abc")));
+    }
+
+    #[test]
+    fn malformed_if_match_never_matches() {
+        for value in &["abc", r#""abc"#, r#""abc"def"#, r#""abc" x"#, ""] {
+            let precondition = Precondition::from_if_match(value);
+            assert_eq!(precondition, Precondition::OneOf(Vec::new()), "{}", value);
+            assert!(!precondition.holds(Some("abc")));
+        }
+    }
+}
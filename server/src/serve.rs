@@ -17,10 +17,10 @@ impl SPAStaticFiles {
         Self { index_path, inner }
     }
 }
-impl Into<Vec<Route>> for SPAStaticFiles {
-    fn into(self) -> Vec<Route> {
-        let handler = Box::new(self.clone());
-        let routes: Vec<Route> = self.inner.into();
+impl From<SPAStaticFiles> for Vec<Route> {
+    fn from(files: SPAStaticFiles) -> Self {
+        let handler = Box::new(files.clone());
+        let routes: Vec<Route> = files.inner.into();
         routes
             .into_iter()
             .map(|mut route| {
@@ -0,0 +1,212 @@
+use super::*;
+use protocol::{Channel, EmitTarget, Mode};
+use rate_limit::RateLimitConfig;
+use rocket::local::Client;
+use sandbox::{FakeBackend, Quota};
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+};
+
+const TEMPLATE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../template");
+
+/// Create a client for the tool endpoints and a session to run them in.
+/// Returns the client along with the id and the owner token of the session.
+fn create_client() -> (Client, String, String) {
+    let backend = Arc::new(FakeBackend::succeeding());
+    let context = Context::new(
+        backend,
+        env::temp_dir().join("yew-playground-test"),
+        Quota::default(),
+    )
+    .map(Arc::new)
+    .expect("failed to create context");
+    let janitor = Arc::new(Janitor::default());
+    let session = janitor
+        .create_session(|id| {
+            Sandbox::create_from_template(Path::new(TEMPLATE_PATH), id, Arc::clone(&context))
+        })
+        .expect("failed to create session");
+
+    let rocket = rocket::ignite()
+        .manage(janitor)
+        .manage(Arc::new(Hub::new(1)))
+        .manage(Arc::new(RateLimiter::new(Default::default())))
+        .mount(
+            "/api",
+            rocket::routes![
+                api_upload_file,
+                api_check,
+                api_emit,
+                api_size,
+                api_clippy,
+                api_macro_expand
+            ],
+        );
+    let client = Client::new(rocket).expect("invalid rocket instance");
+    let id = session.get_id_string();
+    let owner_token = session.owner_token().to_owned();
+    (client, id, owner_token)
+}
+
+/// Paths and bodies of the endpoints which run tools in the session.
+fn tool_requests() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "check",
+            serde_json::to_string(&CheckRequest {
+                channel: Channel::Stable,
+                edition: None,
+                files: Vec::new(),
+            })
+            .unwrap(),
+        ),
+        (
+            "emit",
+            serde_json::to_string(&EmitRequest {
+                channel: Channel::Stable,
+                mode: Mode::Debug,
+                edition: None,
+                target: EmitTarget::LlvmIr,
+            })
+            .unwrap(),
+        ),
+        (
+            "size",
+            serde_json::to_string(&SizeRequest {
+                channel: Channel::Stable,
+            })
+            .unwrap(),
+        ),
+        (
+            "clippy",
+            serde_json::to_string(&ClippyRequest { edition: None }).unwrap(),
+        ),
+        (
+            "macro-expand",
+            serde_json::to_string(&MacroExpandRequest { edition: None }).unwrap(),
+        ),
+    ]
+}
+
+#[test]
+fn tools_require_the_owner_token() {
+    let (client, id, _) = create_client();
+
+    for (tool, body) in tool_requests() {
+        let url = format!("/api/{}/{}", id, tool);
+        let resp = client
+            .post(url.clone())
+            .header(ContentType::JSON)
+            .body(&body)
+            .dispatch();
+        assert_eq!(resp.status(), Status::Forbidden, "{} without a token", tool);
+
+        let resp = client
+            .post(url)
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", "Bearer not-the-owner"))
+            .body(&body)
+            .dispatch();
+        assert_eq!(
+            resp.status(),
+            Status::Forbidden,
+            "{} with another token",
+            tool
+        );
+    }
+}
+
+#[test]
+fn owner_can_run_tools() {
+    let (client, id, owner_token) = create_client();
+
+    for tool in &["check", "clippy", "macro-expand"] {
+        let (_, body) = tool_requests()
+            .into_iter()
+            .find(|(name, _)| name == tool)
+            .unwrap();
+        let resp = client
+            .post(format!("/api/{}/{}", id, tool))
+            .header(ContentType::JSON)
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", owner_token),
+            ))
+            .body(&body)
+            .dispatch();
+        assert_eq!(resp.status(), Status::Ok, "{} with the owner token", tool);
+    }
+}
+
+/// Upload a file with the given `If-Match` header.
+fn upload_if_match(client: &Client, id: &str, owner_token: &str, path: &str, etag: &str) -> Status {
+    client
+        .put(format!("/api/{}/files/{}", id, path))
+        .header(Header::new(
+            "Authorization",
+            format!("Bearer {}", owner_token),
+        ))
+        .header(Header::new("If-Match", etag.to_owned()))
+        .body("fn main() {}")
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn if_match_star_requires_an_existing_file() {
+    let (client, id, owner_token) = create_client();
+
+    let status = upload_if_match(&client, &id, &owner_token, "src/lib.rs", "*");
+    assert_eq!(status, Status::Ok);
+    let status = upload_if_match(&client, &id, &owner_token, "src/new.rs", "*");
+    assert_eq!(status, Status::PreconditionFailed);
+}
+
+#[test]
+fn if_match_accepts_any_strong_tag_of_a_list() {
+    let (client, id, owner_token) = create_client();
+    let resp = client
+        .put(format!("/api/{}/files/src/lib.rs", id))
+        .header(Header::new(
+            "Authorization",
+            format!("Bearer {}", owner_token),
+        ))
+        .header(Header::new("If-Match", "*"))
+        .body("fn main() {}")
+        .dispatch();
+    let etag = resp.headers().get_one("ETag").unwrap().to_owned();
+
+    // the same tag as a weak one never matches
+    let weak = format!("\"other\", W/{}", etag);
+    let status = upload_if_match(&client, &id, &owner_token, "src/lib.rs", &weak);
+    assert_eq!(status, Status::PreconditionFailed);
+
+    let list = format!("\"other\", {}", etag);
+    let status = upload_if_match(&client, &id, &owner_token, "src/lib.rs", &list);
+    assert_eq!(status, Status::Ok);
+}
+
+#[test]
+fn request_ids_are_only_taken_from_trusted_proxies() {
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+    let limiter = RateLimiter::new(RateLimitConfig {
+        trusted_proxies: vec![proxy],
+        ..Default::default()
+    });
+    let rocket = rocket::ignite()
+        .attach(trace::RequestTracing)
+        .manage(Arc::new(limiter));
+    let client = Client::new(rocket).expect("invalid rocket instance");
+
+    let request_id = |remote: IpAddr| {
+        let resp = client
+            .get("/")
+            .remote(SocketAddr::new(remote, 1234))
+            .header(Header::new("X-Request-Id", "chosen-id"))
+            .dispatch();
+        resp.headers().get_one("X-Request-Id").unwrap().to_owned()
+    };
+    assert_eq!(request_id(proxy), "chosen-id");
+    assert_ne!(request_id("10.0.0.2".parse().unwrap()), "chosen-id");
+}
@@ -0,0 +1,123 @@
+//! Logging and request tracing.
+//!
+//! Every request is handled inside a span carrying its request id. Rocket
+//! handles a request on a single thread from start to finish, so the span is
+//! entered when the request arrives and exited once the response is ready.
+
+use crate::rate_limit::RateLimiter;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    Data,
+    Request,
+    Response,
+    State,
+};
+use std::{cell::RefCell, env, str::FromStr, sync::Arc};
+use tracing::{field, span::EnteredSpan, Level, Span};
+use uuid::Uuid;
+
+/// Header containing the request id.
+/// Ids sent by a trusted proxy are used if they're valid.
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// Maximum length of a request id sent by the client.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+thread_local! {
+    /// Request which is currently handled by this thread.
+    static CURRENT_REQUEST: RefCell<Option<(String, EnteredSpan)>> =
+        const { RefCell::new(None) };
+}
+
+/// Set up the logging according to the `PLAYGROUND_LOG_LEVEL` and
+/// `PLAYGROUND_LOG_FORMAT` environment variables.
+/// This also receives the records of the `log` crate, including Rocket's.
+pub fn init_logging() {
+    let level = env::var("PLAYGROUND_LOG_LEVEL")
+        .ok()
+        .and_then(|level| Level::from_str(&level).ok())
+        .unwrap_or(Level::INFO);
+    let builder = tracing_subscriber::fmt().with_max_level(level);
+    let result = match env::var("PLAYGROUND_LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().try_init(),
+        _ => builder.try_init(),
+    };
+    if let Err(err) = result {
+        eprintln!("failed to initialize logging: {}", err);
+    }
+}
+
+/// Id of the request handled by the current thread.
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST.with(|current| current.borrow().as_ref().map(|(id, _)| id.clone()))
+}
+
+/// Record the session the current request belongs to.
+pub fn record_session(id: &Uuid) {
+    Span::current().record("session", field::display(id.to_simple()));
+}
+
+/// Request ids end up in container names, so only a few characters are
+/// allowed.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Whether the request comes directly from one of the trusted proxies of the
+/// rate limiter.
+fn is_from_trusted_proxy(request: &Request) -> bool {
+    let remote = match request.remote() {
+        Some(remote) => remote.ip(),
+        None => return false,
+    };
+    request
+        .guard::<State<Arc<RateLimiter>>>()
+        .succeeded()
+        .is_some_and(|limiter| limiter.is_trusted_proxy(&remote))
+}
+
+/// Gives every request an id and handles it in a span.
+/// The id is returned in the `X-Request-Id` header.
+#[derive(Debug, Default)]
+pub struct RequestTracing;
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        // clients could otherwise pick the names of other requests' containers
+        let forwarded_id = if is_from_trusted_proxy(request) {
+            request.headers().get_one(REQUEST_ID_HEADER)
+        } else {
+            None
+        };
+        let id = forwarded_id
+            .filter(|id| is_valid_request_id(id))
+            .map_or_else(|| Uuid::new_v4().to_simple().to_string(), String::from);
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %request.method(),
+            uri = %request.uri(),
+            session = field::Empty,
+        );
+        CURRENT_REQUEST.with(|current| *current.borrow_mut() = Some((id, span.entered())));
+    }
+
+    fn on_response(&self, _request: &Request, response: &mut Response) {
+        let current = CURRENT_REQUEST.with(|current| current.borrow_mut().take());
+        if let Some((id, span)) = current {
+            tracing::info!(status = response.status().code, "request finished");
+            response.set_header(Header::new(REQUEST_ID_HEADER, id));
+            span.exit();
+        }
+    }
+}